@@ -2,10 +2,13 @@ use starina::channel::Channel;
 use starina::channel::RecvError;
 use starina::error::ErrorCode;
 use starina::handle::Handleable;
+use starina::handle::OwnedHandle;
+use starina::message::CallId;
 use starina::message::Message;
 use starina::message::MessageBuffer;
 use starina::poll::Poll;
 use starina::poll::Readiness;
+use starina::thread::Thread;
 
 pub fn test_channel() {
     let (ch1, ch2) = Channel::new().unwrap();
@@ -57,13 +60,25 @@ pub fn test_channel() {
     // The channel is now full. It's no longer writable.
     assert_eq!(poll1.try_wait().map(|x| x.1), Err(ErrorCode::WouldBlock));
 
+    // A handle in a message which failed to be sent stays in our process.
+    let (ch3, ch4) = Channel::new().unwrap();
+    let ch3_id = ch3.handle_id();
+    let result = ch1.send(Message::Connect { ch: ch3 });
+    assert_eq!(result, Err(ErrorCode::Full));
+    let ch3 = Channel::from_handle(OwnedHandle::from_raw(ch3_id));
+    assert_eq!(ch3.send(Message::Data { data: b"" }), Ok(()));
+    assert!(matches!(
+        ch4.recv(&mut MessageBuffer::new()),
+        Ok(Message::Data { data: b"" })
+    ));
+
     // Receive a message from the peer channel.
     let mut msgbuffer = MessageBuffer::new();
     let result = ch2.recv(&mut msgbuffer);
     assert!(matches!(result, Ok(Message::Data { data: b"" })));
 
-    // The channel is now writable.
-    assert_eq!(poll1.try_wait().map(|x| x.1), Ok(Readiness::WRITABLE));
+    // The channel is not writable until the peer's queue drains enough.
+    assert_eq!(poll1.try_wait().map(|x| x.1), Err(ErrorCode::WouldBlock));
 
     // Drain the peer channel.
     loop {
@@ -84,3 +99,56 @@ pub fn test_channel() {
     // The peer channel has no messages to receive.
     assert_eq!(poll2.try_wait().map(|x| x.1), Ok(Readiness::WRITABLE));
 }
+
+pub fn test_channel_call() {
+    let (client, server) = Channel::new().unwrap();
+    let server_thread = Thread::spawn(move || {
+        let poll = Poll::new().unwrap();
+        poll.add(server.handle_id(), (), Readiness::READABLE)
+            .unwrap();
+        poll.wait().unwrap();
+
+        let mut msgbuffer = MessageBuffer::new();
+        let Ok(Message::Open { call_id, uri }) = server.recv(&mut msgbuffer) else {
+            panic!("unexpected message");
+        };
+        assert_eq!(uri, b"test");
+
+        // A message sent before the reply is not consumed by the call, even
+        // if its data looks like the call ID.
+        server.send(Message::Data { data: b"hello" }).unwrap();
+        server
+            .send(Message::Data {
+                data: &1u32.to_ne_bytes(),
+            })
+            .unwrap();
+        server
+            .send(Message::Abort {
+                call_id,
+                reason: ErrorCode::NotSupported,
+            })
+            .unwrap();
+    })
+    .unwrap();
+
+    let call_id = CallId::from(1);
+    let mut msgbuffer = MessageBuffer::new();
+    let reply = client.call(
+        Message::Open {
+            call_id,
+            uri: b"test",
+        },
+        &mut msgbuffer,
+    );
+    assert!(matches!(
+        reply,
+        Ok(Message::Abort { call_id: id, reason: ErrorCode::NotSupported }) if id == call_id
+    ));
+
+    let result = client.recv(&mut msgbuffer);
+    assert!(matches!(result, Ok(Message::Data { data: b"hello" })));
+    let result = client.recv(&mut msgbuffer);
+    assert!(matches!(result, Ok(Message::Data { data }) if data == 1u32.to_ne_bytes()));
+
+    server_thread.join().unwrap();
+}
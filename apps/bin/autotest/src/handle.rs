@@ -0,0 +1,122 @@
+use starina::channel::Channel;
+use starina::error::ErrorCode;
+use starina::folio::Folio;
+use starina::handle;
+use starina::handle::HandleId;
+use starina::handle::HandleKind;
+use starina::handle::HandleRights;
+use starina::handle::Handleable;
+use starina::handle::OwnedHandle;
+use starina::message::Message;
+use starina::message::MessageBuffer;
+use starina::poll::Poll;
+use starina::poll::Readiness;
+use starina::syscall;
+
+pub fn test_handle() {
+    let (ch1, ch2) = Channel::new().unwrap();
+    let poll2 = Poll::new().unwrap();
+    poll2
+        .add(ch2.handle_id(), (), Readiness::WRITABLE | Readiness::CLOSED)
+        .unwrap();
+
+    // A read-only duplicate can't send messages.
+    let read_only = syscall::handle_duplicate(ch1.handle_id(), HandleRights::READ).unwrap();
+    let read_only = Channel::from_handle(OwnedHandle::from_raw(read_only));
+    let result = read_only.send(Message::Data { data: b"" });
+    assert_eq!(result, Err(ErrorCode::NotAllowed));
+
+    // Rights can't be escalated by duplicating a duplicate.
+    let dup = syscall::handle_duplicate(read_only.handle_id(), HandleRights::ALL).unwrap();
+    let dup = Channel::from_handle(OwnedHandle::from_raw(dup));
+    let result = dup.send(Message::Data { data: b"" });
+    assert_eq!(result, Err(ErrorCode::NotAllowed));
+
+    // Closing the original handle doesn't close the channel as long as a
+    // duplicate is still open.
+    drop(ch1);
+    assert_eq!(poll2.try_wait().map(|x| x.1), Ok(Readiness::WRITABLE));
+    drop(read_only);
+    assert_eq!(poll2.try_wait().map(|x| x.1), Ok(Readiness::WRITABLE));
+    drop(dup);
+    assert_eq!(poll2.try_wait().map(|x| x.1), Ok(Readiness::CLOSED));
+
+    // Attenuate a handle when sending it over a channel.
+    let (ch1, ch2) = Channel::new().unwrap();
+    let (ch3, _ch4) = Channel::new().unwrap();
+    ch1.send_with_rights(
+        Message::Connect { ch: ch3 },
+        &[HandleRights::READ | HandleRights::POLL],
+    )
+    .unwrap();
+
+    let mut msgbuffer = MessageBuffer::new();
+    let Ok(Message::Connect { ch: received }) = ch2.recv(&mut msgbuffer) else {
+        panic!("unexpected message");
+    };
+
+    let result = received.send(Message::Data { data: b"" });
+    assert_eq!(result, Err(ErrorCode::NotAllowed));
+
+    // A poll-only handle can still be added to a poll.
+    let poll = Poll::new().unwrap();
+    poll.add(received.handle_id(), (), Readiness::READABLE)
+        .unwrap();
+
+    // A stale ID of a closed handle doesn't refer to a new handle, even if
+    // its slot is reused.
+    let (ch1, _ch2) = Channel::new().unwrap();
+    let stale = ch1.handle_id();
+    drop(ch1);
+    let (ch3, _ch4) = Channel::new().unwrap();
+    assert_ne!(ch3.handle_id(), stale);
+    assert_eq!(
+        syscall::handle_duplicate(stale, HandleRights::ALL),
+        Err(ErrorCode::InvalidHandle)
+    );
+}
+
+pub fn test_handle_list() {
+    let current = HandleId::from_raw(0);
+    let (ch1, ch2) = Channel::new().unwrap();
+    ch1.send(Message::Data { data: b"hello" }).unwrap();
+    let folio = Folio::alloc(0x2000).unwrap();
+
+    let infos = handle::list(current).unwrap();
+    let find = |id| infos.iter().find(|info| info.id == id).unwrap();
+
+    let ch2_info = find(ch2.handle_id());
+    assert_eq!(
+        ch2_info.kind,
+        HandleKind::Channel {
+            queue_len: 1,
+            peer_alive: true
+        }
+    );
+    assert_eq!(
+        ch2_info.readiness,
+        Some(Readiness::READABLE | Readiness::WRITABLE)
+    );
+
+    // Folio handles are not pollable.
+    let folio_info = find(folio.handle_id());
+    assert_eq!(folio_info.kind, HandleKind::Folio { len: 0x2000 });
+    assert_eq!(folio_info.readiness, None);
+
+    // Closed handles disappear, and the peer notices it.
+    let ch1_id = ch1.handle_id();
+    drop(ch1);
+    let infos = handle::list(current).unwrap();
+    assert!(infos.iter().all(|info| info.id != ch1_id));
+    let ch2_info = infos
+        .iter()
+        .find(|info| info.id == ch2.handle_id())
+        .unwrap();
+    assert_eq!(
+        ch2_info.kind,
+        HandleKind::Channel {
+            queue_len: 1,
+            peer_alive: false
+        }
+    );
+}
@@ -1,22 +1,66 @@
 #![no_std]
 
 mod channel;
+mod handle;
+mod log;
+mod memory;
+mod poll;
+mod shared_ring;
+mod thread;
+mod timer;
+mod vmo;
+mod vmspace;
 
 use starina::environ::Environ;
 use starina::prelude::*;
 use starina::spec::AppSpec;
+use starina::thread::Priority;
 
 use crate::channel::test_channel;
+use crate::channel::test_channel_call;
+use crate::handle::test_handle;
+use crate::handle::test_handle_list;
+use crate::log::test_log_read;
+use crate::memory::test_memory_usage;
+use crate::poll::test_poll;
+use crate::shared_ring::test_shared_ring;
+use crate::thread::test_preemption;
+use crate::thread::test_thread;
+use crate::thread::test_thread_builder;
+use crate::timer::test_timer;
+use crate::vmo::test_vmo;
+use crate::vmspace::test_vmspace;
+
+/// Small enough to test the limit, large enough for all tests.
+pub const MEMORY_LIMIT: usize = 32 * 1024 * 1024;
 
 pub const SPEC: AppSpec = AppSpec {
     name: "autotest",
     env: &[],
     exports: &[],
+    priority: Priority::NORMAL,
+    memory_limit: Some(MEMORY_LIMIT),
+    handle_limit: None,
     main,
 };
 
 fn main(_environ: Environ) {
     info!("Starting tests...");
+    // Run this first: threads spawned by other tests release their stacks
+    // asynchronously, which would make the memory usage fluctuate.
+    test_memory_usage();
     test_channel();
+    test_channel_call();
+    test_handle();
+    test_handle_list();
+    test_log_read();
+    test_poll();
+    test_preemption();
+    test_shared_ring();
+    test_thread();
+    test_thread_builder();
+    test_timer();
+    test_vmo();
+    test_vmspace();
     info!("Passed all tests!");
 }
@@ -0,0 +1,45 @@
+use starina::error::ErrorCode;
+use starina::log::LOG_RECORD_LEN_MAX;
+use starina::prelude::*;
+use starina::syscall;
+
+/// Reads the kernel log from `cursor` until the end. Returns the contents
+/// and the next cursor.
+fn read_until_end(mut cursor: u64) -> (Vec<u8>, u64) {
+    let mut contents = Vec::new();
+    let mut buf = [0; LOG_RECORD_LEN_MAX * 4];
+    loop {
+        let result = syscall::log_read(cursor, &mut buf).unwrap();
+        if result.len == 0 {
+            assert_eq!(result.next_cursor, cursor);
+            return (contents, cursor);
+        }
+
+        contents.extend_from_slice(&buf[..result.len]);
+        cursor = result.next_cursor;
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+pub fn test_log_read() {
+    let (_, start) = read_until_end(0);
+
+    info!("log_read test marker");
+    let (contents, end) = read_until_end(start);
+    assert!(contains(&contents, b"log_read test marker"));
+    assert!(end > start);
+
+    // Reading does not consume records: another reader sees them too.
+    let (contents, _) = read_until_end(start);
+    assert!(contains(&contents, b"log_read test marker"));
+
+    // A cursor from the future is invalid.
+    let mut buf = [0; LOG_RECORD_LEN_MAX];
+    assert_eq!(
+        syscall::log_read(u64::MAX >> 1, &mut buf).err(),
+        Some(ErrorCode::InvalidArg)
+    );
+}
@@ -0,0 +1,32 @@
+use starina::error::ErrorCode;
+use starina::folio::Folio;
+use starina::process;
+
+use crate::MEMORY_LIMIT;
+
+pub fn test_memory_usage() {
+    // Stack pages are charged too when they're touched for the first time.
+    // Go through the same code path once so that the stack usage doesn't
+    // change while measuring.
+    drop(Folio::alloc(0x4000).unwrap());
+    let _ = process::memory_usage().unwrap();
+
+    let before = process::memory_usage().unwrap();
+    assert_eq!(before.limit, Some(MEMORY_LIMIT));
+
+    // Folios are charged until they're freed.
+    let folio = Folio::alloc(0x4000).unwrap();
+    let usage = process::memory_usage().unwrap();
+    assert!(usage.folio_bytes >= before.folio_bytes + 0x4000);
+    assert!(usage.object_bytes > before.object_bytes);
+
+    drop(folio);
+    assert_eq!(process::memory_usage().unwrap(), before);
+
+    // Allocations beyond the limit fail without affecting other apps.
+    assert_eq!(
+        Folio::alloc(MEMORY_LIMIT).err(),
+        Some(ErrorCode::OutOfMemory)
+    );
+    assert_eq!(process::memory_usage().unwrap(), before);
+}
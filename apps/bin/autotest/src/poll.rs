@@ -0,0 +1,55 @@
+use core::time::Duration;
+
+use starina::channel::Channel;
+use starina::error::ErrorCode;
+use starina::handle::HandleId;
+use starina::handle::Handleable;
+use starina::message::Message;
+use starina::poll::PollEvent;
+use starina::poll::PollMode;
+use starina::poll::RawPoll;
+use starina::poll::Readiness;
+use starina::timer;
+
+pub fn test_poll() {
+    // Waiting on a poll with no events times out.
+    let poll = RawPoll::create().unwrap();
+    let deadline = timer::now() + Duration::from_millis(10);
+    assert_eq!(poll.wait_until(deadline), Err(ErrorCode::TimedOut));
+
+    // Multiple ready handles are returned in a single wait.
+    let (ch1, ch2) = Channel::new().unwrap();
+    let (ch3, ch4) = Channel::new().unwrap();
+    poll.add(ch2.handle_id(), Readiness::READABLE).unwrap();
+    poll.add(ch4.handle_id(), Readiness::READABLE).unwrap();
+    ch1.send(Message::Data { data: b"1" }).unwrap();
+    ch3.send(Message::Data { data: b"3" }).unwrap();
+
+    let mut events = [PollEvent::new(HandleId::from_raw(0), Readiness::NONE); 4];
+    let len = poll.wait_many(&mut events, None).unwrap();
+    assert_eq!(len, 2);
+    for event in &events[..len] {
+        assert!(event.handle_id() == ch2.handle_id() || event.handle_id() == ch4.handle_id());
+        assert_eq!(event.readiness(), Readiness::READABLE);
+    }
+
+    // A one-shot handle is reported once until it's re-armed.
+    let poll = RawPoll::create().unwrap();
+    poll.add_with_mode(ch2.handle_id(), Readiness::READABLE, PollMode::OneShot)
+        .unwrap();
+    assert_eq!(poll.try_wait(), Ok((ch2.handle_id(), Readiness::READABLE)));
+    ch1.send(Message::Data { data: b"1" }).unwrap();
+    assert_eq!(poll.try_wait(), Err(ErrorCode::WouldBlock));
+    poll.update(ch2.handle_id(), Readiness::READABLE, Readiness::ALL)
+        .unwrap();
+    assert_eq!(poll.try_wait(), Ok((ch2.handle_id(), Readiness::READABLE)));
+
+    // An edge-triggered handle is reported again only on a new event.
+    poll.add_with_mode(ch4.handle_id(), Readiness::READABLE, PollMode::Edge)
+        .unwrap();
+    assert_eq!(poll.try_wait(), Ok((ch4.handle_id(), Readiness::READABLE)));
+    assert_eq!(poll.try_wait(), Err(ErrorCode::WouldBlock));
+    ch3.send(Message::Data { data: b"3" }).unwrap();
+    assert_eq!(poll.try_wait(), Ok((ch4.handle_id(), Readiness::READABLE)));
+    assert_eq!(poll.try_wait(), Err(ErrorCode::WouldBlock));
+}
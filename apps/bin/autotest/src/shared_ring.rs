@@ -0,0 +1,65 @@
+use starina::error::ErrorCode;
+use starina::handle::HandleRights;
+use starina::shared_ring::SharedRing;
+use starina::vmspace::PageProtect;
+use starina::vmspace::VmSpace;
+
+const RING_LEN: usize = 0x1000;
+
+pub fn test_shared_ring() {
+    let mut producer = SharedRing::create(RING_LEN).unwrap();
+    let rw = HandleRights::READ | HandleRights::WRITE | HandleRights::MAP;
+    let folio = producer.folio().duplicate(rw).unwrap();
+
+    // The same folio is mapped twice: as if it's shared with another process.
+    let mut consumer = SharedRing::from_folio(folio, RING_LEN).unwrap();
+    assert_eq!(consumer.pop_with(|_| ()), Err(ErrorCode::Empty));
+
+    producer.push(b"hello").unwrap();
+    producer.push(b"world!").unwrap();
+    assert_eq!(consumer.pop_with(|data| data == b"hello"), Ok(true));
+
+    let mut small = [0; 2];
+    assert_eq!(consumer.pop(&mut small), Err(ErrorCode::TooSmall));
+    let mut buf = [0; 16];
+    assert_eq!(consumer.pop(&mut buf), Ok(6));
+    assert_eq!(&buf[..6], b"world!");
+
+    // Records wrap around the end of the ring without being split.
+    let record = [0xa5; 1200];
+    for _ in 0..16 {
+        producer.push(&record).unwrap();
+        producer.push(&record).unwrap();
+        assert_eq!(producer.push(&record), Ok(()));
+        assert_eq!(producer.push(&record), Err(ErrorCode::Full));
+        for _ in 0..3 {
+            assert_eq!(consumer.pop_with(|data| data == record), Ok(true));
+        }
+    }
+
+    assert_eq!(producer.push(&[0; RING_LEN]), Err(ErrorCode::TooLarge));
+
+    // A handle without WRITE can only be mapped read-only.
+    let read_only = producer
+        .folio()
+        .duplicate(HandleRights::READ | HandleRights::MAP)
+        .unwrap();
+    assert_eq!(
+        VmSpace::map_anywhere_current(
+            &read_only,
+            RING_LEN,
+            PageProtect::READABLE | PageProtect::WRITEABLE
+        ),
+        Err(ErrorCode::NotAllowed)
+    );
+    let vaddr = VmSpace::map_anywhere_current(&read_only, RING_LEN, PageProtect::READABLE).unwrap();
+    assert_eq!(
+        VmSpace::protect_current(
+            vaddr,
+            RING_LEN,
+            PageProtect::READABLE | PageProtect::WRITEABLE
+        ),
+        Err(ErrorCode::NotAllowed)
+    );
+    VmSpace::unmap_current(vaddr, RING_LEN).unwrap();
+}
@@ -0,0 +1,84 @@
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+
+use starina::error::ErrorCode;
+use starina::sync::Arc;
+use starina::sync::Mutex;
+use starina::thread::Thread;
+
+pub fn test_thread() {
+    let done = Arc::new(AtomicBool::new(false));
+
+    let thread = Thread::spawn({
+        let done = done.clone();
+        move || {
+            done.store(true, Ordering::SeqCst);
+        }
+    })
+    .unwrap();
+
+    // join returns after the thread has exited.
+    thread.join().unwrap();
+    assert!(done.load(Ordering::SeqCst));
+}
+
+pub fn test_thread_builder() {
+    // A stack much larger than the default.
+    const STACK_SIZE: usize = 8 * 1024 * 1024;
+
+    let sum = Arc::new(AtomicUsize::new(0));
+    let thread = Thread::builder()
+        .name("autotest-worker")
+        .stack_size(STACK_SIZE)
+        .spawn({
+            let sum = sum.clone();
+            move || {
+                // Use more stack than the default size.
+                let buf = [1u8; 2 * 1024 * 1024];
+                let total = core::hint::black_box(&buf)
+                    .iter()
+                    .map(|b| *b as usize)
+                    .sum();
+                sum.store(total, Ordering::SeqCst);
+            }
+        })
+        .unwrap();
+
+    thread.join().unwrap();
+    assert_eq!(sum.load(Ordering::SeqCst), 2 * 1024 * 1024);
+
+    assert_eq!(
+        Thread::builder().stack_size(0).spawn(|| {}).err(),
+        Some(ErrorCode::InvalidArg)
+    );
+}
+
+pub fn test_preemption() {
+    // Neither thread below makes system calls: they only make progress if
+    // the timer preempts them. The worker spends most of its time holding
+    // the lock, so the timer often fires while it's held.
+    let counter = Arc::new(Mutex::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = Thread::spawn({
+        let counter = counter.clone();
+        let stop = stop.clone();
+        move || {
+            while !stop.load(Ordering::Relaxed) {
+                let mut counter = counter.lock();
+                *counter += 1;
+                for _ in 0..1000 {
+                    core::hint::spin_loop();
+                }
+            }
+        }
+    })
+    .unwrap();
+
+    while *counter.lock() < 1000 {
+        core::hint::spin_loop();
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    thread.join().unwrap();
+}
@@ -0,0 +1,33 @@
+use core::time::Duration;
+
+use starina::error::ErrorCode;
+use starina::handle::Handleable;
+use starina::poll::RawPoll;
+use starina::poll::Readiness;
+use starina::timer;
+use starina::timer::Timer;
+
+pub fn test_timer() {
+    let timer = Timer::new().unwrap();
+    let poll = RawPoll::create().unwrap();
+    poll.add(timer.handle_id(), Readiness::READABLE).unwrap();
+
+    // A deadline in the past expires immediately.
+    timer.set_deadline(timer::now()).unwrap();
+    assert_eq!(poll.wait(), Ok((timer.handle_id(), Readiness::READABLE)));
+    assert_eq!(timer.ack(), Ok(1));
+    assert_eq!(poll.try_wait(), Err(ErrorCode::WouldBlock));
+
+    // A periodic timer counts missed periods.
+    timer
+        .set_periodic(timer::now(), Duration::from_millis(1))
+        .unwrap();
+    let idle = RawPoll::create().unwrap();
+    let deadline = timer::now() + Duration::from_millis(10);
+    assert_eq!(idle.wait_until(deadline), Err(ErrorCode::TimedOut));
+    assert!(timer.ack().unwrap() >= 2);
+
+    // The timer keeps expiring after the acknowledgement.
+    assert_eq!(poll.wait(), Ok((timer.handle_id(), Readiness::READABLE)));
+    assert!(timer.ack().unwrap() >= 1);
+}
@@ -0,0 +1,27 @@
+use starina::vmo::Vmo;
+use starina::vmspace::PageProtect;
+use starina::vmspace::VmSpace;
+
+const LEN: usize = 0x4000;
+
+pub fn test_vmo() {
+    let rw = PageProtect::READABLE | PageProtect::WRITEABLE;
+    let vmo = Vmo::create(LEN).unwrap();
+    let a = VmSpace::map_anywhere_current(&vmo, LEN, rw).unwrap();
+    let b = VmSpace::map_anywhere_current(&vmo, LEN, rw).unwrap();
+
+    // Pages are zero-filled on first access.
+    assert_eq!(unsafe { *a.add(0x3000).as_ptr::<u64>() }, 0);
+
+    // Both mappings share the same pages, no matter which one touches a
+    // page first.
+    unsafe {
+        *a.add(0x1000).as_mut_ptr::<u64>() = 0xdead_beef;
+        *b.add(0x2000).as_mut_ptr::<u64>() = 0xcafe_babe;
+    }
+    assert_eq!(unsafe { *b.add(0x1000).as_ptr::<u64>() }, 0xdead_beef);
+    assert_eq!(unsafe { *a.add(0x2000).as_ptr::<u64>() }, 0xcafe_babe);
+
+    VmSpace::unmap_current(a, LEN).unwrap();
+    VmSpace::unmap_current(b, LEN).unwrap();
+}
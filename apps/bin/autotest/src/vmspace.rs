@@ -0,0 +1,48 @@
+use starina::address::VAddr;
+use starina::error::ErrorCode;
+use starina::folio::Folio;
+use starina::vmspace::PageProtect;
+use starina::vmspace::VmSpace;
+
+const FIXED_VADDR: VAddr = VAddr::new(0x0000_000a_0000_0000);
+const LEN: usize = 0x2000;
+
+pub fn test_vmspace() {
+    let rw = PageProtect::READABLE | PageProtect::WRITEABLE;
+    let folio = Folio::alloc(LEN).unwrap();
+    VmSpace::map_fixed_current(FIXED_VADDR, &folio, LEN, rw).unwrap();
+    unsafe {
+        *FIXED_VADDR.as_mut_ptr::<u64>() = 0xdead_beef;
+    }
+
+    // Overlapping mappings are rejected.
+    let other = Folio::alloc(LEN).unwrap();
+    assert_eq!(
+        VmSpace::map_fixed_current(FIXED_VADDR.add(0x1000), &other, LEN, rw),
+        Err(ErrorCode::AlreadyMapped)
+    );
+
+    // Protection can be changed within the mapping, but not beyond it.
+    VmSpace::protect_current(FIXED_VADDR, 0x1000, PageProtect::READABLE).unwrap();
+    assert_eq!(unsafe { *FIXED_VADDR.as_ptr::<u64>() }, 0xdead_beef);
+    assert_eq!(
+        VmSpace::protect_current(FIXED_VADDR, 2 * LEN, PageProtect::READABLE),
+        Err(ErrorCode::NotFound)
+    );
+
+    // Only a whole mapping can be unmapped.
+    assert_eq!(
+        VmSpace::unmap_current(FIXED_VADDR, 0x1000),
+        Err(ErrorCode::InvalidArg)
+    );
+    VmSpace::unmap_current(FIXED_VADDR, LEN).unwrap();
+    assert_eq!(
+        VmSpace::unmap_current(FIXED_VADDR, LEN),
+        Err(ErrorCode::NotFound)
+    );
+
+    // The range is free again.
+    VmSpace::map_fixed_current(FIXED_VADDR, &other, LEN, rw).unwrap();
+    assert_eq!(unsafe { *FIXED_VADDR.as_ptr::<u64>() }, 0);
+    VmSpace::unmap_current(FIXED_VADDR, LEN).unwrap();
+}
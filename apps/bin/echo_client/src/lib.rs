@@ -20,6 +20,8 @@ use starina::prelude::*;
 use starina::spec::AppSpec;
 use starina::spec::EnvItem;
 use starina::spec::EnvType;
+use starina::thread::Priority;
+use starina::timer;
 use starina::timer::Timer;
 
 pub const SPEC: AppSpec = AppSpec {
@@ -29,6 +31,9 @@ pub const SPEC: AppSpec = AppSpec {
         ty: EnvType::Service { service: "echo" },
     }],
     exports: &[],
+    priority: Priority::NORMAL,
+    memory_limit: None,
+    handle_limit: None,
     main,
 };
 
@@ -49,7 +54,9 @@ fn main(environ: Environ) {
     let (echo_tx, echo_rx) = env.echo.split();
     let timer = Timer::new().unwrap();
 
-    timer.set_timeout(Duration::from_millis(0)).unwrap();
+    timer
+        .set_periodic(timer::now(), Duration::from_millis(2000))
+        .unwrap();
 
     poll.add(
         echo_rx.handle_id(),
@@ -95,6 +102,11 @@ fn main(environ: Environ) {
                 break;
             }
             State::Timer(timer) if readiness.contains(Readiness::READABLE) => {
+                let expirations = timer.ack().unwrap();
+                if expirations > 1 {
+                    debug_warn!("missed {} timer ticks", expirations - 1);
+                }
+
                 let message = format!("value {}", counter);
                 echo_tx
                     .send(Message::Data {
@@ -103,7 +115,6 @@ fn main(environ: Environ) {
                     .unwrap();
 
                 counter += 1;
-                timer.set_timeout(Duration::from_millis(2000)).unwrap();
             }
             _ => {
                 debug_warn!("unexpected readiness: {:?}", readiness);
@@ -3,11 +3,15 @@
 use starina::environ::Environ;
 use starina::prelude::*;
 use starina::spec::AppSpec;
+use starina::thread::Priority;
 
 pub const SPEC: AppSpec = AppSpec {
     name: "hello",
     env: &[],
     exports: &[],
+    priority: Priority::NORMAL,
+    memory_limit: None,
+    handle_limit: None,
     main,
 };
 
@@ -10,6 +10,7 @@ use starina::spec::AppSpec;
 use starina::spec::EnvItem;
 use starina::spec::EnvType;
 use starina::spec::ExportItem;
+use starina::thread::Priority;
 use starina_linux::BufferedStdin;
 use starina_linux::BufferedStdout;
 use starina_linux::ContainerImage;
@@ -27,6 +28,9 @@ pub const SPEC: AppSpec = AppSpec {
     exports: &[ExportItem::Service {
         service: "linuxrun",
     }],
+    priority: Priority::LOW,
+    memory_limit: None,
+    handle_limit: None,
     main,
 };
 
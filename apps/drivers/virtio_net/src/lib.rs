@@ -21,6 +21,7 @@ use starina::spec::EnvItem;
 use starina::spec::EnvType;
 use starina::spec::ExportItem;
 use starina::sync::Mutex;
+use starina::thread::Priority;
 use virtio_net::VirtioNet;
 
 mod virtio_net;
@@ -36,6 +37,9 @@ pub const SPEC: AppSpec = AppSpec {
     exports: &[ExportItem::Service {
         service: "device/ethernet",
     }],
+    priority: Priority::HIGH,
+    memory_limit: None,
+    handle_limit: None,
     main: starina::mainloop::run::<App, Env>,
 };
 
@@ -0,0 +1,54 @@
+use serde::Serialize;
+use starina::handle;
+use starina::handle::HandleId;
+use starina::handle::HandleKind;
+use starina::prelude::*;
+
+use crate::http::HeaderName;
+use crate::http::Request;
+use crate::http::ResponseWriter;
+use crate::http::StatusCode;
+
+#[derive(Serialize)]
+struct HandleJson {
+    id: i32,
+    rights: String,
+    readiness: Option<String>,
+    object: HandleKind,
+}
+
+/// Lists the handles owned by the API server, e.g. to find leaked
+/// connections.
+pub fn handle_handles(_req: &Request, resp: &mut impl ResponseWriter) -> anyhow::Result<()> {
+    let current = HandleId::from_raw(0); /* current process */
+    let infos = match handle::list(current) {
+        Ok(infos) => infos,
+        Err(err) => {
+            resp.write_headers(StatusCode::new(500).unwrap());
+            resp.write_body(format!("failed to list handles: {err:?}"));
+            return Ok(());
+        }
+    };
+
+    let handles: Vec<HandleJson> = infos
+        .into_iter()
+        .map(|info| {
+            HandleJson {
+                id: info.id.as_raw(),
+                rights: format!("{:?}", info.rights),
+                readiness: info.readiness.map(|r| format!("{r:?}")),
+                object: info.kind,
+            }
+        })
+        .collect();
+
+    let body = serde_json::to_vec(&handles)?;
+
+    let headers = resp.headers_mut();
+    headers.insert(HeaderName::CONTENT_TYPE, "application/json")?;
+
+    resp.write_headers(StatusCode::OK);
+    resp.write_body(body);
+
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use starina::prelude::*;
 use starina::syscall;
 
 use crate::http::HeaderName;
@@ -5,10 +6,26 @@ use crate::http::Request;
 use crate::http::ResponseWriter;
 use crate::http::StatusCode;
 
-pub fn handle_logs(_req: &Request, resp: &mut impl ResponseWriter) -> anyhow::Result<()> {
-    let mut buffer = [0; 4096];
-    let read_len = match syscall::log_read(&mut buffer) {
-        Ok(len) => len,
+const NEXT_CURSOR: HeaderName = HeaderName::from_static("x-log-next-cursor");
+const DROPPED: HeaderName = HeaderName::from_static("x-log-dropped");
+
+/// Returns kernel log records after the `cursor` query parameter (or from
+/// the oldest one). The kernel log is not consumed, so each client tails
+/// it with its own cursor, taken from the `x-log-next-cursor` header.
+pub fn handle_logs(req: &Request, resp: &mut impl ResponseWriter) -> anyhow::Result<()> {
+    let cursor = match req.query.get("cursor").map(|s| s.parse::<u64>()) {
+        Some(Ok(cursor)) => cursor,
+        Some(Err(_)) => {
+            resp.write_headers(StatusCode::new(400).unwrap());
+            resp.write_body("invalid cursor");
+            return Ok(());
+        }
+        None => 0,
+    };
+
+    let mut buffer = vec![0; 16 * 1024];
+    let result = match syscall::log_read(cursor, &mut buffer) {
+        Ok(result) => result,
         Err(_) => {
             resp.write_headers(StatusCode::new(500).unwrap());
             resp.write_body("failed to read logs");
@@ -18,9 +35,11 @@ pub fn handle_logs(_req: &Request, resp: &mut impl ResponseWriter) -> anyhow::Re
 
     let headers = resp.headers_mut();
     headers.insert(HeaderName::CONTENT_TYPE, "text/plain")?;
+    headers.insert(NEXT_CURSOR, format!("{}", result.next_cursor).as_str())?;
+    headers.insert(DROPPED, format!("{}", result.dropped).as_str())?;
 
     resp.write_headers(StatusCode::new(200).unwrap());
-    resp.write_body(&buffer[..read_len]);
+    resp.write_body(&buffer[..result.len]);
 
     Ok(())
 }
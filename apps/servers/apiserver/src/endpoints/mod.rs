@@ -7,6 +7,7 @@ use crate::http::ResponseWriter;
 use crate::http::StatusCode;
 
 pub mod big;
+pub mod handles;
 pub mod index;
 pub mod logs;
 
@@ -14,6 +15,7 @@ pub fn route(req: &Request, resp: &mut impl ResponseWriter) -> anyhow::Result<()
     match (&req.method, req.path.as_str()) {
         (Method::Get, "/") => index::handle_index(req, resp),
         (Method::Get, "/big") => big::handle_big(req, resp),
+        (Method::Get, "/handles") => handles::handle_handles(req, resp),
         (Method::Get, "/logs") => logs::handle_logs(req, resp),
         _ => {
             error(resp, StatusCode::new(404).unwrap(), "Route not found");
@@ -0,0 +1,42 @@
+use starina::prelude::*;
+use starina::time::SystemTime;
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Formats the time in the HTTP date format (IMF-fixdate), e.g.
+/// `Sun, 06 Nov 1994 08:49:37 GMT`.
+///
+/// <https://www.rfc-editor.org/rfc/rfc9110#section-5.6.7>
+pub fn format_http_date(time: SystemTime) -> String {
+    let dt = time.to_utc();
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[dt.weekday as usize],
+        dt.day,
+        MONTHS[dt.month as usize - 1],
+        dt.year,
+        dt.hour,
+        dt.minute,
+        dt.second
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_http_date() {
+        let time = SystemTime::from_nanos(784_111_777 * 1_000_000_000);
+        assert_eq!(format_http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+    }
+
+    #[test]
+    fn test_format_http_date_leap_day() {
+        let time = SystemTime::from_nanos(1_709_164_800 * 1_000_000_000);
+        assert_eq!(format_http_date(time), "Thu, 29 Feb 2024 00:00:00 GMT");
+    }
+}
@@ -1,3 +1,4 @@
+mod date;
 mod headers;
 mod method;
 mod parser;
@@ -5,6 +6,7 @@ mod request;
 mod response;
 mod status;
 
+pub use date::format_http_date;
 pub use headers::HeaderName;
 pub use headers::Headers;
 pub use method::Method;
@@ -31,6 +31,13 @@ impl Query {
 
         Self { params }
     }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
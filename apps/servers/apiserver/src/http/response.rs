@@ -3,9 +3,11 @@ use starina::error::ErrorCode;
 use starina::message::MESSAGE_DATA_LEN_MAX;
 use starina::message::Message;
 use starina::prelude::*;
+use starina::time::SystemTime;
 
 use crate::http::Headers;
 use crate::http::StatusCode;
+use crate::http::format_http_date;
 
 #[derive(Debug)]
 pub enum TryFlushResult {
@@ -113,6 +115,9 @@ impl ResponseWriter for BufferedResponseWriter {
                     let status_code = status.unwrap_or(StatusCode::OK);
                     let mut response = format!("HTTP/1.1 {}\r\n", status_code.as_u16());
                     response.push_str("Connection: close\r\n");
+                    if let Ok(now) = SystemTime::now() {
+                        response.push_str(&format!("Date: {}\r\n", format_http_date(now)));
+                    }
 
                     for (name, value) in headers.iter() {
                         response.push_str(&format!("{}: {}\r\n", name, value));
@@ -22,6 +22,7 @@ use starina::spec::AppSpec;
 use starina::spec::EnvItem;
 use starina::spec::EnvType;
 use starina::sync::Mutex;
+use starina::thread::Priority;
 
 use crate::http::BufferedResponseWriter;
 use crate::http::HeaderName;
@@ -35,6 +36,10 @@ pub const SPEC: AppSpec = AppSpec {
         ty: EnvType::Service { service: "tcpip" },
     }],
     exports: &[],
+    priority: Priority::NORMAL,
+    memory_limit: None,
+    // Each TCP connection consumes handles.
+    handle_limit: Some(4096),
     main,
 };
 
@@ -49,7 +54,6 @@ struct Client {
 }
 
 enum State {
-    Tcpip(ChannelReceiver),
     Listen(Channel),
     Data {
         client: Mutex<Client>,
@@ -62,54 +66,22 @@ fn main(environ: Environ) {
 
     let mut msgbuffer = MessageBuffer::new();
     let poll = Poll::new().unwrap();
-    let (tcpip_tx, tcpip_rx) = env.tcpip.split();
-
-    poll.add(
-        tcpip_rx.handle_id(),
-        State::Tcpip(tcpip_rx),
-        Readiness::READABLE | Readiness::CLOSED,
-    )
-    .unwrap();
 
     let open_call_id = CallId::from(1);
     let uri = b"tcp-listen:0.0.0.0:80";
-    tcpip_tx
-        .send(Message::Open {
+    let listen_ch = match env.tcpip.call(
+        Message::Open {
             call_id: open_call_id,
             uri,
-        })
-        .unwrap();
-
-    let listen_ch = 'initloop: loop {
-        let (state, readiness) = poll.wait().unwrap();
-        match &*state {
-            State::Tcpip(ch) if readiness.contains(Readiness::READABLE) => {
-                match ch.recv(&mut msgbuffer) {
-                    Ok(Message::OpenReply { call_id, ch }) => {
-                        assert_eq!(call_id, open_call_id);
-                        break 'initloop ch;
-                    }
-                    Ok(msg) => {
-                        debug_warn!("unexpected message on tcpip channel: {:?}", msg);
-                    }
-                    Err(RecvError::Parse(msginfo)) => {
-                        debug_warn!(
-                            "malformed message on tcpip channel: {}",
-                            msginfo.kind()
-                        );
-                    }
-                    Err(RecvError::Syscall(ErrorCode::Empty)) => {}
-                    Err(RecvError::Syscall(err)) => {
-                        debug_warn!("recv error on tcpip channel: {:?}", err);
-                    }
-                }
-            }
-            _ => {
-                panic!(
-                    "unexpected readiness during initialization: {:?}",
-                    readiness
-                );
-            }
+        },
+        &mut msgbuffer,
+    ) {
+        Ok(Message::OpenReply { ch, .. }) => ch,
+        Ok(msg) => {
+            panic!("unexpected reply to open: {:?}", msg);
+        }
+        Err(err) => {
+            panic!("failed to open listen channel: {:?}", err);
         }
     };
 
@@ -231,7 +203,10 @@ fn main(environ: Environ) {
                         debug_warn!("unexpected message on data channel: {:?}", msg);
                     }
                     Err(RecvError::Parse(msginfo)) => {
-                        debug_warn!("malformed message on data channel: {}", msginfo.kind());
+                        debug_warn!(
+                            "malformed message on data channel: {}",
+                            msginfo.kind()
+                        );
                     }
                     Err(RecvError::Syscall(ErrorCode::Empty)) => {}
                     Err(RecvError::Syscall(err)) => {
@@ -266,9 +241,6 @@ fn main(environ: Environ) {
             State::Data { .. } => {
                 panic!("unexpected readiness for data channel: {:?}", readiness);
             }
-            State::Tcpip(_) => {
-                debug_warn!("unexpected readiness for tcpip channel: {:?}", readiness);
-            }
         }
     }
 }
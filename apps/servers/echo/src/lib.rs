@@ -10,11 +10,15 @@ use starina::message::Message;
 use starina::prelude::*;
 use starina::spec::AppSpec;
 use starina::spec::ExportItem;
+use starina::thread::Priority;
 
 pub const SPEC: AppSpec = AppSpec {
     name: "echo",
     env: &[],
     exports: &[ExportItem::Service { service: "echo" }],
+    priority: Priority::NORMAL,
+    memory_limit: None,
+    handle_limit: None,
     main: starina::mainloop::run::<App, Env>,
 };
 
@@ -25,6 +25,7 @@ use starina::spec::AppSpec;
 use starina::spec::EnvItem;
 use starina::spec::EnvType;
 use starina::spec::ExportItem;
+use starina::thread::Priority;
 use tcpip::TcpIp;
 
 #[derive(Debug)]
@@ -48,6 +49,10 @@ pub const SPEC: AppSpec = AppSpec {
         },
     }],
     exports: &[ExportItem::Service { service: "tcpip" }],
+    priority: Priority::NORMAL,
+    memory_limit: None,
+    // Each TCP connection consumes handles.
+    handle_limit: Some(4096),
     main,
 };
 
@@ -118,6 +123,9 @@ fn main(environ: Environ) {
             State::Listen(_) => {
                 panic!("unexpected readiness for listen channel: {:?}", readiness);
             }
+            State::Data { ch, smol_handle } if readiness.contains(Readiness::WRITABLE) => {
+                tcpip.handle_data_writable(&poll, ch, *smol_handle);
+            }
             State::Data { ch, smol_handle } if readiness.contains(Readiness::READABLE) => {
                 tcpip.handle_data_channel(&poll, ch, *smol_handle, &mut msgbuffer);
             }
@@ -63,16 +63,48 @@ pub struct TcpIp {
     /// The smoltcp socket states.
     smol_sockets: SocketSet<'static>,
     device: NetDevice,
-    recv_buf: Vec<u8>,
     iface: Interface,
 }
 
+/// Forwards received TCP data to the data channel.
+///
+/// If the channel is full, the rest is left in the socket's receive buffer
+/// (which shrinks the TCP window) until the channel becomes writable.
+fn forward_received_data(sock: &mut Socket, smol_sock: &mut tcp::Socket, poll: &Poll<State>) {
+    while smol_sock.can_recv() {
+        let result = smol_sock.recv(|buf| {
+            let len = buf.len().min(MESSAGE_DATA_LEN_MAX);
+            match sock.ch.send(Message::Data { data: &buf[..len] }) {
+                Ok(()) => (len, Ok(())),
+                Err(err) => (0, Err(err)),
+            }
+        });
+
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(ErrorCode::Full)) => {
+                trace!("data channel {:?} is full", sock.ch.handle_id());
+                poll.listen(sock.ch.handle_id(), Readiness::WRITABLE)
+                    .unwrap();
+                break;
+            }
+            Ok(Err(err)) => {
+                debug_warn!("failed to send received data: {:?}", err);
+                break;
+            }
+            Err(err) => {
+                debug_warn!("failed to receive data from socket: {:?}", err);
+                break;
+            }
+        }
+    }
+}
+
 fn process_tcp_state(
     handle: SocketHandle,
     sock: &mut Socket,
     smol_sock: &mut tcp::Socket,
     needs_listen: &mut Vec<(IpListenEndpoint, ChannelSender)>,
-    recv_buf: &mut [u8],
     poll: &Poll<State>,
 ) {
     match (&mut sock.state, smol_sock.state()) {
@@ -97,7 +129,9 @@ fn process_tcp_state(
             )
             .expect("failed to get channel sender");
 
-            sock.ch.send(Message::Connect { ch: their_ch }).unwrap();
+            if let Err(err) = sock.ch.send_blocking(Message::Connect { ch: their_ch }) {
+                debug_warn!("failed to send connect message: {:?}", err);
+            }
             sock.ch = our_tx;
             sock.state = SocketState::Established;
         }
@@ -117,18 +151,7 @@ fn process_tcp_state(
         }
         (SocketState::Established, _) if smol_sock.can_recv() => {
             // The establish connection with some received data.
-            loop {
-                let len = smol_sock.recv_slice(recv_buf).unwrap();
-                if len == 0 {
-                    break;
-                }
-
-                sock.ch
-                    .send(Message::Data {
-                        data: &recv_buf[..len],
-                    })
-                    .unwrap();
-            }
+            forward_received_data(sock, smol_sock, poll);
         }
         (SocketState::Established, tcp::State::Established) => {
             // Do nothing.
@@ -201,7 +224,6 @@ impl TcpIp {
             iface,
             smol_sockets,
             sockets: HashMap::new(),
-            recv_buf: vec![0; 1514],
         }
     }
 
@@ -241,14 +263,7 @@ impl TcpIp {
             let mut needs_listen = Vec::new();
             for (handle, sock) in self.sockets.iter_mut() {
                 let smol_sock = self.smol_sockets.get_mut::<tcp::Socket>(sock.smol_handle);
-                process_tcp_state(
-                    *handle,
-                    sock,
-                    smol_sock,
-                    &mut needs_listen,
-                    &mut self.recv_buf,
-                    poll,
-                );
+                process_tcp_state(*handle, sock, smol_sock, &mut needs_listen, poll);
             }
 
             // Remove closed sockets from self.sockets and smoltcp's socket set.
@@ -525,6 +540,26 @@ impl TcpIp {
         poll.remove(ch.handle_id()).unwrap();
     }
 
+    pub fn handle_data_writable(
+        &mut self,
+        poll: &Poll<State>,
+        ch: &ChannelReceiver,
+        smol_handle: SocketHandle,
+    ) {
+        trace!("data channel {:?} is writable again", ch.handle_id());
+        poll.unlisten(ch.handle_id(), Readiness::WRITABLE).unwrap();
+
+        if let Some(sock) = self.sockets.get_mut(&smol_handle)
+            && sock.state == SocketState::Established
+        {
+            let smol_sock = self.smol_sockets.get_mut::<tcp::Socket>(sock.smol_handle);
+            forward_received_data(sock, smol_sock, poll);
+        }
+
+        // Let smoltcp send a window update.
+        self.poll(poll);
+    }
+
     pub fn handle_data_channel(
         &mut self,
         poll: &Poll<State>,
@@ -4,6 +4,7 @@ use core::num::NonZeroUsize;
 
 use arrayvec::ArrayVec;
 
+use crate::arch;
 use crate::spinlock::SpinLock;
 
 /// A bump memory allocator.
@@ -35,12 +36,18 @@ impl BumpAllocator {
 
 unsafe impl GlobalAlloc for BumpAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        for region in self.regions.lock().iter_mut() {
-            if let Some(addr) = region.allocate(layout.size(), layout.align()) {
-                return addr.get() as *mut u8;
-            }
+        // See `TalcAllocator::alloc` for why interrupts are disabled.
+        let addr = arch::without_interrupts(|| {
+            self.regions
+                .lock()
+                .iter_mut()
+                .find_map(|region| region.allocate(layout.size(), layout.align()))
+        });
+
+        match addr {
+            Some(addr) => addr.get() as *mut u8,
+            None => panic!("out of memory"),
         }
-        panic!("out of memory");
     }
 
     unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {
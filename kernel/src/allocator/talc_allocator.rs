@@ -6,6 +6,8 @@ use talc::Span;
 use talc::Talc;
 use talc::Talck;
 
+use crate::arch;
+
 pub struct TalcAllocator {
     allocator: Talck<spin::Mutex<()>, ErrOnOom>,
 }
@@ -29,7 +31,10 @@ impl TalcAllocator {
 
 unsafe impl GlobalAlloc for TalcAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        let result = unsafe { self.allocator.alloc(layout) };
+        // In-kernel apps share this heap and can be preempted by interrupts.
+        // Don't let the kernel, which allocates in interrupt handlers, spin
+        // on the lock held by an interrupted app on the same CPU.
+        let result = arch::without_interrupts(|| unsafe { self.allocator.alloc(layout) });
         if result.is_null() {
             panic!("out of memory");
         }
@@ -41,8 +46,8 @@ unsafe impl GlobalAlloc for TalcAllocator {
         debug_assert!(!ptr.is_null());
         debug_assert!(layout.size() > 0);
 
-        unsafe {
+        arch::without_interrupts(|| unsafe {
             self.allocator.dealloc(ptr, layout);
-        }
+        });
     }
 }
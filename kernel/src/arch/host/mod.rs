@@ -22,6 +22,18 @@ pub fn percpu_init() {
     todo!()
 }
 
+pub fn start_secondary_cpus() {
+    todo!()
+}
+
+pub fn send_ipi(cpu: crate::cpuvar::CpuId) {
+    todo!()
+}
+
+pub fn without_interrupts<R>(f: impl FnOnce() -> R) -> R {
+    f()
+}
+
 pub fn halt() -> ! {
     panic!("halted");
 }
@@ -53,7 +65,7 @@ pub fn idle() -> ! {
 pub struct Thread {}
 
 impl Thread {
-    pub fn new_inkernel(pc: usize, arg: usize) -> Thread {
+    pub fn new_inkernel(pc: usize, sp: usize, arg: usize) -> Thread {
         Thread {}
     }
 
@@ -69,7 +81,10 @@ impl Thread {
 pub struct CpuVar {}
 
 impl CpuVar {
-    pub fn new(idle_thread: &crate::refcount::SharedRef<crate::thread::Thread>) -> Self {
+    pub fn new(
+        idle_thread: &crate::refcount::SharedRef<crate::thread::Thread>,
+        kernel_stack_top: VAddr,
+    ) -> Self {
         CpuVar {}
     }
 }
@@ -119,6 +134,26 @@ impl VmSpace {
         todo!()
     }
 
+    pub fn reserve_anywhere(&self, len: usize) -> Result<VAddr, ErrorCode> {
+        todo!()
+    }
+
+    pub fn reserve_fixed(&self, vaddr: VAddr, len: usize) -> Result<(), ErrorCode> {
+        todo!()
+    }
+
+    pub fn map_page(&self, vaddr: VAddr, paddr: PAddr, prot: PageProtect) -> Result<(), ErrorCode> {
+        todo!()
+    }
+
+    pub fn unmap(&self, vaddr: VAddr, len: usize) -> Result<(), ErrorCode> {
+        todo!()
+    }
+
+    pub fn protect(&self, vaddr: VAddr, len: usize, prot: PageProtect) -> Result<(), ErrorCode> {
+        todo!()
+    }
+
     pub fn switch(&self) {
         todo!()
     }
@@ -1,13 +1,20 @@
+use core::alloc::GlobalAlloc;
+use core::alloc::Layout;
 use core::arch::asm;
 use core::arch::naked_asm;
 
+use starina::address::VAddr;
+
 use super::get_cpuvar;
 use super::plic::use_plic;
+use super::sbi;
 use crate::BootInfo;
+use crate::allocator::GLOBAL_ALLOCATOR;
 use crate::arch::riscv64::csr::StvecMode;
 use crate::arch::riscv64::csr::write_stvec;
 use crate::arch::riscv64::entry::trap_entry;
 use crate::cpuvar::CpuId;
+use crate::cpuvar::NUM_CPUS_MAX;
 
 // The kernel entrypoint for RISC-V machines. We expect Linux's RISC-V boot
 // requirements:
@@ -32,6 +39,7 @@ unsafe extern "C" fn riscv64_boot(hartid: u64, dtb: *const u8) -> ! {
 unsafe extern "C" {
     static __bss: u8;
     static __bss_end: u8;
+    static __boot_stack_top: u8;
 }
 
 unsafe extern "C" fn rust_boot(hartid: u64, dtb: *const u8) -> ! {
@@ -43,7 +51,65 @@ unsafe extern "C" fn rust_boot(hartid: u64, dtb: *const u8) -> ! {
     }
 
     let cpu_id = CpuId::new(hartid.try_into().unwrap());
-    crate::boot(BootInfo { cpu_id, dtb });
+    let kernel_stack_top = VAddr::new(&raw const __boot_stack_top as usize);
+    crate::boot(BootInfo {
+        cpu_id,
+        dtb,
+        kernel_stack_top,
+    });
+}
+
+/// The size of the kernel stack for each secondary CPU.
+const SECONDARY_STACK_SIZE: usize = 1024 * 1024; // 1 MiB
+
+/// The entrypoint for secondary CPUs, started by `hart_start` SBI call:
+///
+///   - a0: The hartid of this CPU.
+///   - a1: The top of the kernel stack allocated for this CPU.
+#[unsafe(naked)]
+unsafe extern "C" fn riscv64_secondary_boot(hartid: u64, stack_top: u64) -> ! {
+    naked_asm!(
+        "mv ra, zero",
+        "mv fp, zero",
+        "mv sp, a1",
+        "j {rust_secondary_boot}",
+        rust_secondary_boot = sym rust_secondary_boot,
+    );
+}
+
+unsafe extern "C" fn rust_secondary_boot(hartid: u64, stack_top: u64) -> ! {
+    let cpu_id = CpuId::new(hartid.try_into().unwrap());
+    crate::secondary_boot(cpu_id, VAddr::new(stack_top as usize));
+}
+
+/// Starts all other CPUs through the SBI HSM extension.
+pub fn start_secondary_cpus() {
+    let boot_cpu = get_cpuvar().cpu_id;
+    for hartid in 0..NUM_CPUS_MAX as u64 {
+        if hartid == boot_cpu.as_usize() as u64 {
+            continue;
+        }
+
+        // Skip harts that don't exist or are already running.
+        match sbi::hart_get_status(hartid) {
+            Ok(sbi::HART_STATE_STOPPED) => {}
+            _ => continue,
+        }
+
+        let layout = Layout::from_size_align(SECONDARY_STACK_SIZE, 16).unwrap();
+        let stack = unsafe { GLOBAL_ALLOCATOR.alloc(layout) };
+        if stack.is_null() {
+            warn!("failed to allocate a kernel stack for CPU #{}", hartid);
+            break;
+        }
+
+        let stack_top = stack as usize + SECONDARY_STACK_SIZE;
+        let entry = riscv64_secondary_boot as *const () as usize;
+        if sbi::hart_start(hartid, entry, stack_top).is_err() {
+            warn!("failed to start CPU #{}", hartid);
+            unsafe { GLOBAL_ALLOCATOR.dealloc(stack, layout) };
+        }
+    }
 }
 
 pub fn percpu_init() {
@@ -51,6 +117,9 @@ pub fn percpu_init() {
         asm!("csrw sscratch, tp");
     }
 
+    // Disarm the timer until a thread or a timer needs it.
+    super::set_timer(u64::MAX);
+
     unsafe {
         write_stvec(trap_entry as *const () as usize, StvecMode::Direct);
 
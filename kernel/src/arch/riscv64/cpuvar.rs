@@ -1,5 +1,7 @@
 use core::arch::asm;
 
+use starina::address::VAddr;
+
 use super::thread::Context;
 use crate::refcount::SharedRef;
 use crate::thread::Thread;
@@ -14,16 +16,11 @@ pub struct CpuVar {
 }
 
 impl CpuVar {
-    pub fn new(idle_thread: &SharedRef<Thread>) -> Self {
-        unsafe extern "C" {
-            static __boot_stack_top: u8;
-        }
-
-        let sp_top = &raw const __boot_stack_top as u64;
+    pub fn new(idle_thread: &SharedRef<Thread>, kernel_stack_top: VAddr) -> Self {
         Self {
             context: unsafe { &raw mut (*idle_thread.arch_thread_ptr()).context },
             magic: CPUVAR_MAGIC,
-            kernel_sp: sp_top,
+            kernel_sp: kernel_stack_top.as_usize() as u64,
             a0_scratch: 0,
         }
     }
@@ -22,8 +22,9 @@ pub extern "C" fn inkernel_syscall_entry(
     _n: isize,
 ) -> RetVal {
     naked_asm!(
-        // Disable interrupts in kernel.
-        "csrci sstatus, 1 << 1",
+        // Disable interrupts in kernel. The app might have disabled them
+        // (e.g. while holding a lock): keep the original SIE in t2.
+        "csrrci t2, sstatus, 1 << 1",
 
         "csrrw tp, sscratch, tp",
         "ld t0, {context_offset}(tp)", // Load CpuVar.arch.context
@@ -45,9 +46,13 @@ pub extern "C" fn inkernel_syscall_entry(
         "sd s11, {s11_offset}(t0)",
         "sd ra, {sepc_offset}(t0)",
 
-        // Save sstatus.
-        "csrr t1, sstatus",
-        "sd t1, {sstatus_offset}(t0)",
+        // Save sstatus, with the original SIE in SPIE as traps do, so that
+        // `user_entry` restores it.
+        "andi t1, t2, 1 << 1",
+        "slli t1, t1, 4",
+        "andi t2, t2, ~(1 << 5)",
+        "or t2, t2, t1",
+        "sd t2, {sstatus_offset}(t0)",
 
         // Read the original tp temporarily saved in sscratch, and
         // restore the original sscratch value.
@@ -175,17 +180,30 @@ pub unsafe extern "C" fn trap_entry() -> ! {
 
 pub fn user_entry(thread: *mut crate::arch::Thread) -> ! {
     let context: *mut Context = unsafe { &mut (*thread).context as *mut _ };
+    let user_mode = unsafe { (*thread).user_mode };
     let mut sstatus: u64;
     unsafe {
         asm!("csrr {0}, sstatus", out(reg) sstatus);
-        sstatus |= 1 << 8; // Set SPP to go back to kernel mode
+        if user_mode {
+            sstatus &= !(1 << 8); // Clear SPP to go to user mode
+        } else {
+            sstatus |= 1 << 8; // Set SPP to go back to kernel mode
+        }
 
-        // Clear SPIE to disable interrupts while running apps.
+        // In-kernel apps run with interrupts enabled so that the timer can
+        // preempt them, except while they hold a lock (see
+        // `starina::sync::Mutex`). The kernel itself runs with interrupts
+        // disabled: `inkernel_syscall_entry` and traps save the app's SIE
+        // in SPIE, and we restore it here. Locks in the kernel shared with
+        // in-kernel apps (the heap allocator) disable interrupts on their own.
         //
-        // FIXME: This is a dirty hack to prevent a null pointer dereference bug in m
-        //        which is presumerbly caused by incorrectly saving/restoring the `a1`
-        //        register.
-        sstatus &= !(1 << 5);
+        // U-mode is always interruptible regardless of SPIE.
+        let spie = (*context).sstatus & (1 << 5);
+        if user_mode || spie == 0 {
+            sstatus &= !(1 << 5);
+        } else {
+            sstatus |= 1 << 5;
+        }
 
         asm!("csrw sstatus, {0}", in(reg) sstatus);
     }
@@ -1,4 +1,5 @@
 use core::mem;
+use core::mem::ManuallyDrop;
 
 use starina::address::GPAddr;
 use starina::address::PAddr;
@@ -62,12 +63,14 @@ impl Table {
 }
 
 struct HvPageTable {
-    l0_table: Folio,
+    /// The root table. Not freed as the guest page table may still be
+    /// referenced by a CPU (hgatp) after the hvspace is destroyed.
+    l0_table: ManuallyDrop<Folio>,
 }
 
 impl HvPageTable {
     pub fn new() -> Result<HvPageTable, ErrorCode> {
-        let l0_table = Folio::alloc(size_of::<Table>())?;
+        let l0_table = ManuallyDrop::new(Folio::alloc(size_of::<Table>())?);
         let table = HvPageTable { l0_table };
         Ok(table)
     }
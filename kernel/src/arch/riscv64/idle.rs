@@ -25,6 +25,12 @@ fn resume_from_idle() -> ! {
 }
 
 pub fn halt() -> ! {
+    // Don't let the timer switch to other threads: a panic in an in-kernel
+    // app may happen with interrupts enabled.
+    unsafe {
+        asm!("csrci sstatus, 1 << 1");
+    }
+
     loop {
         unsafe {
             asm!("wfi");
@@ -1,15 +1,22 @@
 use alloc::string::String;
 use core::arch::asm;
 
+use starina::address::VAddr;
 use starina::device_tree::Reg;
 use starina::error::ErrorCode;
 use starina::interrupt::Irq;
 use starina::interrupt::IrqMatcher;
+use starina::vmspace::PageProtect;
 
 use super::plic;
 use super::plic::use_plic;
+use super::sbi;
+use crate::cpuvar::CpuId;
+use crate::cpuvar::current_thread;
 use crate::interrupt::Interrupt;
 use crate::refcount::SharedRef;
+use crate::syscall::syscall_handler;
+use crate::thread::ThreadState;
 use crate::thread::switch_thread;
 
 pub extern "C" fn interrupt_handler() -> ! {
@@ -60,6 +67,8 @@ pub extern "C" fn interrupt_handler() -> ! {
     };
 
     let sepc = unsafe { (*cpuvar.arch.context).sepc } as u64;
+    let sstatus = unsafe { (*cpuvar.arch.context).sstatus } as u64;
+    let from_user = sstatus & (1 << 8) == 0; // SPP
 
     if (is_intr, code) == (true, 9) {
         use_plic(|plic| {
@@ -67,22 +76,70 @@ pub extern "C" fn interrupt_handler() -> ! {
         });
         switch_thread();
     } else if (is_intr, code) == (false, 8) {
+        let context = cpuvar.arch.context;
         unsafe {
             // Skip ecall instruction
-            (*cpuvar.arch.context).sepc += 4;
+            (*context).sepc += 4;
         }
 
-        // let a0 = unsafe { (*cpuvar.arch.context).a0 } as isize;
-        // let a1 = unsafe { (*cpuvar.arch.context).a1 } as isize;
-        // let a2 = unsafe { (*cpuvar.arch.context).a2 } as isize;
-        // let a3 = unsafe { (*cpuvar.arch.context).a3 } as isize;
-        // let a4 = unsafe { (*cpuvar.arch.context).a4 } as isize;
-        // let a5 = unsafe { (*cpuvar.arch.context).a5 } as isize;
-        // let ret = syscall_handler(a0, a1, a2, a3, a4, a5);
-        todo!()
+        let a0 = unsafe { (*context).a0 } as isize;
+        let a1 = unsafe { (*context).a1 } as isize;
+        let a2 = unsafe { (*context).a2 } as isize;
+        let a3 = unsafe { (*context).a3 } as isize;
+        let a4 = unsafe { (*context).a4 } as isize;
+        let a5 = unsafe { (*context).a5 } as isize;
+        let n = unsafe { (*context).a6 } as isize;
+        syscall_handler(a0, a1, a2, a3, a4, a5, n);
     } else if (is_intr, code) == (true, 5) {
         crate::timer::handle_timer_interrupt();
 
+        switch_thread();
+    } else if (is_intr, code) == (true, 1) {
+        // An IPI from another CPU: a thread has been queued while we're idle.
+        clear_ipi();
+        switch_thread();
+    } else if !is_intr
+        && matches!(code, 12 | 13 | 15)
+        && from_user
+        && handle_page_fault(code, stval)
+    {
+        // A page fault on a demand-paged page. Now it's mapped: retry the
+        // faulting instruction.
+        switch_thread();
+    } else if !is_intr && matches!(code, 12 | 13 | 15) && is_stack_overflow(stval) {
+        // The thread has run off the bottom of its stack. This happens in
+        // both user-mode and in-kernel apps, and is the app's bug: kill the
+        // app instead of the whole system.
+        let current = current_thread();
+        error!(
+            "{}: stack overflow in thread \"{}\" ({}), sepc: {:#x}, stval: {:#x}",
+            current.process().name(),
+            current.name(),
+            scause_str,
+            sepc,
+            stval
+        );
+
+        current.process().exit(-1);
+        current.set_state(ThreadState::Exited);
+        drop(current);
+        switch_thread();
+    } else if !is_intr && from_user {
+        // An exception in a user-mode process. Kill the process instead of
+        // the whole system.
+        let current = current_thread();
+        warn!(
+            "{}: killing the process: {} (scause={:#x}), sepc: {:#x}, stval: {:#x}",
+            current.process().name(),
+            scause_str,
+            scause,
+            sepc,
+            stval
+        );
+
+        current.process().exit(-1);
+        current.set_state(ThreadState::Exited);
+        drop(current);
         switch_thread();
     } else {
         panic!(
@@ -91,6 +148,68 @@ pub extern "C" fn interrupt_handler() -> ! {
         );
     }
 }
+
+/// Tries to resolve a page fault in the current user-mode thread's address
+/// space. Returns `true` if the page has been mapped.
+///
+/// Faults from the kernel mode are never resolved because this allocates
+/// memory: an in-kernel app might have been holding the allocator's lock.
+/// Instead, VMOs in the kernel's address space are populated when they're
+/// mapped.
+fn handle_page_fault(code: u64, stval: u64) -> bool {
+    let access = match code {
+        12 => PageProtect::EXECUTABLE,
+        13 => PageProtect::READABLE,
+        _ => PageProtect::WRITEABLE,
+    };
+
+    let current = current_thread();
+    let vmspace = current.process().isolation().vmspace().clone();
+    drop(current);
+    vmspace
+        .handle_page_fault(VAddr::new(stval as usize), access)
+        .is_ok()
+}
+
+/// Returns `true` if the fault address is in the current thread's stack
+/// guard page.
+fn is_stack_overflow(stval: u64) -> bool {
+    current_thread().is_stack_overflow(VAddr::new(stval as usize))
+}
+
+/// Runs `f` with interrupts disabled on this CPU, and restores the previous
+/// interrupt state afterwards.
+pub fn without_interrupts<R>(f: impl FnOnce() -> R) -> R {
+    let sstatus: u64;
+    unsafe {
+        asm!("csrrci {}, sstatus, 1 << 1", out(reg) sstatus);
+    }
+
+    let ret = f();
+
+    if sstatus & (1 << 1) != 0 {
+        unsafe {
+            asm!("csrsi sstatus, 1 << 1");
+        }
+    }
+
+    ret
+}
+
+/// Sends an inter-processor interrupt to `cpu` to wake it up.
+pub fn send_ipi(cpu: CpuId) {
+    if sbi::send_ipi(1 << cpu.as_usize(), 0).is_err() {
+        warn!("failed to send an IPI to CPU {}", cpu);
+    }
+}
+
+/// Acknowledges an IPI: clears the pending supervisor software interrupt.
+pub(super) fn clear_ipi() {
+    unsafe {
+        asm!("csrc sip, {}", in(reg) 1 << 1);
+    }
+}
+
 pub static INTERRUPT_CONTROLLER: PlicWrapper = PlicWrapper::new();
 
 #[derive(Debug)]
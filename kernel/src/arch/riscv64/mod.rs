@@ -16,6 +16,7 @@ mod vcpu;
 mod vmspace;
 
 pub use boot::percpu_init;
+pub use boot::start_secondary_cpus;
 pub use cpuvar::CpuVar;
 pub use cpuvar::get_cpuvar;
 pub use cpuvar::set_cpuvar;
@@ -24,6 +25,8 @@ pub use hvspace::HvSpace;
 pub use idle::halt;
 pub use idle::idle;
 pub use interrupt::INTERRUPT_CONTROLLER;
+pub use interrupt::send_ipi;
+pub use interrupt::without_interrupts;
 pub use serial::console_write;
 pub use thread::Thread;
 pub use timer::read_timer;
@@ -28,8 +28,10 @@ fn priority_reg(irq: Irq) -> MmioReg<LittleEndian, ReadWrite, u32> {
 
 // Interrupt Enable Bits
 // https://github.com/riscv/riscv-plic-spec/blob/master/riscv-plic.adoc#5-interrupt-enables
-fn enable_reg(irq: Irq) -> MmioReg<LittleEndian, ReadWrite, u32> {
-    MmioReg::new(0x2080 + ((irq.as_raw() as usize) / 32 * size_of::<u32>()))
+fn enable_reg(hart: CpuId, irq: Irq) -> MmioReg<LittleEndian, ReadWrite, u32> {
+    MmioReg::new(
+        0x2080 + 0x100 * hart.as_usize() + ((irq.as_raw() as usize) / 32 * size_of::<u32>()),
+    )
 }
 
 /// Interrupt Claim Register
@@ -70,6 +72,9 @@ pub fn use_plic(f: impl FnOnce(&mut Plic)) {
 pub struct Plic {
     folio: MmioFolio,
     listeners: BTreeMap<Irq, SharedRef<Interrupt>>,
+    /// The CPU which receives all device interrupts: the first CPU
+    /// initialized, that is, the boot CPU.
+    irq_cpu: Option<CpuId>,
 }
 
 impl Plic {
@@ -85,6 +90,7 @@ impl Plic {
         Plic {
             folio: mmio_folio,
             listeners: BTreeMap::new(),
+            irq_cpu: None,
         }
     }
 
@@ -93,10 +99,20 @@ impl Plic {
         //
         // Note: Don't use cpuvar() here because it's not initialized yet.
         threshold_reg(cpu_id).write(&mut self.folio, 0);
+
+        if self.irq_cpu.is_none() {
+            self.irq_cpu = Some(cpu_id);
+        }
+    }
+
+    fn irq_cpu(&self) -> CpuId {
+        self.irq_cpu.expect("PLIC is not initialized for any CPU")
     }
 
     pub fn get_pending_irq(&mut self) -> Irq {
-        let raw_irq = claim_reg(get_cpuvar().cpu_id).read(&mut self.folio);
+        debug_assert_eq!(get_cpuvar().cpu_id, self.irq_cpu());
+
+        let raw_irq = claim_reg(self.irq_cpu()).read(&mut self.folio);
         Irq::from_raw(raw_irq)
     }
 
@@ -107,7 +123,7 @@ impl Plic {
 
         priority_reg(irq).write(&mut self.folio, 1);
 
-        let enable = enable_reg(irq);
+        let enable = enable_reg(self.irq_cpu(), irq);
         let mut value = enable.read(&mut self.folio);
         value |= 1 << ((irq.as_raw() as usize) % 32);
         enable.write(&mut self.folio, value);
@@ -121,7 +137,7 @@ impl Plic {
         priority_reg(irq).write(&mut self.folio, 0);
 
         // Clear the enable bit
-        let enable = enable_reg(irq);
+        let enable = enable_reg(self.irq_cpu(), irq);
         let mut value = enable.read(&mut self.folio);
         value &= !(1 << ((irq.as_raw() as usize) % 32));
         enable.write(&mut self.folio, value);
@@ -130,7 +146,9 @@ impl Plic {
     pub fn acknowledge(&mut self, irq: Irq) {
         assert!((irq.as_raw() as usize) < IRQ_MAX);
 
-        claim_reg(get_cpuvar().cpu_id).write(&mut self.folio, irq.as_raw());
+        // The completion must be written to the CPU which claimed the IRQ,
+        // not the CPU which acknowledges it.
+        claim_reg(self.irq_cpu()).write(&mut self.folio, irq.as_raw());
     }
 
     pub fn register_listener(&mut self, irq: Irq, listener: SharedRef<Interrupt>) {
@@ -4,6 +4,7 @@ const fn scause(intr: bool, code: u64) -> u64 {
     if intr { code | (1 << 63) } else { code }
 }
 
+pub const SCAUSE_HOST_SOFT_INTR: u64 = scause(true, 1);
 pub const SCAUSE_HOST_TIMER_INTR: u64 = scause(true, 5);
 pub const SCAUSE_ECALL_FROM_VS: u64 = scause(false, 10);
 pub const SCAUSE_GUEST_INST_PAGE_FAULT: u64 = scause(false, 20);
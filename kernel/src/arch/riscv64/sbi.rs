@@ -42,3 +42,70 @@ pub fn console_putchar(c: u8) {
         let _ = sbi_call(c as c_long, 0, 0, 0, 0, 0, 0, 1);
     }
 }
+
+const EID_HSM: c_long = 0x48534d;
+const EID_IPI: c_long = 0x735049;
+const EID_RFENCE: c_long = 0x52464e43;
+
+/// The hart is powered off, waiting for `hart_start`.
+pub const HART_STATE_STOPPED: c_long = 1;
+
+/// Starts a hart at `start_addr` (a physical address) in S-mode, with its
+/// hart ID in `a0` and `opaque` in `a1`.
+pub fn hart_start(hartid: u64, start_addr: usize, opaque: usize) -> Result<(), Error> {
+    unsafe {
+        sbi_call(
+            hartid as c_long,
+            start_addr as c_long,
+            opaque as c_long,
+            0,
+            0,
+            0,
+            0,
+            EID_HSM,
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn hart_get_status(hartid: u64) -> Result<c_long, Error> {
+    unsafe { sbi_call(hartid as c_long, 0, 0, 0, 0, 0, 2, EID_HSM) }
+}
+
+/// Sends a supervisor software interrupt to the harts in `hart_mask`.
+pub fn send_ipi(hart_mask: u64, hart_mask_base: u64) -> Result<(), Error> {
+    unsafe {
+        sbi_call(
+            hart_mask as c_long,
+            hart_mask_base as c_long,
+            0,
+            0,
+            0,
+            0,
+            0,
+            EID_IPI,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Executes `sfence.vma` for `[start_addr, start_addr + size)` on all harts,
+/// including the calling one. Returns after all harts have completed it.
+pub fn remote_sfence_vma_all(start_addr: usize, size: usize) -> Result<(), Error> {
+    unsafe {
+        sbi_call(
+            0,
+            -1, /* hart_mask_base = -1: all harts */
+            start_addr as c_long,
+            size as c_long,
+            0,
+            0,
+            1,
+            EID_RFENCE,
+        )?;
+    }
+
+    Ok(())
+}
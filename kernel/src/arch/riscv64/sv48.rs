@@ -37,6 +37,10 @@ impl Pte {
         self.0 = Pte::new(paddr, flags).0;
     }
 
+    pub fn clear(&mut self) {
+        self.0 = 0;
+    }
+
     pub fn is_valid(&self) -> bool {
         self.0 & PTE_V != 0
     }
@@ -45,6 +49,10 @@ impl Pte {
         self.0 & (PTE_R | PTE_W | PTE_X) != 0
     }
 
+    pub fn flags(&self) -> u64 {
+        self.0 & ((1 << PTE_PPN_SHIFT) - 1)
+    }
+
     pub fn ppn(&self) -> u64 {
         self.0 >> PTE_PPN_SHIFT
     }
@@ -118,6 +126,33 @@ fn walk_into_last_table(l0_table: &Folio, vaddr: VAddr) -> Result<&mut Table, Er
     Ok(table)
 }
 
+/// Looks for the leaf entry mapping `vaddr` without allocating intermediate
+/// tables. Returns `None` if it's not mapped.
+pub(super) fn lookup_leaf(l0_table: &Folio, vaddr: VAddr) -> Result<Option<Pte>, ErrorCode> {
+    let mut table = paddr2table(l0_table.paddr())?;
+    for level in (1..=3).rev() {
+        let entry = table.0[get_index(vaddr, level)];
+        if !entry.is_valid() {
+            return Ok(None);
+        }
+
+        if entry.is_leaf() {
+            // We don't use huge pages.
+            debug_warn!("lookup_leaf: unexpected huge page at {}", vaddr);
+            return Ok(None);
+        }
+
+        table = paddr2table(entry.paddr())?;
+    }
+
+    let entry = table.0[get_index(vaddr, 0)];
+    if !entry.is_valid() {
+        return Ok(None);
+    }
+
+    Ok(Some(entry))
+}
+
 pub(super) struct PteIter<'a> {
     l0_table: &'a Folio,
     current: &'a mut Table,
@@ -1,10 +1,5 @@
-use core::alloc::GlobalAlloc;
-use core::alloc::Layout;
-
 use starina_types::syscall::RetVal;
 
-use crate::allocator::GLOBAL_ALLOCATOR;
-
 /// Context of a thread.
 #[derive(Debug, Default)]
 #[repr(C, packed)]
@@ -46,34 +41,52 @@ pub struct Context {
 
 pub struct Thread {
     pub(super) context: Context,
+    /// Whether the thread runs in U-mode.
+    pub(super) user_mode: bool,
 }
 
 impl Thread {
     pub fn new_idle() -> Thread {
         Thread {
             context: Default::default(),
+            user_mode: false,
         }
     }
 
-    pub fn new_inkernel(pc: usize, arg: usize) -> Thread {
-        let stack_size = 1024 * 1024;
-        let stack =
-            unsafe { GLOBAL_ALLOCATOR.alloc(Layout::from_size_align(stack_size, 16).unwrap()) };
-        let sp = stack as u64 + stack_size as u64;
-
+    /// Creates a thread which runs in S-mode. `sp` is the top of the stack
+    /// in the kernel's address space.
+    pub fn new_inkernel(pc: usize, sp: usize, arg: usize) -> Thread {
         let mut sstatus: u64;
         unsafe {
             core::arch::asm!("csrr {}, sstatus", out(reg) sstatus);
         }
 
+        // Start with interrupts enabled (SPIE) so that it's preemptible.
+        sstatus |= 1 << 5;
+
         Thread {
             context: Context {
                 sepc: pc.try_into().unwrap(),
                 sstatus,
                 a0: arg.try_into().unwrap(),
-                sp,
+                sp: sp.try_into().unwrap(),
+                ..Default::default()
+            },
+            user_mode: false,
+        }
+    }
+
+    /// Creates a thread which runs in U-mode. `sp` is a virtual address in
+    /// the process's address space.
+    pub fn new_user(pc: usize, sp: usize, arg: usize) -> Thread {
+        Thread {
+            context: Context {
+                sepc: pc as u64,
+                a0: arg as u64,
+                sp: sp as u64,
                 ..Default::default()
             },
+            user_mode: true,
         }
     }
 
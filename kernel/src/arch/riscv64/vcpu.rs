@@ -14,6 +14,7 @@ use starina::vcpu::VCPU_EXIT_NONE;
 use starina::vcpu::VCPU_EXIT_PAGE_FAULT;
 use starina::vcpu::VCPU_EXIT_REBOOT;
 use starina::vcpu::VCpuRunState;
+use starina::vmspace::PageProtect;
 
 use super::get_cpuvar;
 use crate::arch::riscv64::csr::StvecMode;
@@ -31,6 +32,7 @@ use crate::arch::riscv64::riscv::SCAUSE_ECALL_FROM_VS;
 use crate::arch::riscv64::riscv::SCAUSE_GUEST_INST_PAGE_FAULT;
 use crate::arch::riscv64::riscv::SCAUSE_GUEST_LOAD_PAGE_FAULT;
 use crate::arch::riscv64::riscv::SCAUSE_GUEST_STORE_PAGE_FAULT;
+use crate::arch::riscv64::riscv::SCAUSE_HOST_SOFT_INTR;
 use crate::arch::riscv64::riscv::SCAUSE_HOST_TIMER_INTR;
 use crate::arch::riscv64::riscv::SCAUSE_SV_EXT_INTR;
 use crate::arch::riscv64::riscv::SCAUSE_VIRTUAL_INST;
@@ -39,6 +41,7 @@ use crate::cpuvar::current_thread;
 use crate::hvspace::HvSpace;
 use crate::isolation::Isolation;
 use crate::isolation::IsolationSliceMut;
+use crate::refcount::SharedRef;
 use crate::spinlock::SpinLock;
 use crate::thread::switch_thread;
 
@@ -549,11 +552,14 @@ fn handle_guest_page_fault(
 pub struct VCpu {
     context: Context,
     mutable: SpinLock<Mutable>,
+    /// The guest address space. Kept here to handle guest page faults on
+    /// demand-paged memory.
+    hvspace: SharedRef<HvSpace>,
 }
 
 impl VCpu {
     pub fn new(
-        hvspace: &HvSpace,
+        hvspace: SharedRef<HvSpace>,
         entry: usize,
         arg0: usize,
         arg1: usize,
@@ -598,6 +604,7 @@ impl VCpu {
         Ok(VCpu {
             context,
             mutable: SpinLock::new(mutable),
+            hvspace,
         })
     }
 
@@ -964,12 +971,15 @@ extern "C" fn vcpu_trap_handler(vcpu: *mut VCpu) -> ! {
 
     let mut mutable = unsafe { (*vcpu).mutable.lock() };
     match scause {
+        SCAUSE_HOST_SOFT_INTR => {
+            // An IPI from another CPU. Just acknowledge it: we'll check the
+            // run queue in switch_thread.
+            super::interrupt::clear_ipi();
+        }
         SCAUSE_HOST_TIMER_INTR => {
-            let mut now: u64;
-            unsafe {
-                asm!("csrr {}, time", out(reg) now);
-                asm!("csrw stimecmp, {}", in(reg) now + 0x100);
-            }
+            // Kernel timers may have expired while the guest is running.
+            // This also re-arms the timer for the next deadline.
+            crate::timer::handle_timer_interrupt();
         }
         SCAUSE_ECALL_FROM_VS => {
             let (error, value) = match mutable.handle_sbi_call(context) {
@@ -996,7 +1006,19 @@ extern "C" fn vcpu_trap_handler(vcpu: *mut VCpu) -> ! {
                 SCAUSE_GUEST_INST_PAGE_FAULT
                 | SCAUSE_GUEST_LOAD_PAGE_FAULT
                 | SCAUSE_GUEST_STORE_PAGE_FAULT => {
-                    handle_guest_page_faults(&mut mutable, context, scause, htval, stval);
+                    let gpaddr = htval_to_gpaddr(htval, stval);
+                    let access = match scause {
+                        SCAUSE_GUEST_INST_PAGE_FAULT => PageProtect::EXECUTABLE,
+                        SCAUSE_GUEST_LOAD_PAGE_FAULT => PageProtect::READABLE,
+                        _ => PageProtect::WRITEABLE,
+                    };
+
+                    // Try demand-paged RAM first. If it's not, it's an MMIO
+                    // access to be emulated.
+                    let hvspace = unsafe { &(*vcpu).hvspace };
+                    if hvspace.handle_page_fault(gpaddr, access).is_err() {
+                        handle_guest_page_faults(&mut mutable, context, scause, htval, stval);
+                    }
                 }
                 SCAUSE_SV_EXT_INTR => {
                     use super::plic::use_plic;
@@ -1,4 +1,5 @@
 use core::arch::asm;
+use core::mem::ManuallyDrop;
 
 use starina_types::address::PAddr;
 use starina_types::address::VAddr;
@@ -6,6 +7,7 @@ use starina_types::error::ErrorCode;
 use starina_types::vmspace::PageProtect;
 use starina_utils::alignment::is_aligned;
 
+use super::sbi;
 use super::sv48::SATP_MODE_SV48;
 use super::sv48::Table;
 use crate::arch::riscv64::sv48::PTE_R;
@@ -14,6 +16,7 @@ use crate::arch::riscv64::sv48::PTE_V;
 use crate::arch::riscv64::sv48::PTE_W;
 use crate::arch::riscv64::sv48::PTE_X;
 use crate::arch::riscv64::sv48::PteIter;
+use crate::arch::riscv64::sv48::lookup_leaf;
 use crate::folio::Folio;
 use crate::spinlock::SpinLock;
 
@@ -22,6 +25,7 @@ pub(super) const PPN_SHIFT: usize = 12;
 
 const VALLOC_START: VAddr = VAddr::new(0x0000_000b_0000_0000);
 const VALLOC_END: VAddr = VAddr::new(0x0000_000b_ffff_ffff);
+const USER_SPACE_END: usize = 1 << 47;
 
 pub fn vaddr2paddr(vaddr: VAddr) -> Result<PAddr, ErrorCode> {
     // Identical mapping.
@@ -61,12 +65,14 @@ where
 }
 
 struct PageTable {
-    l0_table: Folio,
+    /// The root table. It's never freed because an idle CPU may keep using
+    /// the page table even after the vmspace is destroyed.
+    l0_table: ManuallyDrop<Folio>,
 }
 
 impl PageTable {
     pub fn new() -> Result<PageTable, ErrorCode> {
-        let l0_table = Folio::alloc(size_of::<Table>())?;
+        let l0_table = ManuallyDrop::new(Folio::alloc(size_of::<Table>())?);
         let mut table = PageTable { l0_table };
         table.map_kernel_space()?;
         Ok(table)
@@ -109,39 +115,123 @@ impl PageTable {
         assert!(is_aligned(paddr.as_usize(), PAGE_SIZE));
         assert!(is_aligned(len, PAGE_SIZE));
 
-        let mut flags = PTE_V;
-        if prot.contains(PageProtect::READABLE) {
-            flags |= PTE_R;
-        }
-        if prot.contains(PageProtect::WRITEABLE) {
-            flags |= PTE_W;
-        }
-        if prot.contains(PageProtect::EXECUTABLE) {
-            flags |= PTE_X;
-        }
-        if prot.contains(PageProtect::USER) {
-            flags |= PTE_U;
-        }
-
-        if flags & (PTE_R | PTE_W | PTE_X) == 0 {
-            // Invalid leaf entry pattern: this does not mean an inaccessible leaf page,
-            // but it is a pointer to the next level table!
-            debug_warn!("map_page: {} -> {} has no permissions", vaddr, paddr);
-            return Err(ErrorCode::InvalidArg);
-        }
+        let flags = prot_to_pte_flags(prot)?;
 
+        // Check the whole range first not to leave a partial mapping on
+        // failure. This also allocates all intermediate tables, so filling
+        // the entries below won't fail.
         let mut iter = PteIter::new(&self.l0_table, vaddr, len)?;
         while let Some(pte) = iter.next_entry()? {
             if pte.is_valid() {
                 return Err(ErrorCode::AlreadyMapped);
             }
+        }
 
+        let mut iter = PteIter::new(&self.l0_table, vaddr, len)?;
+        while let Some(pte) = iter.next_entry()? {
             pte.set(paddr, flags);
             paddr = paddr.add(PAGE_SIZE);
-            // FIXME: Invalidate TLB
         }
         Ok(())
     }
+
+    pub fn unmap(&mut self, vaddr: VAddr, len: usize) -> Result<(), ErrorCode> {
+        assert!(is_aligned(vaddr.as_usize(), PAGE_SIZE));
+        assert!(is_aligned(len, PAGE_SIZE));
+
+        let mut iter = PteIter::new(&self.l0_table, vaddr, len)?;
+        while let Some(pte) = iter.next_entry()? {
+            pte.clear();
+        }
+        Ok(())
+    }
+
+    /// Changes the protection of the pages in `[vaddr, vaddr + len)`. All
+    /// pages in the range must be mapped.
+    pub fn protect(
+        &mut self,
+        vaddr: VAddr,
+        len: usize,
+        prot: PageProtect,
+    ) -> Result<(), ErrorCode> {
+        assert!(is_aligned(vaddr.as_usize(), PAGE_SIZE));
+        assert!(is_aligned(len, PAGE_SIZE));
+
+        let flags = prot_to_pte_flags(prot)?;
+
+        // Check the whole range first not to change the protection partially.
+        for offset in (0..len).step_by(PAGE_SIZE) {
+            if lookup_leaf(&self.l0_table, vaddr.add(offset))?.is_none() {
+                return Err(ErrorCode::NotFound);
+            }
+        }
+
+        let mut iter = PteIter::new(&self.l0_table, vaddr, len)?;
+        while let Some(pte) = iter.next_entry()? {
+            let paddr = pte.paddr();
+            pte.set(paddr, flags);
+        }
+        Ok(())
+    }
+}
+
+fn prot_to_pte_flags(prot: PageProtect) -> Result<u64, ErrorCode> {
+    let mut flags = PTE_V;
+    if prot.contains(PageProtect::READABLE) {
+        flags |= PTE_R;
+    }
+    if prot.contains(PageProtect::WRITEABLE) {
+        flags |= PTE_W;
+    }
+    if prot.contains(PageProtect::EXECUTABLE) {
+        flags |= PTE_X;
+    }
+    if prot.contains(PageProtect::USER) {
+        flags |= PTE_U;
+    }
+
+    if flags & (PTE_R | PTE_W | PTE_X) == 0 {
+        // Invalid leaf entry pattern: this does not mean an inaccessible leaf page,
+        // but it is a pointer to the next level table!
+        debug_warn!("page protection {:?} has no permissions", prot);
+        return Err(ErrorCode::InvalidArg);
+    }
+
+    Ok(flags)
+}
+
+/// Checks that `[vaddr, vaddr + len)` can be used for a fixed mapping.
+fn check_fixed_range(vaddr: VAddr, len: usize) -> Result<(), ErrorCode> {
+    if !is_aligned(vaddr.as_usize(), PAGE_SIZE) || !is_aligned(len, PAGE_SIZE) {
+        return Err(ErrorCode::InvalidArg);
+    }
+
+    // Only the lower half of the address space is available: Sv48 requires
+    // the upper bits to be copies of bit 47.
+    let end = match vaddr.as_usize().checked_add(len) {
+        Some(end) if end <= USER_SPACE_END => end,
+        _ => return Err(ErrorCode::InvalidArg),
+    };
+
+    // The VALLOC region is reserved for `map_anywhere`. A fixed mapping
+    // there would collide with a future allocation.
+    if vaddr <= VALLOC_END && end > VALLOC_START.as_usize() {
+        debug_warn!("map_fixed: {} overlaps with the VALLOC region", vaddr);
+        return Err(ErrorCode::InvalidArg);
+    }
+
+    Ok(())
+}
+
+/// Invalidates TLB entries for `[vaddr, vaddr + len)` on all CPUs.
+fn flush_tlb(vaddr: VAddr, len: usize) {
+    if sbi::remote_sfence_vma_all(vaddr.as_usize(), len).is_err() {
+        // FIXME: Other CPUs may keep stale entries. Send IPIs ourselves.
+        debug_warn!("SBI remote sfence.vma failed, flushing the local TLB only");
+        unsafe {
+            asm!("sfence.vma");
+        }
+    }
 }
 
 struct VAlloc {
@@ -204,6 +294,108 @@ impl VmSpace {
         Ok(vaddr)
     }
 
+    pub fn map_fixed(
+        &self,
+        vaddr: VAddr,
+        paddr: PAddr,
+        len: usize,
+        prot: PageProtect,
+    ) -> Result<(), ErrorCode> {
+        check_fixed_range(vaddr, len)?;
+
+        // Overlaps with existing mappings are checked in `PageTable::map`.
+        let mut mutable = self.mutable.lock();
+        mutable.table.map(vaddr, paddr, len, prot)?;
+        Ok(())
+    }
+
+    /// Allocates a virtual address range without mapping anything. Pages are
+    /// mapped later by [`VmSpace::map_page`].
+    pub fn reserve_anywhere(&self, len: usize) -> Result<VAddr, ErrorCode> {
+        assert!(is_aligned(len, PAGE_SIZE));
+
+        let mut mutable = self.mutable.lock();
+        mutable.valloc.alloc(len)
+    }
+
+    /// Checks that `[vaddr, vaddr + len)` is available for a fixed mapping,
+    /// which will be mapped later by [`VmSpace::map_page`].
+    pub fn reserve_fixed(&self, vaddr: VAddr, len: usize) -> Result<(), ErrorCode> {
+        check_fixed_range(vaddr, len)?;
+
+        let mutable = self.mutable.lock();
+        for offset in (0..len).step_by(PAGE_SIZE) {
+            if lookup_leaf(&mutable.table.l0_table, vaddr.add(offset))?.is_some() {
+                return Err(ErrorCode::AlreadyMapped);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Maps a page in a reserved range. It's not an error if the page is
+    /// already mapped: another CPU may have handled a page fault on the same
+    /// page.
+    pub fn map_page(&self, vaddr: VAddr, paddr: PAddr, prot: PageProtect) -> Result<(), ErrorCode> {
+        let mut mutable = self.mutable.lock();
+        match mutable.table.map(vaddr, paddr, PAGE_SIZE, prot) {
+            Ok(()) | Err(ErrorCode::AlreadyMapped) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Unmaps the pages in `[vaddr, vaddr + len)`.
+    pub fn unmap(&self, vaddr: VAddr, len: usize) -> Result<(), ErrorCode> {
+        if !is_aligned(vaddr.as_usize(), PAGE_SIZE) || !is_aligned(len, PAGE_SIZE) {
+            return Err(ErrorCode::InvalidArg);
+        }
+
+        let mut mutable = self.mutable.lock();
+        mutable.table.unmap(vaddr, len)?;
+        flush_tlb(vaddr, len);
+        Ok(())
+    }
+
+    /// Changes the protection of the pages in `[vaddr, vaddr + len)`.
+    pub fn protect(&self, vaddr: VAddr, len: usize, prot: PageProtect) -> Result<(), ErrorCode> {
+        if !is_aligned(vaddr.as_usize(), PAGE_SIZE) || !is_aligned(len, PAGE_SIZE) {
+            return Err(ErrorCode::InvalidArg);
+        }
+
+        let mut mutable = self.mutable.lock();
+        mutable.table.protect(vaddr, len, prot)?;
+        flush_tlb(vaddr, len);
+        Ok(())
+    }
+
+    /// Translates `vaddr` into the physical address it is mapped to, along with
+    /// the protection of the page.
+    pub fn lookup(&self, vaddr: VAddr) -> Result<(PAddr, PageProtect), ErrorCode> {
+        let mutable = self.mutable.lock();
+        let pte = match lookup_leaf(&mutable.table.l0_table, vaddr)? {
+            Some(pte) => pte,
+            None => return Err(ErrorCode::NotFound),
+        };
+
+        let flags = pte.flags();
+        let mut prot = PageProtect::zeroed();
+        if flags & PTE_R != 0 {
+            prot |= PageProtect::READABLE;
+        }
+        if flags & PTE_W != 0 {
+            prot |= PageProtect::WRITEABLE;
+        }
+        if flags & PTE_X != 0 {
+            prot |= PageProtect::EXECUTABLE;
+        }
+        if flags & PTE_U != 0 {
+            prot |= PageProtect::USER;
+        }
+
+        let offset = vaddr.as_usize() % PAGE_SIZE;
+        Ok((pte.paddr().add(offset), prot))
+    }
+
     pub fn switch(&self) {
         let old_satp: u64;
         unsafe {
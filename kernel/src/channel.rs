@@ -6,6 +6,8 @@ use arrayvec::ArrayVec;
 use starina::message::MESSAGE_DATA_LEN_MAX;
 use starina_types::error::ErrorCode;
 use starina_types::handle::HandleId;
+use starina_types::handle::HandleKind;
+use starina_types::handle::HandleRights;
 use starina_types::message::MESSAGE_NUM_HANDLES_MAX;
 use starina_types::message::MessageInfo;
 use starina_types::poll::Readiness;
@@ -14,15 +16,24 @@ use crate::handle::AnyHandle;
 use crate::handle::HandleTable;
 use crate::handle::Handleable;
 use crate::isolation::Isolation;
+use crate::isolation::IsolationPtr;
 use crate::isolation::IsolationSlice;
 use crate::isolation::IsolationSliceMut;
 use crate::poll::Listener;
 use crate::poll::ListenerSet;
 use crate::refcount::SharedRef;
 use crate::spinlock::SpinLock;
+use crate::syscall::SyscallResult;
+use crate::thread::Thread;
+use crate::thread::ThreadState;
 
 pub const MESSAGE_QUEUE_MAX_LEN: usize = 128;
 
+/// Once a sender has hit [`MESSAGE_QUEUE_MAX_LEN`], the peer becomes
+/// writable again only after the queue drains to this length. This prevents
+/// the sender from waking up for every single free slot.
+const MESSAGE_QUEUE_WRITABLE_WATERMARK: usize = MESSAGE_QUEUE_MAX_LEN / 2;
+
 /// A message queue entry.
 struct MessageEntry {
     msginfo: MessageInfo,
@@ -30,6 +41,26 @@ struct MessageEntry {
     handles: ArrayVec<AnyHandle, MESSAGE_NUM_HANDLES_MAX>,
 }
 
+impl MessageEntry {
+    /// Returns the call ID, the first 4 bytes of the message data, if it's a
+    /// reply. Other messages are never taken as a reply, whatever their data
+    /// is.
+    fn call_id(&self) -> Option<u32> {
+        if !self.msginfo.is_reply() {
+            return None;
+        }
+
+        let bytes = self.data.get(..size_of::<u32>())?;
+        Some(u32::from_ne_bytes(bytes.try_into().unwrap()))
+    }
+}
+
+/// A thread blocked in `channel_call`, waiting for the reply.
+struct PendingCall {
+    call_id: u32,
+    thread: SharedRef<Thread>,
+}
+
 /// Channel object fields that are mutable.
 struct Mutable {
     /// The peer channel. If it's `None`, the peer is not connected anymore
@@ -38,6 +69,13 @@ struct Mutable {
     /// The received message queue.
     queue: VecDeque<MessageEntry>,
     listeners: ListenerSet,
+    /// Calls waiting for their replies. Replies to them are not visible to
+    /// `recv`.
+    pending_calls: Vec<PendingCall>,
+    /// Whether the peer has failed to send a message because the queue is
+    /// full. The peer is not writable until the queue drains to
+    /// [`MESSAGE_QUEUE_WRITABLE_WATERMARK`].
+    backpressured: bool,
 }
 
 impl Mutable {
@@ -46,8 +84,82 @@ impl Mutable {
             peer: None,
             queue: VecDeque::new(),
             listeners: ListenerSet::new(),
+            pending_calls: Vec::new(),
+            backpressured: false,
         }
     }
+
+    fn pending_call(&self, entry: &MessageEntry) -> Option<&PendingCall> {
+        let call_id = entry.call_id()?;
+        self.pending_calls
+            .iter()
+            .find(|call| call.call_id == call_id)
+    }
+
+    /// Whether the peer can send a message to this channel.
+    fn is_peer_writable(&self) -> bool {
+        !self.backpressured && self.queue.len() < MESSAGE_QUEUE_MAX_LEN
+    }
+
+    /// Clears the backpressure if the queue has drained enough. Returns the
+    /// peer to notify with [`Channel::notify_writable`] after releasing the
+    /// lock.
+    fn clear_backpressure(&mut self) -> Option<SharedRef<Channel>> {
+        if !self.backpressured || self.queue.len() > MESSAGE_QUEUE_WRITABLE_WATERMARK {
+            return None;
+        }
+
+        self.backpressured = false;
+        self.peer.clone()
+    }
+
+    /// Whether the queue has a message other than replies to pending calls.
+    fn has_unclaimed(&self) -> bool {
+        self.queue
+            .iter()
+            .any(|entry| self.pending_call(entry).is_none())
+    }
+}
+
+/// The caller's buffers of `channel_call`. The message is sent from, and the
+/// reply is received into them.
+#[derive(Clone, Copy)]
+pub struct CallBuffers {
+    /// The data buffer.
+    pub data: IsolationPtr,
+    /// The size of the data buffer.
+    pub data_len: usize,
+    /// The handles buffer, which must have room for
+    /// [`MESSAGE_NUM_HANDLES_MAX`] handles.
+    pub handles: IsolationPtr,
+}
+
+/// The state of a thread blocked in `channel_call`.
+#[derive(Clone)]
+pub struct BlockedCall {
+    ch: SharedRef<Channel>,
+    call_id: u32,
+    buffers: CallBuffers,
+}
+
+impl BlockedCall {
+    /// Tries to receive the reply, as part of resuming the blocked thread.
+    pub fn try_complete(&self, current: &SharedRef<Thread>) -> SyscallResult {
+        let process = current.process();
+        let mut handle_table = process.handles().lock();
+        self.ch.try_recv_reply(
+            process.isolation(),
+            &mut handle_table,
+            self.call_id,
+            self.buffers,
+        )
+    }
+}
+
+impl fmt::Debug for BlockedCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BlockedCall({})", self.call_id)
+    }
 }
 
 pub struct Channel {
@@ -71,6 +183,45 @@ impl Channel {
         Ok((ch0, ch1))
     }
 
+    /// Locks this channel and its peer, and calls `f` with them. The peer is
+    /// `None` if it's disconnected.
+    ///
+    /// Both ends are always locked in the order of their addresses, so that
+    /// CPUs working on both ends at once never deadlock.
+    fn lock_with_peer<R>(&self, f: impl FnOnce(&mut Mutable, Option<&mut Mutable>) -> R) -> R {
+        loop {
+            let Some(peer) = self.mutable.lock().peer.clone() else {
+                // Once disconnected, the peer never comes back.
+                return f(&mut self.mutable.lock(), None);
+            };
+
+            let (mut mutable, mut peer_mutable) =
+                if core::ptr::from_ref(self) < core::ptr::from_ref(&*peer) {
+                    let mutable = self.mutable.lock();
+                    (mutable, peer.mutable.lock())
+                } else {
+                    let peer_mutable = peer.mutable.lock();
+                    (self.mutable.lock(), peer_mutable)
+                };
+
+            // The peer might have been disconnected while we didn't hold the
+            // lock.
+            if mutable.peer.is_none() {
+                continue;
+            }
+
+            return f(&mut mutable, Some(&mut peer_mutable));
+        }
+    }
+
+    /// Notifies listeners that the peer can send messages again.
+    fn notify_writable(&self) {
+        self.mutable
+            .lock()
+            .listeners
+            .notify_all(Readiness::WRITABLE);
+    }
+
     pub fn do_send(
         &self,
         msginfo: MessageInfo,
@@ -80,31 +231,53 @@ impl Channel {
         debug_assert_eq!(msgbuffer.len(), msginfo.data_len());
         debug_assert_eq!(msginfo.num_handles(), handles.len());
 
-        let mutable = self.mutable.lock();
-        let peer_ch = mutable.peer.as_ref().ok_or(ErrorCode::NoPeer)?;
-        let mut peer_mutable = peer_ch.mutable.lock();
-
-        // Check if the peer's queue is full.
-        if peer_mutable.queue.len() >= MESSAGE_QUEUE_MAX_LEN {
-            return Err(ErrorCode::Full);
-        }
-
-        // Allocate space for the message in the peer's queue so that
-        // `VecDeque::push_back` won't panic.
-        if peer_mutable.queue.try_reserve_exact(1).is_err() {
-            return Err(ErrorCode::OutOfMemory);
-        }
-
-        // The message is ready to be sent. Enqueue it.
-        peer_mutable.queue.push_back(MessageEntry {
+        self.enqueue(MessageEntry {
             msginfo,
             data: msgbuffer,
             handles,
+        })
+    }
+
+    /// Enqueues a message into the peer's queue. On failure, the message is
+    /// dropped: the caller is responsible for giving the handles back.
+    fn enqueue(&self, entry: MessageEntry) -> Result<(), ErrorCode> {
+        let mut entry = Some(entry);
+        let result = self.lock_with_peer(|_mutable, peer_mutable| {
+            let Some(peer_mutable) = peer_mutable else {
+                return Err(ErrorCode::NoPeer);
+            };
+
+            // Check if the peer's queue is full.
+            if peer_mutable.queue.len() >= MESSAGE_QUEUE_MAX_LEN {
+                peer_mutable.backpressured = true;
+                return Err(ErrorCode::Full);
+            }
+
+            // Allocate space for the message in the peer's queue so that
+            // `VecDeque::push_back` won't panic.
+            if peer_mutable.queue.try_reserve_exact(1).is_err() {
+                return Err(ErrorCode::OutOfMemory);
+            }
+
+            // The message is ready to be sent. Enqueue it.
+            peer_mutable.queue.push_back(entry.take().unwrap());
+
+            // If it's a reply to a pending call, wake up the caller. Other
+            // receivers won't see it.
+            let entry = peer_mutable.queue.back().unwrap();
+            if let Some(call) = peer_mutable.pending_call(entry) {
+                call.thread.wake();
+                return Ok(());
+            }
+
+            // So the peer has at least one message to read. Wake up a listener if any.
+            peer_mutable.listeners.notify_all(Readiness::READABLE);
+            Ok(())
         });
 
-        // So the peer has at least one message to read. Wake up a listener if any.
-        peer_mutable.listeners.notify_all(Readiness::READABLE);
-        Ok(())
+        // Drop the message not sent after releasing the locks.
+        drop(entry);
+        result
     }
 
     pub fn send(
@@ -114,6 +287,7 @@ impl Channel {
         msginfo: MessageInfo,
         msgbuffer: IsolationSlice,
         handles: IsolationSlice,
+        rights: Option<IsolationSlice>,
     ) -> Result<(), ErrorCode> {
         if msginfo.data_len() > MESSAGE_DATA_LEN_MAX {
             debug_warn!("too large message data: {}", msginfo.data_len());
@@ -132,6 +306,10 @@ impl Channel {
         // in the message entry.
         let num_handles = msginfo.num_handles();
         let mut moved_handles = ArrayVec::new();
+        // The handles before attenuating the rights, to restore them if
+        // sending fails.
+        let mut original_handles: ArrayVec<(HandleId, AnyHandle), MESSAGE_NUM_HANDLES_MAX> =
+            ArrayVec::new();
         if num_handles > 0 {
             // Note: Don't release this lock until we've moved all handles
             //       to guarantee that the second loop never fails.
@@ -139,12 +317,21 @@ impl Channel {
             // First loop: make sure moving handles won't fail and there are
             //             not too many ones.
             let mut handle_ids: ArrayVec<HandleId, MESSAGE_NUM_HANDLES_MAX> = ArrayVec::new();
+            let mut rights_masks: ArrayVec<HandleRights, MESSAGE_NUM_HANDLES_MAX> = ArrayVec::new();
             for i in 0..num_handles {
                 let handle_id = handles.read(isolation, i * size_of::<HandleId>())?;
+                let rights_mask = match &rights {
+                    Some(rights) => {
+                        let raw: u8 = rights.read(isolation, i * size_of::<HandleRights>())?;
+                        HandleRights::from_raw_isize(raw as isize)?
+                    }
+                    None => HandleRights::ALL,
+                };
 
                 // SAFETY: unwrap() won't panic because it should have enough
                 //         capacity up to MESSAGE_HANDLES_MAX_COUNT.
                 handle_ids.try_push(handle_id).unwrap();
+                rights_masks.try_push(rights_mask).unwrap();
 
                 if !handle_table.is_movable(handle_id) {
                     return Err(ErrorCode::HandleNotMovable);
@@ -163,11 +350,29 @@ impl Channel {
 
                 // SAFETY: unwrap() won't panic because `handles` should have
                 //         enough capacity up to MESSAGE_NUM_HANDLES_MAX.
-                moved_handles.try_push(handle).unwrap();
+                moved_handles
+                    .try_push(handle.clone().attenuate(rights_masks[i]))
+                    .unwrap();
+                original_handles.try_push((handle_id, handle)).unwrap();
             }
         }
 
-        self.do_send(msginfo, data, moved_handles)?;
+        let entry = MessageEntry {
+            msginfo,
+            data,
+            handles: moved_handles,
+        };
+
+        if let Err(err) = self.enqueue(entry) {
+            // Give the handles back to the sender so that it can retry
+            // sending the message later, e.g. when the queue is full.
+            for (handle_id, handle) in original_handles {
+                handle_table.restore(handle_id, handle);
+            }
+
+            return Err(err);
+        }
+
         Ok(())
     }
 
@@ -178,9 +383,13 @@ impl Channel {
         msgbuffer: IsolationSliceMut,
         handles: IsolationSliceMut,
     ) -> Result<MessageInfo, ErrorCode> {
-        let mut entry = {
+        let (entry, writable_peer) = {
             let mut mutable = self.mutable.lock();
-            let entry = match mutable.queue.pop_front() {
+            let index = mutable
+                .queue
+                .iter()
+                .position(|entry| mutable.pending_call(entry).is_none());
+            let entry = match index.and_then(|i| mutable.queue.remove(i)) {
                 Some(entry) => entry,
                 None => {
                     // Check if the peer is still connected only if the queue is
@@ -198,24 +407,157 @@ impl Channel {
                 }
             };
 
-            if !mutable.queue.is_empty() {
+            if mutable.has_unclaimed() {
                 // There are more messages in the queue. Mark this channel as
                 // still readable.
                 mutable.listeners.notify_all(Readiness::READABLE);
             }
 
-            if let Some(peer) = &mutable.peer {
-                // The peer is still connected. Notify the peer channel's
-                // listeners that we're ready to receive at least one message.
-                peer.mutable
-                    .lock()
-                    .listeners
-                    .notify_all(Readiness::WRITABLE);
+            (entry, mutable.clear_backpressure())
+        };
+
+        if let Some(peer) = writable_peer {
+            peer.notify_writable();
+        }
+
+        Self::deliver(isolation, handle_table, entry, msgbuffer, handles)
+    }
+
+    /// Sends a message, and blocks the current thread until the reply with
+    /// the same call ID arrives.
+    ///
+    /// The call ID is the first 4 bytes of the message data. The reply is
+    /// received into the same buffers as the message.
+    pub fn call(
+        self: &SharedRef<Channel>,
+        current: &SharedRef<Thread>,
+        isolation: &dyn Isolation,
+        handle_table: &mut HandleTable,
+        msginfo: MessageInfo,
+        buffers: CallBuffers,
+    ) -> SyscallResult {
+        if msginfo.data_len() < size_of::<u32>() {
+            return SyscallResult::Err(ErrorCode::TooSmall);
+        }
+
+        if buffers.data_len < msginfo.data_len() {
+            return SyscallResult::Err(ErrorCode::InvalidArg);
+        }
+
+        let msgbuffer = IsolationSlice::new(buffers.data, msginfo.data_len());
+        let call_id: u32 = match msgbuffer.read(isolation, 0) {
+            Ok(call_id) => call_id,
+            Err(err) => return SyscallResult::Err(err),
+        };
+
+        // Register the call before sending the message so that the reply
+        // never goes to `recv`.
+        {
+            let mut mutable = self.mutable.lock();
+            if mutable
+                .pending_calls
+                .iter()
+                .any(|call| call.call_id == call_id)
+            {
+                return SyscallResult::Err(ErrorCode::AlreadyExists);
+            }
+
+            if mutable.pending_calls.try_reserve(1).is_err() {
+                return SyscallResult::Err(ErrorCode::OutOfMemory);
             }
 
-            entry
+            mutable.pending_calls.push(PendingCall {
+                call_id,
+                thread: current.clone(),
+            });
+        }
+
+        let handles_slice = IsolationSlice::new(
+            buffers.handles,
+            size_of::<HandleId>() * msginfo.num_handles(),
+        );
+        if let Err(err) = self.send(
+            isolation,
+            handle_table,
+            msginfo,
+            msgbuffer,
+            handles_slice,
+            None,
+        ) {
+            self.mutable
+                .lock()
+                .pending_calls
+                .retain(|call| call.call_id != call_id);
+            return SyscallResult::Err(err);
+        }
+
+        self.try_recv_reply(isolation, handle_table, call_id, buffers)
+    }
+
+    fn try_recv_reply(
+        self: &SharedRef<Channel>,
+        isolation: &dyn Isolation,
+        handle_table: &mut HandleTable,
+        call_id: u32,
+        buffers: CallBuffers,
+    ) -> SyscallResult {
+        let (entry, writable_peer) = {
+            let mut mutable = self.mutable.lock();
+            let index = mutable
+                .queue
+                .iter()
+                .position(|entry| entry.call_id() == Some(call_id));
+
+            match index {
+                Some(index) => {
+                    let entry = mutable.queue.remove(index).unwrap();
+                    mutable.pending_calls.retain(|call| call.call_id != call_id);
+                    (entry, mutable.clear_backpressure())
+                }
+                None if mutable.peer.is_none() => {
+                    // The reply will never arrive.
+                    mutable.pending_calls.retain(|call| call.call_id != call_id);
+                    return SyscallResult::Err(ErrorCode::NoPeer);
+                }
+                None => {
+                    return SyscallResult::Block(ThreadState::BlockedByCall(BlockedCall {
+                        ch: self.clone(),
+                        call_id,
+                        buffers,
+                    }));
+                }
+            }
         };
 
+        if let Some(peer) = writable_peer {
+            peer.notify_writable();
+        }
+
+        if entry.msginfo.data_len() > buffers.data_len {
+            // The reply doesn't fit in the buffer. The call is done anyway:
+            // drop the reply along with its handles.
+            return SyscallResult::Err(ErrorCode::TooSmall);
+        }
+
+        let msgbuffer = IsolationSliceMut::new(buffers.data, buffers.data_len);
+        let handles = IsolationSliceMut::new(
+            buffers.handles,
+            size_of::<HandleId>() * MESSAGE_NUM_HANDLES_MAX,
+        );
+        match Self::deliver(isolation, handle_table, entry, msgbuffer, handles) {
+            Ok(msginfo) => SyscallResult::Done(msginfo.into()),
+            Err(err) => SyscallResult::Err(err),
+        }
+    }
+
+    /// Copies a received message into the receiver process.
+    fn deliver(
+        isolation: &dyn Isolation,
+        handle_table: &mut HandleTable,
+        mut entry: MessageEntry,
+        msgbuffer: IsolationSliceMut,
+        handles: IsolationSliceMut,
+    ) -> Result<MessageInfo, ErrorCode> {
         // Install handles into the current (receiver) process.
         for (i, any_handle) in entry.handles.drain(..).enumerate() {
             // TODO: Define the expected behavior when it fails to add a handle.
@@ -232,14 +574,19 @@ impl Channel {
 
 impl Handleable for Channel {
     fn close(&self) {
-        let mutable = self.mutable.lock();
-        if let Some(peer) = &mutable.peer {
-            let mut peer_mutable = peer.mutable.lock();
-            peer_mutable.peer = None;
-            peer_mutable.listeners.notify_all(Readiness::CLOSED);
-        }
+        self.lock_with_peer(|mutable, peer_mutable| {
+            if let Some(peer_mutable) = peer_mutable {
+                peer_mutable.peer = None;
+                peer_mutable.listeners.notify_all(Readiness::CLOSED);
+
+                // Callers on the peer side will never receive their replies.
+                for call in &peer_mutable.pending_calls {
+                    call.thread.wake();
+                }
+            }
 
-        mutable.listeners.notify_all(Readiness::CLOSED);
+            mutable.listeners.notify_all(Readiness::CLOSED);
+        });
     }
 
     fn add_listener(&self, listener: Listener) -> Result<(), ErrorCode> {
@@ -253,24 +600,36 @@ impl Handleable for Channel {
     }
 
     fn readiness(&self) -> Result<Readiness, ErrorCode> {
-        let mut readiness = Readiness::new();
-        let mutable = self.mutable.lock();
-        if !mutable.queue.is_empty() {
-            readiness |= Readiness::READABLE;
-        }
+        let readiness = self.lock_with_peer(|mutable, peer_mutable| {
+            let mut readiness = Readiness::new();
+            if mutable.has_unclaimed() {
+                readiness |= Readiness::READABLE;
+            }
 
-        if let Some(peer) = mutable.peer.as_ref() {
-            let peer_mutable = peer.mutable.lock();
-            if peer_mutable.queue.len() < MESSAGE_QUEUE_MAX_LEN {
-                readiness |= Readiness::WRITABLE;
+            match peer_mutable {
+                Some(peer_mutable) if peer_mutable.is_peer_writable() => {
+                    readiness |= Readiness::WRITABLE;
+                }
+                Some(_) => {}
+                None => {
+                    // Peer is disconnected, channel is closed
+                    readiness |= Readiness::CLOSED;
+                }
             }
-        } else {
-            // Peer is disconnected, channel is closed
-            readiness |= Readiness::CLOSED;
-        }
+
+            readiness
+        });
 
         Ok(readiness)
     }
+
+    fn kind(&self) -> HandleKind {
+        let mutable = self.mutable.lock();
+        HandleKind::Channel {
+            queue_len: mutable.queue.len(),
+            peer_alive: mutable.peer.is_some(),
+        }
+    }
 }
 
 impl fmt::Debug for Channel {
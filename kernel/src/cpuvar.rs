@@ -1,9 +1,10 @@
 //! Per-CPU variables.
+use core::cell::Cell;
 use core::cell::Ref;
 use core::cell::RefCell;
 use core::fmt;
 
-use arrayvec::ArrayVec;
+use starina::address::VAddr;
 
 use crate::arch;
 use crate::refcount::SharedRef;
@@ -43,6 +44,9 @@ pub struct CpuVar {
     pub cpu_id: CpuId,
     pub current_thread: RefCell<SharedRef<Thread>>,
     pub idle_thread: SharedRef<Thread>,
+    /// When the current thread's time slice expires, in timer ticks. `None`
+    /// while the CPU is idle.
+    pub time_slice_expires_at: Cell<Option<u64>>,
 }
 
 // SAFETY: `CpuVar` is a per-CPU storage. Will never be shared between CPUs
@@ -61,34 +65,34 @@ pub fn current_thread() -> Ref<'static, SharedRef<Thread>> {
     arch::get_cpuvar().current_thread.borrow()
 }
 
-const NUM_CPUS_MAX: usize = 4;
+/// The maximum number of CPUs. CPU IDs must be less than this.
+pub const NUM_CPUS_MAX: usize = 4;
 
 // Note: SpinLock is to serialize its initialization. Once initialized, it's
 //       safe to access `CpuVar` without holding the lock because it's a
 //       per-CPU storage. We still need a RefCell in mutable fields though.
-static CPUVARS: SpinLock<ArrayVec<CpuVarInit, { NUM_CPUS_MAX }>> =
-    SpinLock::new(ArrayVec::new_const());
+static CPUVARS: SpinLock<[Option<CpuVarInit>; NUM_CPUS_MAX]> =
+    SpinLock::new([const { None }; NUM_CPUS_MAX]);
 
-/// Initializes Per-CPU variables for the current CPU.
-pub fn percpu_init(cpu_id: CpuId) {
+/// Initializes Per-CPU variables for the current CPU. `kernel_stack_top` is
+/// the stack used when the CPU enters the kernel.
+pub fn percpu_init(cpu_id: CpuId, kernel_stack_top: VAddr) {
     let mut cpuvars = CPUVARS.lock();
-    let index = cpu_id.as_usize();
-    if cpuvars.len() <= index {
-        // Initialize CpuVar slots until the CPU.
-        for _ in 0..=index {
-            let idle_thread = Thread::new_idle().expect("failed to create an idle thread");
-            let cpuvar = CpuVar {
-                arch: arch::CpuVar::new(&idle_thread),
-                cpu_id,
-                current_thread: RefCell::new(idle_thread.clone()),
-                idle_thread,
-            };
-
-            if cpuvars.try_push(CpuVarInit(cpuvar)).is_err() {
-                panic!("too many CPUs");
-            }
-        }
-    }
-
-    arch::set_cpuvar(&cpuvars[cpu_id.as_usize()].0 as *const CpuVar);
+    let Some(slot) = cpuvars.get_mut(cpu_id.as_usize()) else {
+        panic!("too many CPUs: {}", cpu_id);
+    };
+
+    assert!(slot.is_none(), "CPU {cpu_id} is already initialized");
+
+    let idle_thread = Thread::new_idle().expect("failed to create an idle thread");
+    let cpuvar = slot.insert(CpuVarInit(CpuVar {
+        arch: arch::CpuVar::new(&idle_thread, kernel_stack_top),
+        cpu_id,
+        current_thread: RefCell::new(idle_thread.clone()),
+        idle_thread,
+        time_slice_expires_at: Cell::new(None),
+    }));
+
+    // The slot is never moved or freed, so it's safe to keep the pointer.
+    arch::set_cpuvar(&cpuvar.0 as *const CpuVar);
 }
@@ -0,0 +1,193 @@
+//! ELF loader for user-mode processes.
+//!
+//! Only statically-linked executables (`ET_EXEC`) are supported: no dynamic
+//! linking, no relocations, no interpreter. Also, `PT_LOAD` segments must not
+//! share a page.
+use alloc::vec::Vec;
+use core::mem::size_of;
+use core::slice;
+
+use starina_types::address::VAddr;
+use starina_types::error::ErrorCode;
+use starina_types::vmspace::PageProtect;
+use starina_utils::alignment::align_down;
+use starina_utils::alignment::align_up;
+
+use crate::arch;
+use crate::arch::PAGE_SIZE;
+use crate::folio::Folio;
+use crate::memory_account::MemoryAccount;
+use crate::refcount::SharedRef;
+use crate::vmspace::VmSpace;
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ET_EXEC: u16 = 2;
+const EM_RISCV: u16 = 243;
+const PT_LOAD: u32 = 1;
+const PF_X: u32 = 1 << 0;
+const PF_W: u32 = 1 << 1;
+const PF_R: u32 = 1 << 2;
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct Ehdr {
+    ident: [u8; 16],
+    ty: u16,
+    machine: u16,
+    version: u32,
+    entry: u64,
+    phoff: u64,
+    shoff: u64,
+    flags: u32,
+    ehsize: u16,
+    phentsize: u16,
+    phnum: u16,
+    shentsize: u16,
+    shnum: u16,
+    shstrndx: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct Phdr {
+    ty: u32,
+    flags: u32,
+    offset: u64,
+    vaddr: u64,
+    paddr: u64,
+    filesz: u64,
+    memsz: u64,
+    align: u64,
+}
+
+/// Reads a `T` at `offset` in `bytes`, with bounds checking.
+fn read_at<T: Copy>(bytes: &[u8], offset: usize) -> Result<T, ErrorCode> {
+    let end = offset
+        .checked_add(size_of::<T>())
+        .ok_or(ErrorCode::InvalidArg)?;
+    if end > bytes.len() {
+        return Err(ErrorCode::InvalidArg);
+    }
+
+    // SAFETY: The range is checked above. The ELF image is not necessarily
+    //         aligned for `T`.
+    let value = unsafe { (bytes.as_ptr().add(offset) as *const T).read_unaligned() };
+    Ok(value)
+}
+
+/// Loads an ELF executable in `elf` into `vmspace`, and returns the entry
+/// point.
+///
+/// Each `PT_LOAD` segment is copied into a newly allocated folio, that is,
+/// the process can't modify the original ELF image. The folios are charged
+/// to `account`.
+pub fn load(
+    vmspace: &VmSpace,
+    account: &SharedRef<MemoryAccount>,
+    elf: &Folio,
+) -> Result<usize, ErrorCode> {
+    let elf_vaddr = arch::paddr2vaddr(elf.paddr())?;
+    // SAFETY: The folio is owned by the caller, and it's kept alive while
+    //         we're loading it.
+    let bytes = unsafe { slice::from_raw_parts(elf_vaddr.as_ptr::<u8>(), elf.len()) };
+
+    let ehdr: Ehdr = read_at(bytes, 0)?;
+    if ehdr.ident[..4] != ELF_MAGIC
+        || ehdr.ident[4] != ELFCLASS64
+        || ehdr.ident[5] != ELFDATA2LSB
+        || ehdr.ty != ET_EXEC
+        || ehdr.machine != EM_RISCV
+    {
+        debug_warn!("elf: not a RISC-V 64-bit executable");
+        return Err(ErrorCode::InvalidArg);
+    }
+
+    if ehdr.phentsize as usize != size_of::<Phdr>() {
+        debug_warn!("elf: unexpected phentsize: {}", ehdr.phentsize);
+        return Err(ErrorCode::InvalidArg);
+    }
+
+    let mut segments: Vec<Phdr> = Vec::new();
+    for i in 0..(ehdr.phnum as usize) {
+        let phdr_offset = (ehdr.phoff as usize)
+            .checked_add(i * size_of::<Phdr>())
+            .ok_or(ErrorCode::InvalidArg)?;
+        let phdr: Phdr = read_at(bytes, phdr_offset)?;
+        if phdr.ty != PT_LOAD || phdr.memsz == 0 {
+            continue;
+        }
+
+        // Check before mapping anything: an overlapping segment would
+        // otherwise fail in the middle of loading with `AlreadyMapped`.
+        let (start, end) = page_range(&phdr)?;
+        for other in &segments {
+            let (other_start, other_end) = page_range(other)?;
+            if start < other_end && other_start < end {
+                debug_warn!("elf: segments share a page: {:x?} and {:x?}", other, phdr);
+                return Err(ErrorCode::InvalidArg);
+            }
+        }
+
+        segments.push(phdr);
+    }
+
+    for phdr in &segments {
+        load_segment(vmspace, account, bytes, phdr)?;
+    }
+
+    Ok(ehdr.entry as usize)
+}
+
+/// Returns the page-aligned virtual address range of a segment.
+fn page_range(phdr: &Phdr) -> Result<(usize, usize), ErrorCode> {
+    let vaddr = phdr.vaddr as usize;
+    let vaddr_end = vaddr
+        .checked_add(phdr.memsz as usize)
+        .ok_or(ErrorCode::InvalidArg)?;
+    Ok((align_down(vaddr, PAGE_SIZE), align_up(vaddr_end, PAGE_SIZE)))
+}
+
+fn load_segment(
+    vmspace: &VmSpace,
+    account: &SharedRef<MemoryAccount>,
+    bytes: &[u8],
+    phdr: &Phdr,
+) -> Result<(), ErrorCode> {
+    let offset = phdr.offset as usize;
+    let filesz = phdr.filesz as usize;
+    let memsz = phdr.memsz as usize;
+    let vaddr = phdr.vaddr as usize;
+
+    let file_end = offset.checked_add(filesz).ok_or(ErrorCode::InvalidArg)?;
+    if filesz > memsz || file_end > bytes.len() {
+        debug_warn!("elf: invalid segment: {:x?}", phdr);
+        return Err(ErrorCode::InvalidArg);
+    }
+
+    let (map_start, map_end) = page_range(phdr)?;
+
+    // Folio::alloc fills the memory with zeros, so we don't need to clear
+    // the .bss part.
+    let folio = Folio::alloc_charged(map_end - map_start, account)?;
+    let folio_vaddr = arch::paddr2vaddr(folio.paddr())?;
+    unsafe {
+        let dst = folio_vaddr.as_mut_ptr::<u8>().add(vaddr - map_start);
+        core::ptr::copy_nonoverlapping(bytes.as_ptr().add(offset), dst, filesz);
+    }
+
+    let mut prot = PageProtect::zeroed();
+    if phdr.flags & PF_R != 0 {
+        prot |= PageProtect::READABLE;
+    }
+    if phdr.flags & PF_W != 0 {
+        prot |= PageProtect::WRITEABLE;
+    }
+    if phdr.flags & PF_X != 0 {
+        prot |= PageProtect::EXECUTABLE;
+    }
+
+    vmspace.map_fixed(VAddr::new(map_start), SharedRef::new(folio)?, prot, prot)?;
+    Ok(())
+}
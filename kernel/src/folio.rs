@@ -5,19 +5,30 @@ use core::alloc::Layout;
 use starina_types::address::PAddr;
 use starina_types::address::VAddr;
 use starina_types::error::ErrorCode;
+use starina_types::handle::HandleKind;
 use starina_types::poll::Readiness;
 use starina_utils::alignment::is_aligned;
 
 use crate::allocator::GLOBAL_ALLOCATOR;
 use crate::arch::PAGE_SIZE;
+use crate::arch::paddr2vaddr;
 use crate::arch::vaddr2paddr;
 use crate::handle::Handleable;
+use crate::memory_account::MemoryAccount;
+use crate::memory_account::MemoryCharge;
+use crate::memory_account::MemoryKind;
 use crate::poll::Listener;
 use crate::poll::Poll;
+use crate::refcount::SharedRef;
 
 pub struct Folio {
     paddr: PAddr,
     len: usize,
+    /// Whether the memory is allocated from the kernel's allocator. If so,
+    /// it's returned to the allocator when the folio is dropped.
+    allocated: bool,
+    /// The process the memory is charged to, if any.
+    charge: Option<MemoryCharge>,
 }
 
 impl Folio {
@@ -35,6 +46,9 @@ impl Folio {
 
         // SAFETY: `len` is not zero as checked above.
         let ptr = unsafe { GLOBAL_ALLOCATOR.alloc(layout) };
+        if ptr.is_null() {
+            return Err(ErrorCode::OutOfMemory);
+        }
 
         // Fill the allocated memory with zeros.
         unsafe {
@@ -44,11 +58,25 @@ impl Folio {
         let folio = Self {
             paddr: vaddr2paddr(VAddr::new(ptr as usize)).unwrap(),
             len,
+            allocated: true,
+            charge: None,
         };
 
         Ok(folio)
     }
 
+    /// Allocates a folio, charging the memory to `account` until the folio
+    /// is freed.
+    pub fn alloc_charged(
+        len: usize,
+        account: &SharedRef<MemoryAccount>,
+    ) -> Result<Folio, ErrorCode> {
+        let charge = account.charge(MemoryKind::Folio, len)?;
+        let mut folio = Folio::alloc(len)?;
+        folio.charge = Some(charge);
+        Ok(folio)
+    }
+
     pub fn pin(paddr: PAddr, len: usize) -> Result<Folio, ErrorCode> {
         if len == 0 || !is_aligned(len, PAGE_SIZE) {
             return Err(ErrorCode::InvalidArg);
@@ -61,7 +89,12 @@ impl Folio {
         // TODO: Make sure the paddr range is not owned by any other folio.
         // TODO: Check if the paddr is mappable - should not point to the kernel memory.
 
-        let folio = Self { paddr, len };
+        let folio = Self {
+            paddr,
+            len,
+            allocated: false,
+            charge: None,
+        };
 
         Ok(folio)
     }
@@ -75,6 +108,24 @@ impl Folio {
     }
 }
 
+impl Drop for Folio {
+    fn drop(&mut self) {
+        if !self.allocated {
+            // A pinned folio (e.g. MMIO region). Nothing to free.
+            return;
+        }
+
+        let vaddr = paddr2vaddr(self.paddr).unwrap();
+        let layout = Layout::from_size_align(self.len, PAGE_SIZE).unwrap();
+
+        // SAFETY: The memory was allocated by `GLOBAL_ALLOCATOR` with the
+        // same layout in `Folio::alloc`, and no one references it anymore.
+        unsafe {
+            GLOBAL_ALLOCATOR.dealloc(vaddr.as_usize() as *mut u8, layout);
+        }
+    }
+}
+
 impl Handleable for Folio {
     fn close(&self) {
         // Do nothing
@@ -94,4 +145,8 @@ impl Handleable for Folio {
         debug_warn!("unsupported method at {}:{}", file!(), line!());
         Err(ErrorCode::NotSupported)
     }
+
+    fn kind(&self) -> HandleKind {
+        HandleKind::Folio { len: self.len }
+    }
 }
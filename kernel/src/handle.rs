@@ -1,9 +1,14 @@
-use alloc::collections::btree_map::BTreeMap;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
 use core::any::Any;
 use core::ops::Deref;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
 
 use starina_types::error::ErrorCode;
 use starina_types::handle::HandleId;
+use starina_types::handle::HandleInfo;
+use starina_types::handle::HandleKind;
 use starina_types::handle::HandleRights;
 use starina_types::poll::Readiness;
 
@@ -11,18 +16,23 @@ use crate::poll::Listener;
 use crate::poll::Poll;
 use crate::refcount::SharedRef;
 
-const NUM_HANDLES_MAX: usize = 128;
-
 /// Handle, a reference-counted pointer to a kernel object with allowed
 /// operations on it, aka *"capability"*.
 pub struct Handle<T: Handleable + ?Sized> {
     object: SharedRef<T>,
     rights: HandleRights,
+    /// The number of open handles duplicated from the same handle. `None`
+    /// if the handle has never been duplicated.
+    dups: Option<SharedRef<AtomicUsize>>,
 }
 
 impl<T: Handleable + ?Sized> Handle<T> {
     pub fn new(object: SharedRef<T>, rights: HandleRights) -> Handle<T> {
-        Handle { object, rights }
+        Handle {
+            object,
+            rights,
+            dups: None,
+        }
     }
 
     pub fn into_object(self) -> SharedRef<T> {
@@ -32,6 +42,40 @@ impl<T: Handleable + ?Sized> Handle<T> {
     pub fn is_capable(&self, required: HandleRights) -> bool {
         self.rights.is_capable(required)
     }
+
+    /// Creates a new handle to the same object, with rights restricted to
+    /// `rights_mask`.
+    ///
+    /// The object won't be closed until all duplicated handles are closed.
+    pub fn duplicate(&mut self, rights_mask: HandleRights) -> Result<Handle<T>, ErrorCode> {
+        let dups = match &self.dups {
+            Some(dups) => dups.clone(),
+            None => {
+                let dups = SharedRef::new(AtomicUsize::new(1))?;
+                self.dups = Some(dups.clone());
+                dups
+            }
+        };
+
+        dups.fetch_add(1, Ordering::Relaxed);
+        Ok(Handle {
+            object: self.object.clone(),
+            rights: self.rights & rights_mask,
+            dups: Some(dups),
+        })
+    }
+
+    /// Closes the handle. The object is closed only if this is the last
+    /// handle duplicated from the same handle.
+    pub fn close(self) {
+        if let Some(dups) = &self.dups
+            && dups.fetch_sub(1, Ordering::AcqRel) > 1
+        {
+            return;
+        }
+
+        self.object.close();
+    }
 }
 
 impl<T: Handleable + ?Sized> Deref for Handle<T> {
@@ -47,6 +91,7 @@ impl<T: Handleable + ?Sized> Clone for Handle<T> {
         Handle {
             object: self.object.clone(),
             rights: self.rights,
+            dups: self.dups.clone(),
         }
     }
 }
@@ -58,7 +103,30 @@ impl AnyHandle {
     pub fn downcast<T: Handleable>(self) -> Option<Handle<T>> {
         let object = self.0.object.downcast().ok()?;
         let rights = self.0.rights;
-        Some(Handle { object, rights })
+        let dups = self.0.dups;
+        Some(Handle {
+            object,
+            rights,
+            dups,
+        })
+    }
+
+    pub fn is_capable(&self, required: HandleRights) -> bool {
+        self.0.is_capable(required)
+    }
+
+    pub fn rights(&self) -> HandleRights {
+        self.0.rights
+    }
+
+    /// Restricts the rights of the handle to `rights_mask`.
+    pub fn attenuate(mut self, rights_mask: HandleRights) -> AnyHandle {
+        self.0.rights = self.0.rights & rights_mask;
+        self
+    }
+
+    pub fn close(self) {
+        self.0.close();
     }
 }
 
@@ -67,6 +135,7 @@ impl<T: Handleable> From<Handle<T>> for AnyHandle {
         Self(Handle {
             object: h.object, // upcasting happens here (thanks to CoerceUnsized)
             rights: h.rights,
+            dups: h.dups,
         })
     }
 }
@@ -83,72 +152,119 @@ pub trait Handleable: Any + Send + Sync {
     fn add_listener(&self, listener: Listener) -> Result<(), ErrorCode>;
     fn remove_listener(&self, poll: &Poll) -> Result<(), ErrorCode>;
     fn readiness(&self) -> Result<Readiness, ErrorCode>;
+    /// Describes the object for debugging.
+    fn kind(&self) -> HandleKind;
 }
 
+/// The number of low bits of a handle ID used for the slot index.
+const INDEX_BITS: u32 = 16;
+/// The bits of a handle ID used for the generation, above the index. The
+/// sign bit is not used so that IDs are always positive.
+const GENERATION_MASK: u16 = (1 << (31 - INDEX_BITS)) - 1;
+
+/// The maximum number of handles a process can have.
+pub const HANDLE_LIMIT_MAX: usize = (1 << INDEX_BITS) - 1;
+/// The number of handles a process can have if not specified.
+pub const HANDLE_LIMIT_DEFAULT: usize = 1024;
+
+struct Slot {
+    /// Incremented every time the slot is reused, so that a stale ID of a
+    /// closed handle doesn't refer to a new one.
+    generation: u16,
+    handle: Option<AnyHandle>,
+}
+
+/// Handles owned by a process.
+///
+/// A handle ID consists of a slot index and the slot's generation. Freed
+/// slots are reused in the order they're freed so that it takes long for
+/// a generation to wrap around. Using a stale ID results in
+/// [`ErrorCode::InvalidHandle`].
 pub struct HandleTable {
-    handles: BTreeMap<HandleId, AnyHandle>,
-    next_id: i32,
+    slots: Vec<Slot>,
+    /// Indices of unused slots.
+    free: VecDeque<usize>,
+    /// The number of handles in the table.
+    len: usize,
+    limit: usize,
 }
 
 impl HandleTable {
-    pub const fn new() -> HandleTable {
-        HandleTable {
-            handles: BTreeMap::new(),
-            next_id: 1,
+    pub fn new(limit: usize) -> Result<HandleTable, ErrorCode> {
+        if limit > HANDLE_LIMIT_MAX {
+            return Err(ErrorCode::InvalidArg);
         }
+
+        Ok(HandleTable {
+            slots: Vec::new(),
+            free: VecDeque::new(),
+            len: 0,
+            limit,
+        })
     }
 
     pub fn insert<H: Into<AnyHandle>>(&mut self, object: H) -> Result<HandleId, ErrorCode> {
-        if self.handles.len() >= NUM_HANDLES_MAX {
+        if self.len >= self.limit {
             return Err(ErrorCode::TooManyHandles);
         }
 
-        let handle_id = HandleId::from_raw(self.next_id);
-        let value = object.into();
-
-        if self.handles.try_insert(handle_id, value).is_err() {
-            return Err(ErrorCode::AlreadyExists);
-        }
+        let index = match self.free.pop_front() {
+            Some(index) => {
+                let slot = &mut self.slots[index];
+                slot.generation = slot.generation.wrapping_add(1) & GENERATION_MASK;
+                index
+            }
+            None => {
+                self.slots
+                    .try_reserve(1)
+                    .map_err(|_| ErrorCode::OutOfMemory)?;
+                // Make sure `take` can push the index later without allocating.
+                self.free
+                    .try_reserve(self.slots.len() + 1)
+                    .map_err(|_| ErrorCode::OutOfMemory)?;
+                self.slots.push(Slot {
+                    generation: 0,
+                    handle: None,
+                });
+                self.slots.len() - 1
+            }
+        };
 
-        self.next_id += 1;
-        Ok(handle_id)
+        let slot = &mut self.slots[index];
+        debug_assert!(slot.handle.is_none());
+        slot.handle = Some(object.into());
+        self.len += 1;
+        Ok(encode_id(index, slot.generation))
     }
 
-    /// Insert two handles, and return the first ID.
-    ///
-    /// The IDs are guaranteed to be consecutive.
-    pub fn insert_consecutive<H: Into<AnyHandle>>(
+    /// Inserts two handles at once: either both or none are inserted.
+    pub fn insert_pair<H: Into<AnyHandle>>(
         &mut self,
         first: H,
         second: H,
-    ) -> Result<HandleId, ErrorCode> {
-        if self.handles.len() + 2 > NUM_HANDLES_MAX {
+    ) -> Result<(HandleId, HandleId), ErrorCode> {
+        if self.len + 2 > self.limit {
             return Err(ErrorCode::TooManyHandles);
         }
 
-        let first_id = HandleId::from_raw(self.next_id);
-        let second_id = HandleId::from_raw(self.next_id + 1);
-
-        if self.handles.contains_key(&first_id) || self.handles.contains_key(&second_id) {
-            return Err(ErrorCode::AlreadyExists);
+        let first_id = self.insert(first)?;
+        match self.insert(second) {
+            Ok(second_id) => Ok((first_id, second_id)),
+            Err(err) => {
+                // Only fails on out of memory. The handle is not visible to
+                // the process yet: remove it without closing the object.
+                self.take(first_id);
+                Err(err)
+            }
         }
-
-        self.handles.insert(first_id, first.into());
-        self.handles.insert(second_id, second.into());
-
-        self.next_id += 2;
-        Ok(first_id)
     }
 
     pub fn is_movable(&self, handle: HandleId) -> bool {
-        self.handles.contains_key(&handle)
+        self.lookup(handle).is_ok()
     }
 
     pub fn get_any(&self, handle: HandleId) -> Result<AnyHandle, ErrorCode> {
-        self.handles
-            .get(&handle)
-            .cloned()
-            .ok_or(ErrorCode::NotFound)
+        self.lookup(handle).cloned()
     }
 
     pub fn get<T: Handleable>(&self, handle: HandleId) -> Result<Handle<T>, ErrorCode> {
@@ -157,13 +273,131 @@ impl HandleTable {
         Ok(handle)
     }
 
+    /// Duplicates a handle with a subset of its rights, and returns the new
+    /// handle ID.
+    pub fn duplicate(
+        &mut self,
+        handle: HandleId,
+        rights_mask: HandleRights,
+    ) -> Result<HandleId, ErrorCode> {
+        if self.len >= self.limit {
+            return Err(ErrorCode::TooManyHandles);
+        }
+
+        let index = self.slot_index(handle)?;
+        let original = self.slots[index].handle.as_mut().unwrap();
+        let dup = AnyHandle(original.0.duplicate(rights_mask)?);
+        match self.insert(dup.clone()) {
+            Ok(id) => Ok(id),
+            Err(err) => {
+                // Won't close the object: the original handle is still open.
+                dup.close();
+                Err(err)
+            }
+        }
+    }
+
     pub fn take(&mut self, handle: HandleId) -> Option<AnyHandle> {
-        self.handles.remove(&handle)
+        let index = self.slot_index(handle).ok()?;
+        let taken = self.slots[index].handle.take();
+        self.len -= 1;
+        // Reserved in `insert`: this never allocates.
+        self.free.push_back(index);
+        taken
+    }
+
+    /// Puts a handle removed by [`HandleTable::take`] back at the same ID.
+    ///
+    /// No handles must be inserted in between.
+    pub fn restore(&mut self, handle_id: HandleId, handle: AnyHandle) {
+        let (index, generation) = decode_id(handle_id).unwrap();
+        let slot = &mut self.slots[index];
+        debug_assert!(slot.handle.is_none() && slot.generation == generation);
+        slot.handle = Some(handle);
+        self.len += 1;
+
+        // The slot was pushed to the free list by `take` recently.
+        let pos = self.free.iter().rposition(|i| *i == index).unwrap();
+        self.free.remove(pos);
+    }
+
+    /// Describes handles in the table, skipping the first `skip` ones.
+    /// Stops when `f` returns `false`.
+    pub fn inspect(&self, skip: usize, mut f: impl FnMut(HandleInfo) -> bool) {
+        let handles = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let handle = slot.handle.as_ref()?;
+                Some((encode_id(index, slot.generation), handle))
+            })
+            .skip(skip);
+
+        for (id, handle) in handles {
+            let rights = handle.rights();
+            // Only pollable objects support readiness.
+            let readiness = if rights.is_capable(HandleRights::POLL) {
+                handle.readiness().ok()
+            } else {
+                None
+            };
+
+            let info = HandleInfo {
+                id,
+                rights,
+                readiness,
+                kind: handle.kind(),
+            };
+
+            if !f(info) {
+                break;
+            }
+        }
+    }
+
+    /// Removes all handles from the table, and returns them.
+    pub fn take_all(&mut self) -> impl Iterator<Item = AnyHandle> + use<> {
+        self.free.clear();
+        self.len = 0;
+        core::mem::take(&mut self.slots)
+            .into_iter()
+            .filter_map(|slot| slot.handle)
     }
 
     pub fn close(&mut self, handle: HandleId) -> Result<(), ErrorCode> {
-        let handle = self.handles.remove(&handle).ok_or(ErrorCode::NotFound)?;
+        let handle = self.take(handle).ok_or(ErrorCode::InvalidHandle)?;
         handle.close();
         Ok(())
     }
+
+    /// Returns the slot index of `handle` if it refers to an open handle.
+    fn slot_index(&self, handle: HandleId) -> Result<usize, ErrorCode> {
+        let (index, generation) = decode_id(handle).ok_or(ErrorCode::InvalidHandle)?;
+        match self.slots.get(index) {
+            Some(slot) if slot.generation == generation && slot.handle.is_some() => Ok(index),
+            _ => Err(ErrorCode::InvalidHandle),
+        }
+    }
+
+    fn lookup(&self, handle: HandleId) -> Result<&AnyHandle, ErrorCode> {
+        let index = self.slot_index(handle)?;
+        Ok(self.slots[index].handle.as_ref().unwrap())
+    }
+}
+
+fn encode_id(index: usize, generation: u16) -> HandleId {
+    // The index is 1-origin so that 0 is never a valid handle ID.
+    let raw = ((generation as i32) << INDEX_BITS) | (index as i32 + 1);
+    HandleId::from_raw(raw)
+}
+
+fn decode_id(handle: HandleId) -> Option<(usize, u16)> {
+    let raw = handle.as_raw();
+    let index = (raw & ((1 << INDEX_BITS) - 1)) as usize;
+    if raw <= 0 || index == 0 {
+        return None;
+    }
+
+    Some((index - 1, (raw >> INDEX_BITS) as u16))
 }
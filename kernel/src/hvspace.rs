@@ -1,47 +1,104 @@
 //! Virtual memory space management.
+use alloc::collections::BTreeMap;
+
 use starina::address::GPAddr;
 use starina::error::ErrorCode;
 use starina::poll::Readiness;
+use starina_types::handle::HandleKind;
 use starina_types::vmspace::PageProtect;
+use starina_utils::alignment::align_down;
 
 use crate::arch;
-use crate::folio::Folio;
+use crate::arch::PAGE_SIZE;
 use crate::handle::Handleable;
 use crate::poll::Listener;
-use crate::refcount::SharedRef;
+use crate::spinlock::SpinLock;
+use crate::vmspace::MappedObject;
+
+struct Mapping {
+    /// The mapped object. It's kept alive as long as this hvspace exists.
+    object: MappedObject,
+    prot: PageProtect,
+}
 
 pub struct HvSpace {
     arch: arch::HvSpace,
+    /// Objects mapped into the guest, keyed by the start address.
+    mappings: SpinLock<BTreeMap<GPAddr, Mapping>>,
 }
 
 impl HvSpace {
     pub fn new() -> Result<HvSpace, ErrorCode> {
         let arch = arch::HvSpace::new()?;
-        Ok(HvSpace { arch })
+        Ok(HvSpace {
+            arch,
+            mappings: SpinLock::new(BTreeMap::new()),
+        })
     }
 
     pub fn arch(&self) -> &arch::HvSpace {
         &self.arch
     }
 
+    /// Maps `object` at `gpaddr`. A VMO is mapped page by page when the guest
+    /// touches it (see [`HvSpace::handle_page_fault`]).
     pub fn map(
         &self,
         gpaddr: GPAddr,
-        folio: SharedRef<Folio>,
+        object: MappedObject,
         len: usize,
         prot: PageProtect,
     ) -> Result<(), ErrorCode> {
-        if folio.len() != len {
-            debug_warn!("len != folio.len");
+        if object.len() != len {
+            debug_warn!("len != object.len");
             return Err(ErrorCode::InvalidArg);
         }
 
-        let paddr = folio.paddr();
+        let end = gpaddr.checked_add(len).ok_or(ErrorCode::InvalidArg)?;
+        let mut mappings = self.mappings.lock();
+        if let Some((start, mapping)) = mappings.range(..end).next_back()
+            && start.as_usize() + mapping.object.len() > gpaddr.as_usize()
+        {
+            return Err(ErrorCode::AlreadyMapped);
+        }
+
+        if let MappedObject::Folio(folio) = &object {
+            self.arch.map(gpaddr, folio.paddr(), len, prot)?;
+        }
+
+        mappings.insert(gpaddr, Mapping { object, prot });
+        Ok(())
+    }
+
+    /// Handles a guest page fault at `gpaddr`. Returns an error if it's not
+    /// in a VMO, e.g. an MMIO access to be emulated.
+    pub fn handle_page_fault(&self, gpaddr: GPAddr, access: PageProtect) -> Result<(), ErrorCode> {
+        let mappings = self.mappings.lock();
+        let Some((start, mapping)) = mappings.range(..=gpaddr).next_back() else {
+            return Err(ErrorCode::NotFound);
+        };
+
+        let offset = gpaddr.as_usize() - start.as_usize();
+        if offset >= mapping.object.len() {
+            return Err(ErrorCode::NotFound);
+        }
+
+        let MappedObject::Vmo(vmo) = &mapping.object else {
+            return Err(ErrorCode::NotAllowed);
+        };
 
-        // The arch's page table will own a reference to the folio.
-        core::mem::forget(folio);
+        if mapping.prot & access != access {
+            return Err(ErrorCode::NotAllowed);
+        }
 
-        self.arch.map(gpaddr, paddr, len, prot)
+        let page_offset = align_down(offset, PAGE_SIZE);
+        let paddr = vmo.get_or_alloc_page(page_offset)?;
+        let page_gpaddr = GPAddr::new(start.as_usize() + page_offset);
+        match self.arch.map(page_gpaddr, paddr, PAGE_SIZE, mapping.prot) {
+            // Another vCPU may have handled the fault on the same page.
+            Ok(()) | Err(ErrorCode::AlreadyMapped) => Ok(()),
+            Err(err) => Err(err),
+        }
     }
 }
 
@@ -64,4 +121,8 @@ impl Handleable for HvSpace {
         debug_warn!("unsupported method at {}:{}", file!(), line!());
         Err(ErrorCode::NotSupported)
     }
+
+    fn kind(&self) -> HandleKind {
+        HandleKind::HvSpace
+    }
 }
@@ -1,6 +1,7 @@
 use starina::interrupt::IrqMatcher;
 use starina::poll::Readiness;
 use starina_types::error::ErrorCode;
+use starina_types::handle::HandleKind;
 use starina_types::interrupt::Irq;
 
 use crate::arch::INTERRUPT_CONTROLLER;
@@ -86,4 +87,8 @@ impl Handleable for Interrupt {
 
         Ok(readiness)
     }
+
+    fn kind(&self) -> HandleKind {
+        HandleKind::Interrupt
+    }
 }
@@ -23,6 +23,10 @@ impl Isolation for InKernel {
         &KERNEL_VMSPACE
     }
 
+    fn is_usermode(&self) -> bool {
+        false
+    }
+
     fn read_bytes(&self, ptr: IsolationPtr, dst: &mut [u8]) -> Result<(), ErrorCode> {
         let raw_ptr = ptr.0 as *const u8;
         let src = unsafe { slice::from_raw_parts(raw_ptr, dst.len()) };
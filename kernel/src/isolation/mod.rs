@@ -7,9 +7,11 @@ use core::slice;
 use starina_types::error::ErrorCode;
 
 mod inkernel;
+mod usermode;
 
 pub use inkernel::INKERNEL_ISOLATION;
 pub use inkernel::KERNEL_VMSPACE;
+pub use usermode::UserMode;
 
 use crate::refcount::SharedRef;
 use crate::vmspace::VmSpace;
@@ -141,6 +143,8 @@ impl Deref for IsolationSliceMut {
 /// This trait defines how to access memory in an isolation space.
 pub trait Isolation: Send + Sync {
     fn vmspace(&self) -> &SharedRef<VmSpace>;
+    /// Whether threads in this isolation run in the CPU's user mode.
+    fn is_usermode(&self) -> bool;
     fn read_bytes(&self, ptr: IsolationPtr, dst: &mut [u8]) -> Result<(), ErrorCode>;
     fn write_bytes(&self, ptr: IsolationPtr, src: &[u8]) -> Result<(), ErrorCode>;
 }
@@ -0,0 +1,103 @@
+use core::cmp::min;
+use core::slice;
+
+use starina::address::VAddr;
+use starina::error::ErrorCode;
+use starina_types::vmspace::PageProtect;
+
+use super::Isolation;
+use super::IsolationPtr;
+use crate::arch;
+use crate::arch::PAGE_SIZE;
+use crate::refcount::SharedRef;
+use crate::vmspace::VmSpace;
+
+/// User-mode isolation.
+///
+/// Each process has its own address space and runs in the CPU's user mode.
+/// Pointers from the process are virtual addresses in its address space, and
+/// are never dereferenced directly by the kernel: every access walks the page
+/// table and copies through the kernel's mapping of physical memory.
+pub struct UserMode {
+    vmspace: SharedRef<VmSpace>,
+}
+
+impl UserMode {
+    pub fn new() -> Result<UserMode, ErrorCode> {
+        let vmspace = SharedRef::new(VmSpace::new_user()?)?;
+        Ok(UserMode { vmspace })
+    }
+
+    /// Calls `f` for each page-sized chunk in `[ptr, ptr + len)`, with the
+    /// kernel pointer to the chunk.
+    fn for_each_chunk<F>(
+        &self,
+        ptr: IsolationPtr,
+        len: usize,
+        required: PageProtect,
+        mut f: F,
+    ) -> Result<(), ErrorCode>
+    where
+        F: FnMut(*mut u8, usize, usize),
+    {
+        let mut offset = 0;
+        while offset < len {
+            let uaddr = ptr.0.checked_add(offset).ok_or(ErrorCode::InvalidAddress)?;
+            let vaddr = VAddr::new(uaddr);
+            let (paddr, prot) = match self.vmspace.lookup(vaddr) {
+                Ok(found) => found,
+                Err(_) => {
+                    // The page may be demand-paged and not touched yet.
+                    self.vmspace
+                        .handle_page_fault(vaddr, required)
+                        .map_err(|_| ErrorCode::InvalidAddress)?;
+                    self.vmspace
+                        .lookup(vaddr)
+                        .map_err(|_| ErrorCode::InvalidAddress)?
+                }
+            };
+
+            // Kernel pages are mapped in the same address space, but they
+            // are not accessible from the user mode.
+            if !prot.contains(PageProtect::USER) || !prot.contains(required) {
+                return Err(ErrorCode::InvalidAddress);
+            }
+
+            let chunk_len = min(len - offset, PAGE_SIZE - uaddr % PAGE_SIZE);
+            let kaddr = arch::paddr2vaddr(paddr)?;
+            f(unsafe { kaddr.as_mut_ptr() }, offset, chunk_len);
+            offset += chunk_len;
+        }
+
+        Ok(())
+    }
+}
+
+impl Isolation for UserMode {
+    fn vmspace(&self) -> &SharedRef<VmSpace> {
+        &self.vmspace
+    }
+
+    fn is_usermode(&self) -> bool {
+        true
+    }
+
+    fn read_bytes(&self, ptr: IsolationPtr, dst: &mut [u8]) -> Result<(), ErrorCode> {
+        self.for_each_chunk(ptr, dst.len(), PageProtect::READABLE, |src, offset, len| {
+            let src = unsafe { slice::from_raw_parts(src, len) };
+            dst[offset..offset + len].copy_from_slice(src);
+        })
+    }
+
+    fn write_bytes(&self, ptr: IsolationPtr, src: &[u8]) -> Result<(), ErrorCode> {
+        self.for_each_chunk(
+            ptr,
+            src.len(),
+            PageProtect::WRITEABLE,
+            |dst, offset, len| {
+                let dst = unsafe { slice::from_raw_parts_mut(dst, len) };
+                dst.copy_from_slice(&src[offset..offset + len]);
+            },
+        )
+    }
+}
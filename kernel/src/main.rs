@@ -15,6 +15,7 @@ use core::mem::MaybeUninit;
 use allocator::GLOBAL_ALLOCATOR;
 use cpuvar::CpuId;
 use isolation::KERNEL_VMSPACE;
+use starina::address::VAddr;
 
 #[macro_use]
 mod print;
@@ -24,15 +25,18 @@ mod arch;
 mod channel;
 mod cpuvar;
 mod device_tree;
+mod elf;
 mod folio;
 mod handle;
 mod hvspace;
 mod interrupt;
 mod isolation;
+mod memory_account;
 mod panic;
 mod poll;
 mod process;
 mod refcount;
+mod rtc;
 mod scheduler;
 mod spinlock;
 mod startup;
@@ -41,6 +45,7 @@ mod thread;
 mod timer;
 mod utils;
 mod vcpu;
+mod vmo;
 mod vmspace;
 
 const EARLY_RAM_SIZE: usize = 256 * 1024;
@@ -49,6 +54,7 @@ static EARLY_RAM: [MaybeUninit<u8>; EARLY_RAM_SIZE] = [MaybeUninit::uninit(); EA
 pub struct BootInfo {
     dtb: *const u8,
     cpu_id: CpuId,
+    kernel_stack_top: VAddr,
 }
 
 pub fn boot(bootinfo: BootInfo) -> ! {
@@ -58,12 +64,24 @@ pub fn boot(bootinfo: BootInfo) -> ! {
 
     let device_tree = device_tree::parse(bootinfo.dtb).expect("failed to parse device tree");
     timer::init(device_tree.timer_freq);
-    cpuvar::percpu_init(bootinfo.cpu_id);
+    rtc::init(&device_tree);
+    cpuvar::percpu_init(bootinfo.cpu_id, bootinfo.kernel_stack_top);
     arch::percpu_init();
     startup::load_inkernel_apps(device_tree);
 
     // Switch to the kernel's address space.
     KERNEL_VMSPACE.switch();
 
+    arch::start_secondary_cpus();
+    thread::switch_thread();
+}
+
+/// The entry point for CPUs other than the boot CPU.
+pub fn secondary_boot(cpu_id: CpuId, kernel_stack_top: VAddr) -> ! {
+    cpuvar::percpu_init(cpu_id, kernel_stack_top);
+    arch::percpu_init();
+    KERNEL_VMSPACE.switch();
+
+    info!("CPU {} is up", cpu_id);
     thread::switch_thread();
 }
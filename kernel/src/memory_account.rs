@@ -0,0 +1,136 @@
+//! Per-process memory accounting.
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+
+use starina_types::error::ErrorCode;
+use starina_types::process::MemoryUsage;
+
+use crate::refcount::SharedRef;
+
+/// What the charged memory is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryKind {
+    /// Memory mapped into the process: folios and VMO pages.
+    Folio,
+    /// Kernel objects created by the process.
+    Object,
+}
+
+/// Tracks how much memory a process consumes, and enforces its limit.
+///
+/// Memory is charged to the process which allocated it, even if the object
+/// is later transferred to another process. The charge is released when the
+/// memory is freed, not when the process exits.
+///
+/// An account may have a parent: memory charged to it is also charged to
+/// the parent, so that a process can't escape its limit by creating child
+/// processes.
+///
+/// Heap allocations made by in-kernel apps themselves (e.g. `Vec` and `Box`)
+/// are not charged: they share the kernel's heap allocator.
+pub struct MemoryAccount {
+    limit: Option<usize>,
+    parent: Option<SharedRef<MemoryAccount>>,
+    total: AtomicUsize,
+    folio_bytes: AtomicUsize,
+    object_bytes: AtomicUsize,
+}
+
+impl MemoryAccount {
+    pub const fn new(limit: Option<usize>) -> MemoryAccount {
+        MemoryAccount {
+            limit,
+            parent: None,
+            total: AtomicUsize::new(0),
+            folio_bytes: AtomicUsize::new(0),
+            object_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    /// Creates an account with no limit of its own, bounded by `parent`'s.
+    pub const fn new_child(parent: SharedRef<MemoryAccount>) -> MemoryAccount {
+        MemoryAccount {
+            limit: None,
+            parent: Some(parent),
+            total: AtomicUsize::new(0),
+            folio_bytes: AtomicUsize::new(0),
+            object_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    /// Charges `bytes` to the account and its ancestors. Returns
+    /// [`ErrorCode::OutOfMemory`] if it exceeds any of their limits.
+    ///
+    /// The memory is uncharged when the returned [`MemoryCharge`] is
+    /// dropped: keep it alongside the allocated memory.
+    pub fn charge(
+        self: &SharedRef<Self>,
+        kind: MemoryKind,
+        bytes: usize,
+    ) -> Result<MemoryCharge, ErrorCode> {
+        self.add(kind, bytes)?;
+        Ok(MemoryCharge {
+            account: self.clone(),
+            kind,
+            bytes,
+        })
+    }
+
+    /// Memory usage of the process, including its child processes.
+    pub fn usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            folio_bytes: self.folio_bytes.load(Ordering::Relaxed),
+            object_bytes: self.object_bytes.load(Ordering::Relaxed),
+            limit: self.limit,
+        }
+    }
+
+    fn add(&self, kind: MemoryKind, bytes: usize) -> Result<(), ErrorCode> {
+        let limit = self.limit.unwrap_or(usize::MAX);
+        self.total
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| {
+                total
+                    .checked_add(bytes)
+                    .filter(|new_total| *new_total <= limit)
+            })
+            .map_err(|_| ErrorCode::OutOfMemory)?;
+
+        if let Some(parent) = &self.parent
+            && let Err(err) = parent.add(kind, bytes)
+        {
+            self.total.fetch_sub(bytes, Ordering::Relaxed);
+            return Err(err);
+        }
+
+        self.counter(kind).fetch_add(bytes, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn sub(&self, kind: MemoryKind, bytes: usize) {
+        self.counter(kind).fetch_sub(bytes, Ordering::Relaxed);
+        self.total.fetch_sub(bytes, Ordering::Relaxed);
+        if let Some(parent) = &self.parent {
+            parent.sub(kind, bytes);
+        }
+    }
+
+    fn counter(&self, kind: MemoryKind) -> &AtomicUsize {
+        match kind {
+            MemoryKind::Folio => &self.folio_bytes,
+            MemoryKind::Object => &self.object_bytes,
+        }
+    }
+}
+
+/// Memory charged to a [`MemoryAccount`]. Dropping it uncharges the memory.
+pub struct MemoryCharge {
+    account: SharedRef<MemoryAccount>,
+    kind: MemoryKind,
+    bytes: usize,
+}
+
+impl Drop for MemoryCharge {
+    fn drop(&mut self) {
+        self.account.sub(self.kind, self.bytes);
+    }
+}
@@ -4,18 +4,30 @@ use alloc::vec::Vec;
 use core::fmt;
 use core::hash::Hash;
 
+use arrayvec::ArrayVec;
 use hashbrown::HashSet;
 use starina_types::error::ErrorCode;
 use starina_types::handle::HandleId;
+use starina_types::handle::HandleKind;
+use starina_types::poll::PollEvent;
+use starina_types::poll::PollMode;
 use starina_types::poll::Readiness;
+use starina_types::syscall::RetVal;
+use starina_types::timer::MonotonicTime;
 
 use crate::handle::AnyHandle;
 use crate::handle::Handleable;
+use crate::isolation::IsolationPtr;
+use crate::isolation::IsolationSliceMut;
 use crate::refcount::SharedRef;
 use crate::spinlock::SpinLock;
 use crate::syscall::SyscallResult;
 use crate::thread::Thread;
 use crate::thread::ThreadState;
+use crate::timer;
+
+/// The maximum number of events returned by a `poll_wait_many` call.
+const POLL_EVENTS_MAX: usize = 64;
 
 struct UniqueQueue<T> {
     queue: VecDeque<T>,
@@ -55,6 +67,10 @@ impl<T: Eq + Ord + Copy + Hash> UniqueQueue<T> {
         debug_assert!(removed, "value was in queue but not in set");
         Some(value)
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
 }
 
 pub struct Listener {
@@ -66,6 +82,15 @@ pub struct Listener {
 impl Listener {
     pub fn notify(&self, readiness: Readiness) {
         let mut mutable = self.poll.mutable.lock();
+
+        // A one-shot listenee which has already fired is ignored until it's
+        // re-armed by `poll_update`.
+        if let Some(listenee) = mutable.listenee.get(&self.id)
+            && !listenee.armed
+        {
+            return;
+        }
+
         if mutable.ready_handles.enqueue(self.id).is_err() {
             debug_warn!("failed to notify listener due to out-of-memory");
             return;
@@ -120,6 +145,9 @@ impl ListenerSet {
 struct Listenee {
     handle: AnyHandle,
     interests: Readiness,
+    mode: PollMode,
+    /// `false` if this is a one-shot listenee and it has already fired.
+    armed: bool,
 }
 
 struct Mutable {
@@ -149,39 +177,40 @@ impl Poll {
         handle: AnyHandle,
         id: HandleId,
         interests: Readiness,
+        mode: PollMode,
     ) -> Result<(), ErrorCode> {
-        let mut mutable = self.mutable.lock();
-        if mutable.listenee.contains_key(&id) {
-            return Err(ErrorCode::AlreadyExists);
-        }
-
-        // Add the listener to the listener object.
-        handle.add_listener(Listener {
-            poll: self.clone(),
-            interests,
-            id,
-        })?;
-
         let listenee = Listenee {
             handle: handle.clone(),
             interests,
+            mode,
+            armed: true,
         };
 
-        if mutable.listenee.try_insert(id, listenee).is_err() {
+        if self
+            .mutable
+            .lock()
+            .listenee
+            .try_insert(id, listenee)
+            .is_err()
+        {
             return Err(ErrorCode::AlreadyExists);
         }
 
+        // Add the listener to the listener object. Don't hold the poll lock
+        // here: the object notifies listeners (and thus locks this poll)
+        // while holding its own lock.
+        if let Err(err) = handle.add_listener(Listener {
+            poll: self.clone(),
+            interests,
+            id,
+        }) {
+            self.mutable.lock().listenee.remove(&id);
+            return Err(err);
+        }
+
         let readiness = handle.readiness()?;
         if readiness.contains(interests) {
-            if mutable.ready_handles.enqueue(id).is_err() {
-                debug_warn!("failed to enqueue a ready handle due to out-of-memory");
-            }
-
-            // Wake up a thread waiting for the event. Only one thread is woken
-            // up at a time to prevent a thundering herd.
-            if let Some(waiter) = mutable.waiters.pop_front() {
-                waiter.wake();
-            }
+            self.mark_ready(id);
         }
 
         Ok(())
@@ -193,103 +222,313 @@ impl Poll {
         or_mask: Readiness,
         and_mask: Readiness,
     ) -> Result<(), ErrorCode> {
-        let mut mutable = self.mutable.lock();
-        let listenee = mutable.listenee.get_mut(&id).ok_or(ErrorCode::NotFound)?;
-
-        let new_interests = (listenee.interests | or_mask) & and_mask;
-        listenee.interests = new_interests;
+        let (handle, new_interests) = {
+            let mut mutable = self.mutable.lock();
+            let listenee = mutable.listenee.get_mut(&id).ok_or(ErrorCode::NotFound)?;
+            let new_interests = (listenee.interests | or_mask) & and_mask;
+            listenee.interests = new_interests;
+            listenee.armed = true;
+            (listenee.handle.clone(), new_interests)
+        };
 
-        listenee.handle.remove_listener(self)?;
-        listenee.handle.add_listener(Listener {
+        handle.remove_listener(self)?;
+        handle.add_listener(Listener {
             poll: self.clone(),
             interests: new_interests,
             id,
         })?;
 
         // Check if the updated interests match current readiness
-        let readiness = listenee.handle.readiness()?;
+        let readiness = handle.readiness()?;
         if readiness.contains(new_interests) {
-            if mutable.ready_handles.enqueue(id).is_err() {
-                debug_warn!("failed to enqueue a ready handle due to out-of-memory");
-            }
-
-            // Wake up a thread waiting for the event
-            if let Some(waiter) = mutable.waiters.pop_front() {
-                waiter.wake();
-            }
+            self.mark_ready(id);
         }
 
         Ok(())
     }
 
     pub fn remove(self: &SharedRef<Poll>, id: HandleId) -> Result<(), ErrorCode> {
-        let mut mutable = self.mutable.lock();
-        let listenee = mutable.listenee.remove(&id).ok_or(ErrorCode::NotFound)?;
+        let listenee = {
+            let mut mutable = self.mutable.lock();
+            let listenee = mutable.listenee.remove(&id).ok_or(ErrorCode::NotFound)?;
+
+            // Remove from queue by creating a new queue without the removed handle
+            let mut new_queue = UniqueQueue::new();
+            while let Some(handle_id) = mutable.ready_handles.pop() {
+                if handle_id != id {
+                    let _ = new_queue.enqueue(handle_id);
+                }
+            }
+            mutable.ready_handles = new_queue;
+            listenee
+        };
+
         listenee.handle.remove_listener(self)?;
+        Ok(())
+    }
 
-        // Remove from queue by creating a new queue without the removed handle
-        let mut new_queue = UniqueQueue::new();
-        while let Some(handle_id) = mutable.ready_handles.pop() {
-            if handle_id != id {
-                let _ = new_queue.enqueue(handle_id);
-            }
+    /// Enqueues a ready handle, and wakes up a thread waiting for it.
+    fn mark_ready(&self, id: HandleId) {
+        let mut mutable = self.mutable.lock();
+        if mutable.ready_handles.enqueue(id).is_err() {
+            debug_warn!("failed to enqueue a ready handle due to out-of-memory");
         }
-        mutable.ready_handles = new_queue;
 
-        Ok(())
+        // Wake up a thread waiting for the event. Only one thread is woken
+        // up at a time to prevent a thundering herd.
+        if let Some(waiter) = mutable.waiters.pop_front() {
+            waiter.wake();
+        }
     }
 
-    pub fn try_wait(
+    fn try_wait(
         self: &SharedRef<Poll>,
         current: &SharedRef<Thread>,
         non_blocking: bool,
+        wait: &PollWait,
     ) -> SyscallResult {
-        let mut mutable = self.mutable.lock();
+        let max_events = match wait.events {
+            Some((_, max_events)) => max_events,
+            None => 1,
+        };
 
-        // Check if there are any ready events.
-        while let Some(id) = mutable.ready_handles.pop() {
-            let Some(listenee) = mutable.listenee.get_mut(&id) else {
-                // The handle was removed from the poll. Try the next one.
-                continue;
-            };
+        loop {
+            // Pop candidates under the lock, and check their readiness without
+            // it: objects lock themselves first, and then this poll.
+            let mut candidates: ArrayVec<(HandleId, AnyHandle), POLL_EVENTS_MAX> = ArrayVec::new();
+            {
+                let mut mutable = self.mutable.lock();
+                while candidates.len() < max_events.min(POLL_EVENTS_MAX) {
+                    let Some(id) = mutable.ready_handles.pop() else {
+                        break;
+                    };
+
+                    match mutable.listenee.get(&id) {
+                        // A one-shot listenee enqueued before it fired is
+                        // skipped.
+                        Some(listenee) if listenee.armed => {
+                            candidates.push((id, listenee.handle.clone()));
+                        }
+                        // The handle was removed from the poll. Try the next one.
+                        _ => continue,
+                    }
+                }
+            }
+
+            let mut readinesses: ArrayVec<Readiness, POLL_EVENTS_MAX> = ArrayVec::new();
+            for (_, handle) in &candidates {
+                match handle.readiness() {
+                    Ok(readiness) => readinesses.push(readiness),
+                    Err(e) => {
+                        debug_warn!("failed to get readiness for handle: {:?}", e);
+                        // Don't lose handles popped so far.
+                        let mut mutable = self.mutable.lock();
+                        for (id, _) in candidates {
+                            mutable.ready_handles.enqueue(id).unwrap();
+                        }
+
+                        return SyscallResult::Err(e);
+                    }
+                }
+            }
+
+            let mut mutable = self.mutable.lock();
+            let mut num_events = 0;
+            let mut ready_ids: ArrayVec<HandleId, POLL_EVENTS_MAX> = ArrayVec::new();
+            for ((id, _), readiness) in candidates.iter().zip(readinesses) {
+                let id = *id;
+                let Some(listenee) = mutable.listenee.get_mut(&id) else {
+                    // Removed while we didn't hold the lock.
+                    continue;
+                };
+
+                if !listenee.armed {
+                    continue;
+                }
 
-            let readiness = match listenee.handle.readiness() {
-                Ok(readiness) => readiness,
-                Err(e) => {
-                    debug_warn!("failed to get readiness for handle: {:?}", e);
-                    return SyscallResult::Err(e);
+                let interested = listenee.interests & readiness;
+                if interested.is_empty() {
+                    continue;
                 }
-            };
 
-            let interested = listenee.interests & readiness;
-            if !interested.is_empty() {
-                // Always re-enqueue to check if handle is still ready for future polls.
-                // The handle's readiness() method will determine current state.
+                // In the level-triggered mode, re-enqueue the handle to check if
+                // it's still ready in future polls. In other modes, the handle is
+                // enqueued again only when the object notifies a new event.
+                let requeue = match listenee.mode {
+                    PollMode::Level => true,
+                    PollMode::Edge => false,
+                    PollMode::OneShot => {
+                        listenee.armed = false;
+                        false
+                    }
+                };
+
+                match wait.events {
+                    Some((events_ptr, _)) => {
+                        // Re-enqueue after collecting events not to return the
+                        // same handle twice.
+                        if requeue {
+                            ready_ids.push(id);
+                        }
+
+                        let events = IsolationSliceMut::new(
+                            events_ptr,
+                            max_events.saturating_mul(size_of::<PollEvent>()),
+                        );
+                        let offset = num_events * size_of::<PollEvent>();
+                        let event = PollEvent::new(id, interested);
+                        if let Err(err) = events.write(current.process().isolation(), offset, event)
+                        {
+                            // Don't lose handles popped so far.
+                            for (id, _) in &candidates {
+                                mutable.ready_handles.enqueue(*id).unwrap();
+                            }
+
+                            return SyscallResult::Err(err);
+                        }
+                    }
+                    None => {
+                        if requeue {
+                            mutable.ready_handles.enqueue(id).unwrap();
+                        }
+
+                        drop(mutable);
+                        wait.cancel_timeout(current);
+                        return SyscallResult::Done((id, interested).into());
+                    }
+                }
+
+                num_events += 1;
+            }
+
+            for id in ready_ids {
                 mutable.ready_handles.enqueue(id).unwrap();
+            }
+
+            if num_events > 0 {
+                drop(mutable);
+                wait.cancel_timeout(current);
+                return SyscallResult::Done(RetVal::new(num_events as isize));
+            }
+
+            if !mutable.ready_handles.is_empty() {
+                // Some handles have become ready while we didn't hold the lock,
+                // or there are more candidates to check.
+                continue;
+            }
+
+            // No events are ready.
+            if non_blocking {
+                // Return WouldBlock error instead of blocking.
+                return SyscallResult::Err(ErrorCode::WouldBlock);
+            }
+
+            if let Some(deadline) = wait.deadline
+                && timer::now() >= deadline
+            {
+                // We might be in the waiter queue if we've been blocked before.
+                mutable
+                    .waiters
+                    .retain(|waiter| !SharedRef::ptr_eq(waiter, current));
+                drop(mutable);
+                wait.cancel_timeout(current);
+                return SyscallResult::Err(ErrorCode::TimedOut);
+            }
+
+            // Block the current thread.
+            if mutable.waiters.try_reserve(1).is_err() {
+                return SyscallResult::Err(ErrorCode::OutOfMemory);
+            }
 
-                return SyscallResult::Done((id, interested).into());
+            mutable.waiters.push_back(current.clone());
+            drop(mutable);
+
+            // Don't hold the poll lock here: the timer interrupt handler locks
+            // the timers first, and then polls through listeners.
+            if let Some(deadline) = wait.deadline
+                && let Err(err) = timer::wake_at(current, deadline)
+            {
+                self.mutable
+                    .lock()
+                    .waiters
+                    .retain(|waiter| !SharedRef::ptr_eq(waiter, current));
+                return SyscallResult::Err(err);
             }
+
+            return SyscallResult::Block(ThreadState::BlockedByPoll(wait.clone()));
         }
+    }
+}
+
+/// A `poll_wait` or `poll_wait_many` call, which might block the thread.
+#[derive(Clone)]
+pub struct PollWait {
+    poll: SharedRef<Poll>,
+    deadline: Option<MonotonicTime>,
+    /// The buffer to fill ready events into and its capacity, for
+    /// `poll_wait_many`. If it's `None`, a single event is returned as the
+    /// return value.
+    events: Option<(IsolationPtr, usize)>,
+}
 
-        // No events are ready.
-        if non_blocking {
-            // Return WouldBlock error instead of blocking.
-            return SyscallResult::Err(ErrorCode::WouldBlock);
+impl PollWait {
+    pub fn new(
+        poll: SharedRef<Poll>,
+        deadline: Option<MonotonicTime>,
+        events: Option<(IsolationPtr, usize)>,
+    ) -> PollWait {
+        PollWait {
+            poll,
+            deadline,
+            events,
         }
+    }
+
+    /// Returns ready events if any, or blocks the current thread until an
+    /// event arrives or the deadline passes.
+    pub fn try_wait(&self, current: &SharedRef<Thread>, non_blocking: bool) -> SyscallResult {
+        self.poll.try_wait(current, non_blocking, self)
+    }
 
-        // Block the current thread.
-        if mutable.waiters.try_reserve(1).is_err() {
-            return SyscallResult::Err(ErrorCode::OutOfMemory);
+    fn cancel_timeout(&self, current: &SharedRef<Thread>) {
+        if self.deadline.is_some() {
+            timer::cancel_wake(current);
         }
+    }
+}
 
-        mutable.waiters.push_back(current.clone());
-        SyscallResult::Block(ThreadState::BlockedByPoll(self.clone()))
+impl fmt::Debug for PollWait {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PollWait")
+            .field("deadline", &self.deadline)
+            .finish()
     }
 }
 
 impl Handleable for Poll {
     fn close(&self) {
-        // Nothing to do.
+        // Listeners in the objects hold references to this poll. Remove them
+        // to break the reference cycles, and wake up the threads blocked on
+        // this poll.
+        let (listenees, waiters) = {
+            let mut mutable = self.mutable.lock();
+            mutable.ready_handles = UniqueQueue::new();
+            (
+                core::mem::take(&mut mutable.listenee),
+                core::mem::take(&mut mutable.waiters),
+            )
+        };
+
+        for listenee in listenees.values() {
+            if let Err(err) = listenee.handle.remove_listener(self) {
+                debug_warn!("failed to remove listener from handle: {:?}", err);
+            }
+        }
+
+        for waiter in waiters {
+            timer::cancel_wake(&waiter);
+            waiter.set_state(ThreadState::Runnable(Some(ErrorCode::Closed.into())));
+        }
     }
 
     fn add_listener(&self, _listener: Listener) -> Result<(), ErrorCode> {
@@ -306,6 +545,10 @@ impl Handleable for Poll {
         debug_warn!("unsupported method at {}:{}", file!(), line!());
         Err(ErrorCode::NotSupported)
     }
+
+    fn kind(&self) -> HandleKind {
+        HandleKind::Poll
+    }
 }
 
 impl fmt::Debug for Poll {
@@ -1,5 +1,6 @@
+use starina_types::log::LOG_RECORD_LEN_MAX;
+
 use crate::spinlock::SpinLock;
-use crate::utils::ring_buffer::RingBuffer;
 
 /// The console output writer.
 ///
@@ -126,79 +127,159 @@ macro_rules! debug_warn {
     };
 }
 
-const LOG_BUFFER_SIZE: usize = 16 * 1024;
+/// The number of records kept in [`LOG_BUFFER`].
+const LOG_RECORDS_MAX: usize = 128;
 
-pub static LOG_BUFFER: SpinLock<RingBuffer<u8, LOG_BUFFER_SIZE>> = SpinLock::new(RingBuffer::new());
+#[derive(Clone, Copy)]
+struct LogRecord {
+    len: usize,
+    data: [u8; LOG_RECORD_LEN_MAX],
+}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl LogRecord {
+    const fn new() -> Self {
+        Self {
+            len: 0,
+            data: [0; LOG_RECORD_LEN_MAX],
+        }
+    }
+}
 
-    #[test]
-    fn test_log_buffer_write_and_read() {
-        let mut buffer: RingBuffer<u8, 1024> = RingBuffer::new();
-        let data = b"hello world";
+/// The kernel log: a ring of line-sized records with sequence numbers.
+///
+/// Reading does not consume records, so multiple readers can follow the log
+/// independently, each with its own cursor (the sequence number of the next
+/// record to read). Once the ring is full, the oldest record is evicted.
+pub struct LogBuffer {
+    records: [LogRecord; LOG_RECORDS_MAX],
+    /// The record being written. It becomes visible to readers on a newline
+    /// or when it's full.
+    pending: LogRecord,
+    /// The sequence number of the pending record.
+    next_seq: u64,
+}
 
-        buffer.write(data);
+impl LogBuffer {
+    pub const fn new() -> Self {
+        Self {
+            records: [LogRecord::new(); LOG_RECORDS_MAX],
+            pending: LogRecord::new(),
+            next_seq: 0,
+        }
+    }
 
-        let mut read_buf = [0u8; 20];
-        let read_len = buffer.read(0, &mut read_buf);
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.pending.data[self.pending.len] = byte;
+            self.pending.len += 1;
+            if byte == b'\n' || self.pending.len == LOG_RECORD_LEN_MAX {
+                self.commit();
+            }
+        }
+    }
 
-        assert_eq!(read_len, data.len());
-        assert_eq!(&read_buf[..read_len], data);
+    fn commit(&mut self) {
+        let index = (self.next_seq % LOG_RECORDS_MAX as u64) as usize;
+        self.records[index] = self.pending;
+        self.pending.len = 0;
+        self.next_seq += 1;
     }
 
-    #[test]
-    fn test_log_buffer_wrap_around() {
-        let mut buffer: RingBuffer<u8, 100> = RingBuffer::new();
-        let large_data = vec![b'x'; 150];
+    /// The sequence number of the next record to be written.
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
 
-        buffer.write(&large_data);
+    fn oldest_seq(&self) -> u64 {
+        self.next_seq.saturating_sub(LOG_RECORDS_MAX as u64)
+    }
 
-        let mut read_buf = [0u8; 100];
-        let read_len = buffer.read(0, &mut read_buf);
+    /// Copies the record at `seq` into `buf`. If it has already been evicted,
+    /// copies the oldest record instead.
+    ///
+    /// Returns the sequence number of the copied record and its length, or
+    /// `None` if there are no records at or after `seq`.
+    pub fn read(&self, seq: u64, buf: &mut [u8; LOG_RECORD_LEN_MAX]) -> Option<(u64, usize)> {
+        let seq = seq.max(self.oldest_seq());
+        if seq >= self.next_seq {
+            return None;
+        }
 
-        assert_eq!(read_len, 0);
+        let record = &self.records[(seq % LOG_RECORDS_MAX as u64) as usize];
+        buf[..record.len].copy_from_slice(&record.data[..record.len]);
+        Some((seq, record.len))
     }
+}
 
-    #[test]
-    fn test_log_buffer_partial_read() {
-        let mut buffer: RingBuffer<u8, 1024> = RingBuffer::new();
-        let data = b"0123456789";
-
-        buffer.write(data);
+pub static LOG_BUFFER: SpinLock<LogBuffer> = SpinLock::new(LogBuffer::new());
 
-        let mut small_buf = [0u8; 5];
-        let read_len = buffer.read(0, &mut small_buf);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        assert_eq!(read_len, 5);
-        assert_eq!(&small_buf, b"01234");
+    fn read_all(log: &LogBuffer, mut cursor: u64) -> Vec<(u64, Vec<u8>)> {
+        let mut records = Vec::new();
+        let mut buf = [0; LOG_RECORD_LEN_MAX];
+        while let Some((seq, len)) = log.read(cursor, &mut buf) {
+            records.push((seq, buf[..len].to_vec()));
+            cursor = seq + 1;
+        }
+        records
     }
 
     #[test]
-    fn test_log_buffer_offset_read() {
-        let mut buffer: RingBuffer<u8, 1024> = RingBuffer::new();
-        let data = b"hello world";
+    fn test_log_buffer_write_and_read() {
+        let mut log = LogBuffer::new();
+        log.write(b"hello\nworld\n");
 
-        buffer.write(data);
+        let records = read_all(&log, 0);
+        assert_eq!(
+            records,
+            vec![(0, b"hello\n".to_vec()), (1, b"world\n".to_vec())]
+        );
+    }
 
-        let mut read_buf = [0u8; 5];
-        let read_len = buffer.read(6, &mut read_buf);
+    #[test]
+    fn test_log_buffer_multiple_readers() {
+        let mut log = LogBuffer::new();
+        log.write(b"a\nb\n");
 
-        assert_eq!(read_len, 5);
-        assert_eq!(&read_buf, b"world");
+        assert_eq!(read_all(&log, 0).len(), 2);
+        assert_eq!(read_all(&log, 0).len(), 2);
+        assert_eq!(read_all(&log, 1), vec![(1, b"b\n".to_vec())]);
     }
 
     #[test]
-    fn test_log_buffer_out_of_bounds_offset() {
-        let mut buffer: RingBuffer<u8, 1024> = RingBuffer::new();
-        let data = b"hello";
+    fn test_log_buffer_pending_record() {
+        let mut log = LogBuffer::new();
+        log.write(b"hello ");
+        assert_eq!(read_all(&log, 0), vec![]);
 
-        buffer.write(data);
+        log.write(b"world\n");
+        assert_eq!(read_all(&log, 0), vec![(0, b"hello world\n".to_vec())]);
+    }
 
-        let mut read_buf = [0u8; 10];
-        let read_len = buffer.read(10, &mut read_buf);
+    #[test]
+    fn test_log_buffer_long_line() {
+        let mut log = LogBuffer::new();
+        log.write(&[b'x'; LOG_RECORD_LEN_MAX + 1]);
+        log.write(b"\n");
+
+        let records = read_all(&log, 0);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].1.len(), LOG_RECORD_LEN_MAX);
+        assert_eq!(records[1].1, b"x\n");
+    }
+
+    #[test]
+    fn test_log_buffer_eviction() {
+        let mut log = LogBuffer::new();
+        for _ in 0..LOG_RECORDS_MAX + 3 {
+            log.write(b"x\n");
+        }
 
-        assert_eq!(read_len, 0);
+        let records = read_all(&log, 0);
+        assert_eq!(records.len(), LOG_RECORDS_MAX);
+        assert_eq!(records[0].0, 3);
     }
 }
@@ -1,23 +1,103 @@
 //! Process management.
 use core::fmt;
 
+use arrayvec::ArrayString;
+use starina_types::error::ErrorCode;
+use starina_types::handle::HandleKind;
+use starina_types::poll::Readiness;
+
+use crate::elf;
+use crate::folio::Folio;
+use crate::handle::HANDLE_LIMIT_DEFAULT;
+use crate::handle::HANDLE_LIMIT_MAX;
 use crate::handle::HandleTable;
+use crate::handle::Handleable;
 use crate::isolation::INKERNEL_ISOLATION;
 use crate::isolation::Isolation;
+use crate::isolation::UserMode;
+use crate::memory_account::MemoryAccount;
+use crate::poll::Listener;
+use crate::poll::ListenerSet;
+use crate::poll::Poll;
 use crate::refcount::SharedRef;
 use crate::spinlock::SpinLock;
 
+pub const PROCESS_NAME_LEN_MAX: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Running,
+    Exited(i32),
+}
+
+struct Mutable {
+    state: State,
+    /// The number of threads which have not exited yet.
+    num_threads: usize,
+    listeners: ListenerSet,
+}
+
 pub struct Process {
+    name: ArrayString<PROCESS_NAME_LEN_MAX>,
+    /// Memory consumed by the process.
+    memory_account: SharedRef<MemoryAccount>,
     isolation: SharedRef<dyn Isolation>,
     handles: SpinLock<HandleTable>,
+    /// The entry point of the executable. `None` if the process is not
+    /// loaded from an executable (e.g. in-kernel apps).
+    entry: Option<usize>,
+    mutable: SpinLock<Mutable>,
 }
 
 impl Process {
-    pub const fn create(isolation: SharedRef<dyn Isolation>) -> Process {
-        Process {
+    pub fn create(
+        name: &str,
+        isolation: SharedRef<dyn Isolation>,
+        memory_account: SharedRef<MemoryAccount>,
+        handle_limit: Option<usize>,
+    ) -> Result<Process, ErrorCode> {
+        let name = ArrayString::from(name).map_err(|_| ErrorCode::TooLarge)?;
+        let handles = HandleTable::new(handle_limit.unwrap_or(HANDLE_LIMIT_DEFAULT))?;
+        Ok(Process {
+            name,
+            memory_account,
             isolation,
-            handles: SpinLock::new(HandleTable::new()),
-        }
+            handles: SpinLock::new(handles),
+            entry: None,
+            mutable: SpinLock::new(Mutable {
+                state: State::Running,
+                num_threads: 0,
+                listeners: ListenerSet::new(),
+            }),
+        })
+    }
+
+    /// Creates a user-mode process from an ELF executable in `elf`.
+    ///
+    /// The process has the default handle limit. Its memory, including the
+    /// executable's, is charged to the process itself and to
+    /// `parent_account`, so it's bounded by the creator's memory limit.
+    pub fn create_user(
+        name: &str,
+        elf: &Folio,
+        parent_account: &SharedRef<MemoryAccount>,
+    ) -> Result<SharedRef<Process>, ErrorCode> {
+        let memory_account = SharedRef::new(MemoryAccount::new_child(parent_account.clone()))?;
+        let isolation = UserMode::new()?;
+        let entry = elf::load(isolation.vmspace(), &memory_account, elf)?;
+
+        let isolation = SharedRef::new(isolation)? as SharedRef<dyn Isolation>;
+        let mut process = Process::create(name, isolation, memory_account, None)?;
+        process.entry = Some(entry);
+        SharedRef::new(process)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn memory_account(&self) -> &SharedRef<MemoryAccount> {
+        &self.memory_account
     }
 
     pub fn handles(&self) -> &SpinLock<HandleTable> {
@@ -27,15 +107,117 @@ impl Process {
     pub fn isolation(&self) -> &dyn Isolation {
         &*self.isolation
     }
+
+    pub fn entry(&self) -> Option<usize> {
+        self.entry
+    }
+
+    pub fn is_exited(&self) -> bool {
+        matches!(self.mutable.lock().state, State::Exited(_))
+    }
+
+    /// Returns the exit code if the process has exited.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self.mutable.lock().state {
+            State::Running => None,
+            State::Exited(code) => Some(code),
+        }
+    }
+
+    /// Called when a new thread is created in this process.
+    pub fn add_thread(&self) {
+        self.mutable.lock().num_threads += 1;
+    }
+
+    /// Called when a thread in this process has exited. The process exits
+    /// when the last thread exits.
+    pub fn remove_thread(&self) {
+        let is_last = {
+            let mut mutable = self.mutable.lock();
+            debug_assert!(mutable.num_threads > 0);
+            mutable.num_threads -= 1;
+            mutable.num_threads == 0
+        };
+
+        if is_last {
+            self.exit(0);
+        }
+    }
+
+    /// Terminates the process.
+    ///
+    /// All handles in the process are closed so that its peers notice that
+    /// the process has gone. Remaining threads are not stopped here: they
+    /// are reaped when they are scheduled next time.
+    pub fn exit(&self, code: i32) {
+        {
+            let mut mutable = self.mutable.lock();
+            if matches!(mutable.state, State::Exited(_)) {
+                return;
+            }
+
+            mutable.state = State::Exited(code);
+        }
+
+        trace!("{}: exited with code {}", self.name, code);
+
+        // Close handles without holding the handle table lock: closing a
+        // handle may wake up threads and notify other processes.
+        let handles = self.handles.lock().take_all();
+        for handle in handles {
+            handle.close();
+        }
+
+        self.mutable
+            .lock()
+            .listeners
+            .notify_all(Readiness::READABLE);
+    }
 }
 
 impl fmt::Debug for Process {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Process")
+        write!(f, "Process({})", self.name)
+    }
+}
+
+impl Handleable for Process {
+    fn close(&self) {
+        // Closing a process handle does not terminate the process.
+    }
+
+    fn add_listener(&self, listener: Listener) -> Result<(), ErrorCode> {
+        self.mutable.lock().listeners.add_listener(listener)?;
+        Ok(())
+    }
+
+    fn remove_listener(&self, poll: &Poll) -> Result<(), ErrorCode> {
+        self.mutable.lock().listeners.remove_listener(poll);
+        Ok(())
+    }
+
+    fn readiness(&self) -> Result<Readiness, ErrorCode> {
+        let readiness = match self.mutable.lock().state {
+            State::Running => Readiness::new(),
+            State::Exited(_) => Readiness::READABLE,
+        };
+
+        Ok(readiness)
+    }
+
+    fn kind(&self) -> HandleKind {
+        HandleKind::Process
     }
 }
 
 pub static KERNEL_PROCESS: spin::Lazy<SharedRef<Process>> = spin::Lazy::new(|| {
-    let process = Process::create(INKERNEL_ISOLATION.clone());
+    let memory_account = SharedRef::new(MemoryAccount::new(None)).unwrap();
+    let process = Process::create(
+        "kernel",
+        INKERNEL_ISOLATION.clone(),
+        memory_account,
+        Some(HANDLE_LIMIT_MAX),
+    )
+    .unwrap();
     SharedRef::new(process).unwrap()
 });
@@ -15,9 +15,14 @@ use core::sync::atomic::Ordering;
 use starina_types::error::ErrorCode;
 
 use crate::handle::Handleable;
+use crate::memory_account::MemoryAccount;
+use crate::memory_account::MemoryCharge;
+use crate::memory_account::MemoryKind;
 
 pub struct RefCounted<T: ?Sized> {
     counter: AtomicUsize,
+    /// The memory charged for this object. Released when it's freed.
+    charge: spin::Once<MemoryCharge>,
     value: T,
 }
 
@@ -25,6 +30,7 @@ impl<T> RefCounted<T> {
     pub const fn new(value: T) -> Self {
         Self {
             counter: AtomicUsize::new(1),
+            charge: spin::Once::new(),
             value,
         }
     }
@@ -79,6 +85,12 @@ impl<T> SharedRef<T> {
         a.ptr == b.ptr
     }
 
+    /// Returns the address of the object. It's unique as long as the object
+    /// is alive.
+    pub fn addr(a: &SharedRef<T>) -> usize {
+        a.ptr.as_ptr() as usize
+    }
+
     pub fn ptr_eq_self(a: &SharedRef<T>, this: &T) -> bool {
         let this_ptr: *const T = this;
         let inner_ptr: *const T = &a.inner().value;
@@ -87,6 +99,19 @@ impl<T> SharedRef<T> {
 }
 
 impl<T: ?Sized> SharedRef<T> {
+    /// Charges the memory of the object to `account` until it's freed. It
+    /// does nothing if the object is already charged.
+    pub fn charge_to(&self, account: &SharedRef<MemoryAccount>) -> Result<(), ErrorCode> {
+        let inner = self.inner();
+        if inner.charge.is_completed() {
+            return Ok(());
+        }
+
+        let charge = account.charge(MemoryKind::Object, mem::size_of_val(inner))?;
+        inner.charge.call_once(|| charge);
+        Ok(())
+    }
+
     /// Returns a reference to the inner object.
     fn inner(&self) -> &RefCounted<T> {
         // SAFETY: The object will be kept alive as long as `self` is alive.
@@ -0,0 +1,51 @@
+//! Goldfish RTC, a real-time clock device available on QEMU's `virt` machine.
+//!
+//! <https://android.googlesource.com/platform/external/qemu/+/master/docs/GOLDFISH-VIRTUAL-HARDWARE.TXT>
+use starina::address::PAddr;
+use starina::device_tree::DeviceTree;
+
+use crate::folio::Folio;
+use crate::timer;
+use crate::utils::mmio::LittleEndian;
+use crate::utils::mmio::MmioFolio;
+use crate::utils::mmio::MmioReg;
+use crate::utils::mmio::ReadOnly;
+
+/// The lower 32 bits of the nanoseconds since the UNIX epoch. Reading this
+/// register latches the upper 32 bits into `TIME_HIGH`.
+const TIME_LOW: MmioReg<LittleEndian, ReadOnly, u32> = MmioReg::new(0x00);
+const TIME_HIGH: MmioReg<LittleEndian, ReadOnly, u32> = MmioReg::new(0x04);
+
+/// Reads the wall-clock time from the RTC, and seeds the kernel's realtime
+/// clock with it.
+///
+/// This must be called before switching to the kernel's address space: the
+/// RTC is not mapped in it, and we don't need it once the time is read.
+pub fn init(device_tree: &DeviceTree) {
+    let Some(node) = device_tree
+        .devices
+        .values()
+        .find(|node| node.compatible.iter().any(|c| c == "google,goldfish-rtc"))
+    else {
+        warn!("RTC not found, the wall-clock time is not available");
+        return;
+    };
+
+    let Some(reg) = node.reg.first() else {
+        warn!("RTC has no reg property");
+        return;
+    };
+
+    let folio = match Folio::pin(PAddr::new(reg.addr as usize), reg.size as usize) {
+        Ok(folio) => folio,
+        Err(err) => {
+            warn!("failed to pin the RTC registers: {:?}", err);
+            return;
+        }
+    };
+
+    let mut mmio = MmioFolio::from_folio(folio).unwrap();
+    let low = TIME_LOW.read(&mut mmio) as u64;
+    let high = TIME_HIGH.read(&mut mmio) as u64;
+    timer::set_realtime((high << 32) | low);
+}
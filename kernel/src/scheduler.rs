@@ -1,42 +1,157 @@
 use alloc::collections::VecDeque;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
 
 use starina_types::error::ErrorCode;
+use starina_types::thread::Priority;
 
+use crate::arch;
+use crate::cpuvar::CpuId;
+use crate::cpuvar::NUM_CPUS_MAX;
 use crate::refcount::SharedRef;
 use crate::spinlock::SpinLock;
 use crate::thread::Thread;
 
 pub static GLOBAL_SCHEDULER: Scheduler = Scheduler::new();
 
+/// Per-priority FIFO run queues.
+struct RunQueue {
+    levels: [VecDeque<SharedRef<Thread>>; Priority::NUM_LEVELS],
+}
+
+impl RunQueue {
+    const fn new() -> RunQueue {
+        RunQueue {
+            levels: [const { VecDeque::new() }; Priority::NUM_LEVELS],
+        }
+    }
+
+    fn pop_highest(&mut self) -> Option<SharedRef<Thread>> {
+        self.levels
+            .iter_mut()
+            .rev()
+            .find_map(|queue| queue.pop_front())
+    }
+}
+
+/// A priority-based scheduler.
+///
+/// Each CPU has its own run queue, where each priority level has its own
+/// FIFO queue. A thread in a higher level always runs before threads in
+/// lower levels on the same CPU. A CPU with nothing to run steals a thread
+/// from other CPUs.
 pub struct Scheduler {
-    runqueue: SpinLock<VecDeque<SharedRef<Thread>>>,
+    runqueues: [SpinLock<RunQueue>; NUM_CPUS_MAX],
+    /// The bitmap of idle CPUs waiting for an IPI.
+    idle_cpus: AtomicUsize,
 }
 
 impl Scheduler {
     pub const fn new() -> Scheduler {
         Scheduler {
-            runqueue: SpinLock::new(VecDeque::new()),
+            runqueues: [const { SpinLock::new(RunQueue::new()) }; NUM_CPUS_MAX],
+            idle_cpus: AtomicUsize::new(0),
         }
     }
 
     pub fn push(&self, new_thread: SharedRef<Thread>) {
+        // Don't queue the thread twice, or two CPUs might pick it up.
+        if !new_thread.mark_as_queued() {
+            return;
+        }
+
+        let cpu_id = arch::get_cpuvar().cpu_id;
+        let level = new_thread.priority().as_usize();
+
         // SAFETY: This should not panic because we've already reserved the
         //         capacity in `try_reserve`.
-        self.runqueue.lock().push_back(new_thread);
+        self.runqueues[cpu_id.as_usize()].lock().levels[level].push_back(new_thread);
+
+        self.wake_idle_cpu(cpu_id);
     }
 
     pub fn try_reserve_cap(&self, new_cap: usize) -> Result<(), ErrorCode> {
-        let mut runqueue = self.runqueue.lock();
-        if let Some(additional) = new_cap.checked_sub(runqueue.capacity()) {
-            runqueue
-                .try_reserve(additional)
-                .map_err(|_| ErrorCode::OutOfMemory)?;
+        // A thread may be queued in any level of any CPU. Reserve the
+        // capacity for all of them.
+        for runqueue in &self.runqueues {
+            let mut runqueue = runqueue.lock();
+            for queue in runqueue.levels.iter_mut() {
+                if let Some(additional) = new_cap.checked_sub(queue.capacity()) {
+                    queue
+                        .try_reserve(additional)
+                        .map_err(|_| ErrorCode::OutOfMemory)?;
+                }
+            }
         }
 
         Ok(())
     }
 
+    /// Returns `true` if there's a thread queued in the current CPU with a
+    /// higher priority than `priority`.
+    pub fn has_higher_priority(&self, priority: Priority) -> bool {
+        let cpu_id = arch::get_cpuvar().cpu_id;
+        let runqueue = self.runqueues[cpu_id.as_usize()].lock();
+        runqueue.levels[priority.as_usize() + 1..]
+            .iter()
+            .any(|queue| !queue.is_empty())
+    }
+
+    /// Picks the next thread to run on the current CPU.
+    ///
+    /// If it returns `None`, the CPU is marked as idle and will be woken up
+    /// by an IPI when a thread is queued.
     pub fn schedule(&self) -> Option<SharedRef<Thread>> {
-        self.runqueue.lock().pop_front()
+        let cpu_id = arch::get_cpuvar().cpu_id;
+        let cpu_bit = 1 << cpu_id.as_usize();
+
+        if let Some(thread) = self.pick(cpu_id) {
+            self.idle_cpus.fetch_and(!cpu_bit, Ordering::SeqCst);
+            return Some(thread);
+        }
+
+        // Mark this CPU as idle, and then check the run queues again: a
+        // thread might have been queued before other CPUs notice that we're
+        // idle.
+        self.idle_cpus.fetch_or(cpu_bit, Ordering::SeqCst);
+        if let Some(thread) = self.pick(cpu_id) {
+            self.idle_cpus.fetch_and(!cpu_bit, Ordering::SeqCst);
+            return Some(thread);
+        }
+
+        None
+    }
+
+    fn pick(&self, cpu_id: CpuId) -> Option<SharedRef<Thread>> {
+        let thread = self.runqueues[cpu_id.as_usize()]
+            .lock()
+            .pop_highest()
+            .or_else(|| self.steal(cpu_id))?;
+
+        thread.mark_as_dequeued();
+        Some(thread)
+    }
+
+    /// Steals a thread from other CPUs' run queues.
+    fn steal(&self, cpu_id: CpuId) -> Option<SharedRef<Thread>> {
+        (1..NUM_CPUS_MAX)
+            .map(|i| (cpu_id.as_usize() + i) % NUM_CPUS_MAX)
+            .find_map(|victim| self.runqueues[victim].lock().pop_highest())
+    }
+
+    /// Wakes up an idle CPU (other than the current one) to run the newly
+    /// queued thread.
+    fn wake_idle_cpu(&self, current: CpuId) {
+        let idle_cpus = self.idle_cpus.load(Ordering::SeqCst) & !(1 << current.as_usize());
+        if idle_cpus == 0 {
+            return;
+        }
+
+        let target = idle_cpus.trailing_zeros() as usize;
+        let target_bit = 1 << target;
+        // Claim the CPU so that other CPUs don't send redundant IPIs.
+        if self.idle_cpus.fetch_and(!target_bit, Ordering::SeqCst) & target_bit != 0 {
+            arch::send_ipi(CpuId::new(target as u8));
+        }
     }
 }
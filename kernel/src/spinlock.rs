@@ -7,6 +7,10 @@ use core::panic::Location;
 use core::sync::atomic::AtomicBool;
 use core::sync::atomic::Ordering;
 
+/// The number of spins to warn about a possible deadlock.
+#[cfg(debug_assertions)]
+const DEADLOCK_SPINS_THRESHOLD: usize = 100_000_000;
+
 /// A simple spinlock.
 pub struct SpinLock<T: ?Sized> {
     lock: AtomicBool,
@@ -28,20 +32,27 @@ impl<T> SpinLock<T> {
     #[track_caller]
     pub fn lock(&self) -> SpinLockGuard<'_, T> {
         #[cfg(debug_assertions)]
-        if self.lock.load(Ordering::Relaxed) {
-            println!(
-                "spinlock: {:x}: deadlock detected - mutex will never be left locked in single CPU!\ncalled from: {}\ncurrently locked by: {:?}",
-                self as *const _ as usize,
-                Location::caller(),
-                unsafe { *self.locked_by.get() },
-            );
-        }
+        let mut num_spins: usize = 0;
 
         while self
             .lock
             .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
             .is_err()
         {
+            // Other CPUs may hold the lock for a while, but not this long.
+            #[cfg(debug_assertions)]
+            {
+                num_spins += 1;
+                if num_spins == DEADLOCK_SPINS_THRESHOLD {
+                    println!(
+                        "spinlock: {:x}: possible deadlock detected\ncalled from: {}\ncurrently locked by: {:?}",
+                        self as *const _ as usize,
+                        Location::caller(),
+                        unsafe { *self.locked_by.get() },
+                    );
+                }
+            }
+
             core::hint::spin_loop();
         }
 
@@ -16,12 +16,18 @@ use starina::syscall::VsyscallPage;
 
 use crate::channel::Channel;
 use crate::handle::Handle;
-use crate::process::KERNEL_PROCESS;
-use crate::scheduler::GLOBAL_SCHEDULER;
+use crate::isolation::INKERNEL_ISOLATION;
+use crate::memory_account::MemoryAccount;
+use crate::process::Process;
+use crate::refcount::SharedRef;
+use crate::thread::DEFAULT_STACK_SIZE;
 use crate::thread::Thread;
+use crate::thread::ThreadStack;
+use crate::timer;
 
 const INKERNEL_APPS: &[AppSpec] = &[
-    // autotest::SPEC,
+    #[cfg(feature = "autotest")]
+    autotest::SPEC,
     hello::SPEC,
     apiserver::SPEC,
     virtio_net::SPEC,
@@ -51,6 +57,16 @@ pub fn load_inkernel_apps(device_tree: DeviceTree) {
 
     for spec in INKERNEL_APPS {
         info!("startup: starting \"{}\"", spec.name);
+        let memory_account = SharedRef::new(MemoryAccount::new(spec.memory_limit)).unwrap();
+        let process = Process::create(
+            spec.name,
+            INKERNEL_ISOLATION.clone(),
+            memory_account,
+            spec.handle_limit,
+        )
+        .and_then(SharedRef::new)
+        .expect("failed to create a process");
+
         let mut env = serde_json::Map::new();
         for EnvItem { name: env_name, ty } in spec.env {
             let value = match ty {
@@ -84,8 +100,10 @@ pub fn load_inkernel_apps(device_tree: DeviceTree) {
                     // Enqueue a connect message to the server.
                     let (server_ch, client_ch) = Channel::new().unwrap();
                     {
-                        let server_ch_handle =
-                            Handle::new(server_ch, HandleRights::READ | HandleRights::WRITE);
+                        let server_ch_handle = Handle::new(
+                            server_ch,
+                            HandleRights::READ | HandleRights::WRITE | HandleRights::POLL,
+                        );
 
                         let mut handles = ArrayVec::new();
                         handles.push(server_ch_handle.into());
@@ -100,9 +118,11 @@ pub fn load_inkernel_apps(device_tree: DeviceTree) {
 
                     // Add the client channel to the environment.
                     let handle_id = {
-                        let handles = KERNEL_PROCESS.handles();
-                        let handle =
-                            Handle::new(client_ch, HandleRights::READ | HandleRights::WRITE);
+                        let handles = process.handles();
+                        let handle = Handle::new(
+                            client_ch,
+                            HandleRights::READ | HandleRights::WRITE | HandleRights::POLL,
+                        );
                         handles
                             .lock()
                             .insert(handle)
@@ -117,8 +137,11 @@ pub fn load_inkernel_apps(device_tree: DeviceTree) {
         }
 
         if let Some(ch) = server_channels.get(spec.name) {
-            let handle = Handle::new(ch.clone(), HandleRights::READ | HandleRights::WRITE);
-            let handle_id = KERNEL_PROCESS.handles().lock().insert(handle).unwrap();
+            let handle = Handle::new(
+                ch.clone(),
+                HandleRights::READ | HandleRights::WRITE | HandleRights::POLL,
+            );
+            let handle_id = process.handles().lock().insert(handle).unwrap();
             env.insert("startup_ch".into(), serde_json::json!(handle_id.as_raw()));
         };
 
@@ -130,11 +153,19 @@ pub fn load_inkernel_apps(device_tree: DeviceTree) {
             name: spec.name.as_ptr(),
             name_len: spec.name.len(),
             main: spec.main,
+            time_page: &timer::TIME_PAGE,
         }));
 
         let arg = vsyscall_page as *const VsyscallPage as usize;
-        let thread = Thread::new_inkernel(starina::start::start as usize, arg as usize).unwrap();
-
-        GLOBAL_SCHEDULER.push(thread);
+        let stack = ThreadStack::alloc(&process, DEFAULT_STACK_SIZE).unwrap();
+        Thread::new_inkernel(
+            process,
+            "main",
+            stack,
+            starina::start::start as usize,
+            arg as usize,
+            spec.priority,
+        )
+        .unwrap();
     }
 }
@@ -1,3 +1,4 @@
+use alloc::vec::Vec;
 use core::cmp::min;
 
 use starina::address::GPAddr;
@@ -7,32 +8,48 @@ use starina::interrupt::IrqMatcher;
 use starina_types::error::ErrorCode;
 use starina_types::handle::HandleId;
 use starina_types::handle::HandleRights;
+use starina_types::handle::RawHandleInfo;
+use starina_types::log::LOG_RECORD_LEN_MAX;
+use starina_types::log::LogRead;
 use starina_types::message::MESSAGE_DATA_LEN_MAX;
 use starina_types::message::MESSAGE_NUM_HANDLES_MAX;
 use starina_types::message::MessageInfo;
+use starina_types::poll::PollMode;
 use starina_types::poll::Readiness;
+use starina_types::process::MemoryUsage;
 use starina_types::syscall::*;
+use starina_types::thread::Priority;
+use starina_types::timer::MonotonicTime;
 use starina_types::vcpu::VCpuRunState;
 use starina_types::vmspace::PageProtect;
 
 use crate::arch;
+use crate::channel::CallBuffers;
 use crate::channel::Channel;
 use crate::cpuvar::current_thread;
 use crate::folio::Folio;
 use crate::handle::Handle;
+use crate::handle::HandleTable;
 use crate::hvspace::HvSpace;
 use crate::interrupt::Interrupt;
 use crate::isolation::IsolationPtr;
 use crate::isolation::IsolationSlice;
 use crate::isolation::IsolationSliceMut;
 use crate::poll::Poll;
-use crate::process::KERNEL_PROCESS;
+use crate::poll::PollWait;
+use crate::process::PROCESS_NAME_LEN_MAX;
+use crate::process::Process;
 use crate::refcount::SharedRef;
+use crate::thread::DEFAULT_STACK_SIZE;
+use crate::thread::THREAD_NAME_LEN_MAX;
 use crate::thread::Thread;
+use crate::thread::ThreadStack;
 use crate::thread::ThreadState;
 use crate::thread::switch_thread;
 use crate::timer::Timer;
 use crate::vcpu::VCpu;
+use crate::vmo::Vmo;
+use crate::vmspace::MappedObject;
 use crate::vmspace::VmSpace;
 
 pub enum SyscallResult {
@@ -70,29 +87,50 @@ fn log_write(
 
 fn log_read(
     current: &SharedRef<Thread>,
+    cursor: u64,
     buf_ptr: IsolationPtr,
     buf_len: usize,
-) -> Result<usize, ErrorCode> {
+    result_slice: IsolationSliceMut,
+) -> Result<(), ErrorCode> {
     let slice = IsolationSliceMut::new(buf_ptr, buf_len);
     let isolation = current.process().isolation();
 
-    let mut log_buffer = crate::print::LOG_BUFFER.lock();
-    let mut tmp = [0u8; 512];
-    let mut total_len = 0;
-    while let Some(remaining_len) = buf_len.checked_sub(total_len) {
-        // Read from log buffer to tmp.
-        let max_len = min(remaining_len, tmp.len());
-        let read_len = log_buffer.read(&mut tmp[..max_len]);
-        if read_len == 0 {
+    if cursor > crate::print::LOG_BUFFER.lock().next_seq() {
+        return Err(ErrorCode::InvalidArg);
+    }
+
+    let mut result = LogRead {
+        len: 0,
+        next_cursor: cursor,
+        dropped: 0,
+    };
+
+    loop {
+        // Copy one record at a time so that we don't hold the lock while
+        // touching the user buffer.
+        let mut tmp = [0u8; LOG_RECORD_LEN_MAX];
+        let Some((seq, len)) = crate::print::LOG_BUFFER
+            .lock()
+            .read(result.next_cursor, &mut tmp)
+        else {
+            break;
+        };
+
+        if result.len + len > buf_len {
+            if result.len == 0 {
+                return Err(ErrorCode::TooSmall);
+            }
+
             break;
         }
 
-        // Write tmp to the user buffer.
-        slice.write_bytes(isolation, total_len, &tmp[..read_len])?;
-        total_len += read_len;
+        slice.write_bytes(isolation, result.len, &tmp[..len])?;
+        result.len += len;
+        result.dropped += seq - result.next_cursor;
+        result.next_cursor = seq + 1;
     }
 
-    Ok(total_len)
+    result_slice.write(isolation, 0, result)
 }
 
 fn thread_spawn(
@@ -100,22 +138,119 @@ fn thread_spawn(
     process_handle: HandleId,
     pc: usize,
     arg: usize,
+    stack_size: usize,
+    name_ptr: IsolationPtr,
+    name_len: usize,
 ) -> Result<HandleId, ErrorCode> {
-    if process_handle.as_raw() != 0 {
-        debug_warn!("thread_spawn syscall does not support process != 0");
-        return Err(ErrorCode::NotSupported);
+    if name_len > THREAD_NAME_LEN_MAX {
+        return Err(ErrorCode::TooLarge);
     }
 
-    let process = current.process();
-    if !SharedRef::ptr_eq(process, &KERNEL_PROCESS) {
-        debug_warn!("thread_spawn syscall supports only in-kernel process (for now)");
-        return Err(ErrorCode::NotSupported);
+    let mut name_buf = [0u8; THREAD_NAME_LEN_MAX];
+    let name_slice = IsolationSlice::new(name_ptr, name_len);
+    name_slice.read_to_slice(current.process().isolation(), 0, &mut name_buf[..name_len])?;
+    let name = core::str::from_utf8(&name_buf[..name_len]).map_err(|_| ErrorCode::InvalidArg)?;
+
+    let stack_size = if stack_size == 0 {
+        DEFAULT_STACK_SIZE
+    } else {
+        stack_size
+    };
+
+    // New threads inherit the priority of the spawning thread.
+    let priority = current.base_priority();
+    let mut handle_table = current.process().handles().lock();
+    let thread = if process_handle.as_raw() == 0 {
+        // Spawn a thread in the current process.
+        let process = current.process();
+        let stack = ThreadStack::alloc(process, stack_size)?;
+        if process.isolation().is_usermode() {
+            Thread::new_user(process.clone(), name, stack, pc, arg, priority)?
+        } else {
+            Thread::new_inkernel(process.clone(), name, stack, pc, arg, priority)?
+        }
+    } else {
+        // Spawn a thread in another process.
+        let process = handle_table.get::<Process>(process_handle)?;
+        if !process.is_capable(HandleRights::WRITE) {
+            return Err(ErrorCode::NotAllowed);
+        }
+
+        if !process.isolation().is_usermode() {
+            debug_warn!("thread_spawn syscall supports only user-mode processes");
+            return Err(ErrorCode::NotSupported);
+        }
+
+        // The process knows nothing about the new thread. Use the executable's
+        // entry point if `pc` is not specified.
+        let pc = if pc == 0 {
+            process.entry().ok_or(ErrorCode::InvalidArg)?
+        } else {
+            pc
+        };
+
+        let stack = ThreadStack::alloc(&process, stack_size)?;
+        Thread::new_user(process.into_object(), name, stack, pc, arg, priority)?
+    };
+
+    let handle = Handle::new(
+        thread,
+        HandleRights::READ | HandleRights::WRITE | HandleRights::POLL,
+    );
+    let handle_id = handle_table.insert(handle)?;
+    Ok(handle_id)
+}
+
+fn thread_set_priority(
+    current: &SharedRef<Thread>,
+    thread_handle: HandleId,
+    priority: Priority,
+) -> Result<(), ErrorCode> {
+    if thread_handle.as_raw() == 0 {
+        // The current thread.
+        current.set_priority(priority);
+        return Ok(());
+    }
+
+    let handle_table = current.process().handles().lock();
+    let thread = handle_table.get::<Thread>(thread_handle)?;
+    if !thread.is_capable(HandleRights::WRITE) {
+        return Err(ErrorCode::NotAllowed);
     }
 
-    let thread = Thread::new_inkernel(pc, arg)?;
-    let handle = Handle::new(thread, HandleRights::READ | HandleRights::WRITE);
+    thread.set_priority(priority);
+    Ok(())
+}
+
+fn process_create(
+    current: &SharedRef<Thread>,
+    name_ptr: IsolationPtr,
+    name_len: usize,
+    elf_handle: HandleId,
+) -> Result<HandleId, ErrorCode> {
+    if name_len > PROCESS_NAME_LEN_MAX {
+        return Err(ErrorCode::TooLarge);
+    }
+
+    let mut name_buf = [0u8; PROCESS_NAME_LEN_MAX];
+    let name_slice = IsolationSlice::new(name_ptr, name_len);
+    name_slice.read_to_slice(current.process().isolation(), 0, &mut name_buf[..name_len])?;
+    let name = core::str::from_utf8(&name_buf[..name_len]).map_err(|_| ErrorCode::InvalidArg)?;
+
+    let mut handle_table = current.process().handles().lock();
+    let elf = handle_table.get::<Folio>(elf_handle)?;
+    if !elf.is_capable(HandleRights::READ) {
+        return Err(ErrorCode::NotAllowed);
+    }
 
-    let handle_id = process.handles().lock().insert(handle)?;
+    let memory_account = current.process().memory_account();
+    let process = Process::create_user(name, &elf, memory_account)?;
+    process.charge_to(memory_account)?;
+    let handle = Handle::new(
+        process,
+        HandleRights::READ | HandleRights::WRITE | HandleRights::POLL,
+    );
+    let handle_id = handle_table.insert(handle)?;
     Ok(handle_id)
 }
 
@@ -125,8 +260,65 @@ fn handle_close(current: &SharedRef<Thread>, handle: HandleId) -> Result<(), Err
     Ok(())
 }
 
+fn handle_duplicate(
+    current: &SharedRef<Thread>,
+    handle: HandleId,
+    rights_mask: HandleRights,
+) -> Result<HandleId, ErrorCode> {
+    let mut handle_table = current.process().handles().lock();
+    handle_table.duplicate(handle, rights_mask)
+}
+
+/// Writes up to `max_len` [`RawHandleInfo`]s of a process's handles into `buf`,
+/// skipping the first `skip` handles. `process_handle` `0` means the current
+/// process. Returns the number of entries written.
+fn handle_list(
+    current: &SharedRef<Thread>,
+    process_handle: HandleId,
+    skip: usize,
+    buf: IsolationSliceMut,
+    max_len: usize,
+) -> Result<usize, ErrorCode> {
+    let mut infos = Vec::new();
+    let mut collect = |info| {
+        if infos.try_reserve(1).is_err() {
+            return false;
+        }
+
+        infos.push(info);
+        infos.len() < max_len
+    };
+
+    if max_len > 0 {
+        let handle_table = current.process().handles().lock();
+        if process_handle.as_raw() == 0 {
+            handle_table.inspect(skip, &mut collect);
+        } else {
+            let process = handle_table.get::<Process>(process_handle)?;
+            if !process.is_capable(HandleRights::READ) {
+                return Err(ErrorCode::NotAllowed);
+            }
+
+            // Don't lock two handle tables at once.
+            drop(handle_table);
+            process.handles().lock().inspect(skip, &mut collect);
+        }
+    }
+
+    // Copy them after releasing the lock: writing into the buffer may cause
+    // page faults.
+    let isolation = current.process().isolation();
+    for (i, info) in infos.iter().enumerate() {
+        let raw = RawHandleInfo::from(*info);
+        buf.write(isolation, i * size_of::<RawHandleInfo>(), raw)?;
+    }
+
+    Ok(infos.len())
+}
+
 fn poll_create(current: &SharedRef<Thread>) -> Result<HandleId, ErrorCode> {
     let poll = Poll::new()?;
+    poll.charge_to(current.process().memory_account())?;
     let handle = Handle::new(poll, HandleRights::POLL | HandleRights::WRITE);
     let poll_id = current.process().handles().lock().insert(handle)?;
     Ok(poll_id)
@@ -137,6 +329,7 @@ fn poll_add(
     poll: HandleId,
     object: HandleId,
     interests: Readiness,
+    mode: PollMode,
 ) -> Result<(), ErrorCode> {
     let handles = current.process().handles().lock();
     let poll = handles.get::<Poll>(poll)?;
@@ -146,7 +339,11 @@ fn poll_add(
         return Err(ErrorCode::NotAllowed);
     }
 
-    poll.add(object_handle, object, interests)?;
+    if !object_handle.is_capable(HandleRights::POLL) {
+        return Err(ErrorCode::NotAllowed);
+    }
+
+    poll.add(object_handle, object, interests, mode)?;
     Ok(())
 }
 
@@ -184,7 +381,12 @@ fn poll_remove(
     Ok(())
 }
 
-fn poll_wait(current: &SharedRef<Thread>, poll: HandleId) -> SyscallResult {
+fn poll_wait(
+    current: &SharedRef<Thread>,
+    poll: HandleId,
+    deadline: Option<MonotonicTime>,
+    events: Option<(IsolationPtr, usize)>,
+) -> SyscallResult {
     let handles = current.process().handles().lock();
     let poll = match handles.get::<Poll>(poll) {
         Ok(poll) => poll,
@@ -197,7 +399,16 @@ fn poll_wait(current: &SharedRef<Thread>, poll: HandleId) -> SyscallResult {
         return SyscallResult::Err(ErrorCode::NotAllowed);
     }
 
-    poll.try_wait(current, false)
+    PollWait::new(poll.into_object(), deadline, events).try_wait(current, false)
+}
+
+/// Decodes a deadline argument. Zero means no deadline.
+fn deadline_from_raw_isize(raw: isize) -> Option<MonotonicTime> {
+    if raw == 0 {
+        None
+    } else {
+        Some(MonotonicTime::from_nanos(raw as u64))
+    }
 }
 
 fn poll_try_wait(current: &SharedRef<Thread>, poll: HandleId) -> SyscallResult {
@@ -213,15 +424,36 @@ fn poll_try_wait(current: &SharedRef<Thread>, poll: HandleId) -> SyscallResult {
         return SyscallResult::Err(ErrorCode::NotAllowed);
     }
 
-    poll.try_wait(current, true)
+    PollWait::new(poll.into_object(), None, None).try_wait(current, true)
 }
 
-fn channel_create(current: &SharedRef<Thread>) -> Result<HandleId, ErrorCode> {
+/// Creates a channel pair. Returns the first handle ID, and writes the
+/// second one to `second_slice`.
+fn channel_create(
+    current: &SharedRef<Thread>,
+    second_slice: IsolationSliceMut,
+) -> Result<HandleId, ErrorCode> {
     let (ch1, ch2) = Channel::new()?;
+    let memory_account = current.process().memory_account();
+    ch1.charge_to(memory_account)?;
+    ch2.charge_to(memory_account)?;
     let handle_table = &mut current.process().handles().lock();
-    let ch1_handle = Handle::new(ch1, HandleRights::READ | HandleRights::WRITE);
-    let ch2_handle = Handle::new(ch2, HandleRights::READ | HandleRights::WRITE);
-    let ch1_id = handle_table.insert_consecutive(ch1_handle, ch2_handle)?;
+    let ch1_handle = Handle::new(
+        ch1,
+        HandleRights::READ | HandleRights::WRITE | HandleRights::POLL,
+    );
+    let ch2_handle = Handle::new(
+        ch2,
+        HandleRights::READ | HandleRights::WRITE | HandleRights::POLL,
+    );
+    let (ch1_id, ch2_id) = handle_table.insert_pair(ch1_handle, ch2_handle)?;
+    if let Err(err) = second_slice.write(current.process().isolation(), 0, ch2_id) {
+        // The app never sees the handles. Drop both channels.
+        handle_table.take(ch1_id);
+        handle_table.take(ch2_id);
+        return Err(err);
+    }
+
     Ok(ch1_id)
 }
 
@@ -231,6 +463,7 @@ fn channel_send(
     msginfo: MessageInfo,
     data_ptr: IsolationPtr,
     handles_ptr: IsolationPtr,
+    rights_ptr: Option<IsolationPtr>,
 ) -> Result<(), ErrorCode> {
     let process = current.process();
     let isolation = process.isolation();
@@ -243,7 +476,9 @@ fn channel_send(
 
     let data = IsolationSlice::new(data_ptr, msginfo.data_len());
     let handles = IsolationSlice::new(handles_ptr, size_of::<HandleId>() * msginfo.num_handles());
-    ch.send(isolation, &mut handle_table, msginfo, data, handles)
+    let rights = rights_ptr
+        .map(|ptr| IsolationSlice::new(ptr, size_of::<HandleRights>() * msginfo.num_handles()));
+    ch.send(isolation, &mut handle_table, msginfo, data, handles, rights)
 }
 
 fn channel_recv(
@@ -268,6 +503,41 @@ fn channel_recv(
     Ok(msginfo)
 }
 
+fn channel_call(
+    current: &SharedRef<Thread>,
+    handle: HandleId,
+    msginfo: MessageInfo,
+    data_ptr: IsolationPtr,
+    data_len: usize,
+    handles_ptr: IsolationPtr,
+) -> SyscallResult {
+    let process = current.process();
+    let isolation = process.isolation();
+    let mut handle_table = process.handles().lock();
+    let ch = match handle_table.get::<Channel>(handle) {
+        Ok(ch) => ch,
+        Err(e) => {
+            return SyscallResult::Err(e);
+        }
+    };
+
+    if !ch.is_capable(HandleRights::READ | HandleRights::WRITE) {
+        return SyscallResult::Err(ErrorCode::NotAllowed);
+    }
+
+    ch.call(
+        current,
+        isolation,
+        &mut handle_table,
+        msginfo,
+        CallBuffers {
+            data: data_ptr,
+            data_len,
+            handles: handles_ptr,
+        },
+    )
+}
+
 pub fn vmspace_map(
     current: &SharedRef<Thread>,
     handle: HandleId,
@@ -290,18 +560,17 @@ pub fn vmspace_map(
         handle.into_object()
     };
 
-    let folio = handle_table.get::<Folio>(folio)?;
-    if !folio.is_capable(HandleRights::MAP) {
-        return Err(ErrorCode::NotAllowed);
+    if !prot.user_allowed_flags() {
+        return Err(ErrorCode::InvalidArg);
     }
 
-    if vaddr != VAddr::new(0) {
-        debug_warn!("vmspace_map syscall does not support vaddr != 0");
-        return Err(ErrorCode::NotSupported);
+    let (object, max_prot) = get_mappable(&handle_table, folio)?;
+    if prot & max_prot != prot {
+        return Err(ErrorCode::NotAllowed);
     }
 
-    if folio.len() != len {
-        debug_warn!("vmspace_map syscall does not support folio.len != len");
+    if object.len() != len {
+        debug_warn!("vmspace_map syscall does not support object.len != len");
         return Err(ErrorCode::NotSupported);
     }
 
@@ -310,15 +579,119 @@ pub fn vmspace_map(
         return Err(ErrorCode::NotSupported);
     }
 
-    let vaddr = vmspace.map_anywhere(folio.into_object(), prot)?;
-    Ok(vaddr)
+    if vaddr == VAddr::new(0) {
+        vmspace.map_anywhere(object, prot, max_prot)
+    } else {
+        vmspace.map_fixed(vaddr, object, prot, max_prot)?;
+        Ok(vaddr)
+    }
+}
+
+/// Looks up a mappable object (a folio or a VMO), and returns it with the
+/// most permissive page protection it can be mapped with.
+///
+/// The same object can be mapped into multiple vmspaces. Each mapping is
+/// limited by the rights of the handle used to map it, so a process can
+/// share memory read-only by duplicating the handle without WRITE.
+fn get_mappable(
+    handle_table: &HandleTable,
+    handle: HandleId,
+) -> Result<(MappedObject, PageProtect), ErrorCode> {
+    let handle = handle_table.get_any(handle)?;
+    if !handle.is_capable(HandleRights::MAP) {
+        return Err(ErrorCode::NotAllowed);
+    }
+
+    let mut max_prot = PageProtect::zeroed();
+    if handle.is_capable(HandleRights::READ) {
+        max_prot |= PageProtect::READABLE;
+    }
+    if handle.is_capable(HandleRights::WRITE) {
+        max_prot |= PageProtect::WRITEABLE;
+    }
+    if handle.is_capable(HandleRights::EXEC) {
+        max_prot |= PageProtect::EXECUTABLE;
+    }
+
+    let object = if let Some(folio) = handle.clone().downcast::<Folio>() {
+        MappedObject::Folio(folio.into_object())
+    } else if let Some(vmo) = handle.downcast::<Vmo>() {
+        MappedObject::Vmo(vmo.into_object())
+    } else {
+        return Err(ErrorCode::UnexpectedType);
+    };
+
+    Ok((object, max_prot))
+}
+
+pub fn vmspace_unmap(
+    current: &SharedRef<Thread>,
+    handle: HandleId,
+    vaddr: VAddr,
+    len: usize,
+) -> Result<(), ErrorCode> {
+    let process = current.process();
+    let vmspace = if handle.as_raw() == 0 {
+        process.isolation().vmspace().clone()
+    } else {
+        let handle_table = process.handles().lock();
+        let handle = handle_table.get::<VmSpace>(handle)?;
+        if !handle.is_capable(HandleRights::WRITE) {
+            return Err(ErrorCode::NotAllowed);
+        }
+
+        handle.into_object()
+    };
+
+    vmspace.unmap(vaddr, len)
+}
+
+pub fn vmspace_protect(
+    current: &SharedRef<Thread>,
+    handle: HandleId,
+    vaddr: VAddr,
+    len: usize,
+    prot: PageProtect,
+) -> Result<(), ErrorCode> {
+    if !prot.user_allowed_flags() {
+        return Err(ErrorCode::InvalidArg);
+    }
+
+    let process = current.process();
+    let vmspace = if handle.as_raw() == 0 {
+        process.isolation().vmspace().clone()
+    } else {
+        let handle_table = process.handles().lock();
+        let handle = handle_table.get::<VmSpace>(handle)?;
+        if !handle.is_capable(HandleRights::WRITE) {
+            return Err(ErrorCode::NotAllowed);
+        }
+
+        handle.into_object()
+    };
+
+    vmspace.protect(vaddr, len, prot)
 }
 
 pub fn folio_alloc(current: &SharedRef<Thread>, len: usize) -> Result<HandleId, ErrorCode> {
-    let folio = Folio::alloc(len)?;
+    let memory_account = current.process().memory_account();
+    let folio = SharedRef::new(Folio::alloc_charged(len, memory_account)?)?;
+    folio.charge_to(memory_account)?;
     let handle: Handle<Folio> = Handle::new(
-        SharedRef::new(folio)?,
-        HandleRights::READ | HandleRights::WRITE | HandleRights::MAP,
+        folio,
+        HandleRights::READ | HandleRights::WRITE | HandleRights::EXEC | HandleRights::MAP,
+    );
+    let handle_id = current.process().handles().lock().insert(handle)?;
+    Ok(handle_id)
+}
+
+pub fn vmo_create(current: &SharedRef<Thread>, len: usize) -> Result<HandleId, ErrorCode> {
+    let memory_account = current.process().memory_account();
+    let vmo = SharedRef::new(Vmo::new(len, memory_account.clone())?)?;
+    vmo.charge_to(memory_account)?;
+    let handle: Handle<Vmo> = Handle::new(
+        vmo,
+        HandleRights::READ | HandleRights::WRITE | HandleRights::EXEC | HandleRights::MAP,
     );
     let handle_id = current.process().handles().lock().insert(handle)?;
     Ok(handle_id)
@@ -335,9 +708,10 @@ pub fn folio_pin(
     paddr: PAddr,
     len: usize,
 ) -> Result<HandleId, ErrorCode> {
-    let folio = Folio::pin(paddr, len)?;
+    let folio = SharedRef::new(Folio::pin(paddr, len)?)?;
+    folio.charge_to(current.process().memory_account())?;
     let handle: Handle<Folio> = Handle::new(
-        SharedRef::new(folio)?,
+        folio,
         HandleRights::READ | HandleRights::WRITE | HandleRights::MAP,
     );
     let handle_id = current.process().handles().lock().insert(handle)?;
@@ -349,7 +723,10 @@ fn interrupt_create(
     irq_matcher: IrqMatcher,
 ) -> Result<HandleId, ErrorCode> {
     let interrupt = Interrupt::attach(irq_matcher)?;
-    let handle = Handle::new(interrupt, HandleRights::READ | HandleRights::WRITE);
+    let handle = Handle::new(
+        interrupt,
+        HandleRights::READ | HandleRights::WRITE | HandleRights::POLL,
+    );
     let handle_id = current.process().handles().lock().insert(handle)?;
     Ok(handle_id)
 }
@@ -362,11 +739,9 @@ fn interrupt_ack(current: &SharedRef<Thread>, handle: HandleId) -> Result<(), Er
 }
 
 fn hvspace_create(current: &SharedRef<Thread>) -> Result<HandleId, ErrorCode> {
-    let hvspace = HvSpace::new()?;
-    let handle = Handle::new(
-        SharedRef::new(hvspace)?,
-        HandleRights::READ | HandleRights::WRITE,
-    );
+    let hvspace = SharedRef::new(HvSpace::new()?)?;
+    hvspace.charge_to(current.process().memory_account())?;
+    let handle = Handle::new(hvspace, HandleRights::READ | HandleRights::WRITE);
     let mut handle_table = current.process().handles().lock();
     let handle_id = handle_table.insert(handle)?;
     Ok(handle_id)
@@ -382,11 +757,69 @@ fn hvspace_map(
 ) -> Result<(), ErrorCode> {
     let handle_table = current.process().handles().lock();
     let hvspace = handle_table.get::<HvSpace>(hvspace_handle)?;
-    let folio = handle_table.get::<Folio>(folio_handle)?;
-    hvspace.map(gpaddr, folio.into_object(), len, prot)?;
+    let (object, max_prot) = get_mappable(&handle_table, folio_handle)?;
+    if prot & max_prot != prot {
+        return Err(ErrorCode::NotAllowed);
+    }
+
+    hvspace.map(gpaddr, object, len, prot)?;
+    Ok(())
+}
+
+fn process_kill(
+    current: &SharedRef<Thread>,
+    process_handle: HandleId,
+    code: i32,
+) -> Result<(), ErrorCode> {
+    let handle_table = current.process().handles().lock();
+    let process = handle_table.get::<Process>(process_handle)?;
+    if !process.is_capable(HandleRights::WRITE) {
+        return Err(ErrorCode::NotAllowed);
+    }
+
+    let process = process.into_object();
+    // Killing the process closes its handles. Release the lock first in
+    // case it's the current process.
+    drop(handle_table);
+    process.exit(code);
     Ok(())
 }
 
+fn process_exit_code(
+    current: &SharedRef<Thread>,
+    process_handle: HandleId,
+) -> Result<i32, ErrorCode> {
+    let handle_table = current.process().handles().lock();
+    let process = handle_table.get::<Process>(process_handle)?;
+    if !process.is_capable(HandleRights::READ) {
+        return Err(ErrorCode::NotAllowed);
+    }
+
+    process.exit_code().ok_or(ErrorCode::WouldBlock)
+}
+
+/// Reports the memory usage of a process. `process_handle` `0` means the
+/// current process.
+fn process_memory_usage(
+    current: &SharedRef<Thread>,
+    process_handle: HandleId,
+    usage_slice: IsolationSliceMut,
+) -> Result<(), ErrorCode> {
+    let usage = if process_handle.as_raw() == 0 {
+        current.process().memory_account().usage()
+    } else {
+        let handle_table = current.process().handles().lock();
+        let process = handle_table.get::<Process>(process_handle)?;
+        if !process.is_capable(HandleRights::READ) {
+            return Err(ErrorCode::NotAllowed);
+        }
+
+        process.memory_account().usage()
+    };
+
+    usage_slice.write(current.process().isolation(), 0, usage)
+}
+
 fn vcpu_create(
     current: &SharedRef<Thread>,
     hvspace_handle: HandleId,
@@ -396,8 +829,9 @@ fn vcpu_create(
 ) -> Result<HandleId, ErrorCode> {
     let mut handle_table = current.process().handles().lock();
     let hvspace = handle_table.get::<HvSpace>(hvspace_handle)?;
-    let vcpu = VCpu::new(hvspace.into_object(), entry, arg0, arg1)?;
-    let handle = Handle::new(SharedRef::new(vcpu)?, HandleRights::EXEC);
+    let vcpu = SharedRef::new(VCpu::new(hvspace.into_object(), entry, arg0, arg1)?)?;
+    vcpu.charge_to(current.process().memory_account())?;
+    let handle = Handle::new(vcpu, HandleRights::EXEC);
     let handle_id = handle_table.insert(handle)?;
     Ok(handle_id)
 }
@@ -422,7 +856,11 @@ fn vcpu_run(
 
 fn timer_create(current: &SharedRef<Thread>) -> Result<HandleId, ErrorCode> {
     let timer = SharedRef::new(Timer::new())?;
-    let handle = Handle::new(timer, HandleRights::READ | HandleRights::WRITE);
+    timer.charge_to(current.process().memory_account())?;
+    let handle = Handle::new(
+        timer,
+        HandleRights::READ | HandleRights::WRITE | HandleRights::POLL,
+    );
     let handle_id = current.process().handles().lock().insert(handle)?;
     Ok(handle_id)
 }
@@ -430,14 +868,21 @@ fn timer_create(current: &SharedRef<Thread>) -> Result<HandleId, ErrorCode> {
 fn timer_set(
     current: &SharedRef<Thread>,
     timer_handle: HandleId,
-    duration_ns: u64,
+    deadline: MonotonicTime,
+    interval_ns: Option<u64>,
 ) -> Result<(), ErrorCode> {
     let handle_table = current.process().handles().lock();
     let timer = handle_table.get::<Timer>(timer_handle)?;
-    timer.set_timeout(duration_ns)?;
+    timer.set(deadline, interval_ns)?;
     Ok(())
 }
 
+fn timer_ack(current: &SharedRef<Thread>, timer_handle: HandleId) -> Result<u64, ErrorCode> {
+    let handle_table = current.process().handles().lock();
+    let timer = handle_table.get::<Timer>(timer_handle)?;
+    Ok(timer.ack())
+}
+
 #[allow(clippy::too_many_arguments)]
 fn do_syscall(
     a0: isize,
@@ -459,13 +904,36 @@ fn do_syscall(
             handle_close(current, handle)?;
             Ok(SyscallResult::Done(RetVal::new(0)))
         }
+        SYS_HANDLE_DUPLICATE => {
+            let handle = HandleId::from_raw_isize(a0)?;
+            let rights_mask = HandleRights::from_raw_isize(a1)?;
+            let dup = handle_duplicate(current, handle, rights_mask)?;
+            Ok(SyscallResult::Done(dup.into()))
+        }
         SYS_THREAD_SPAWN => {
             let process_handle = HandleId::from_raw_isize(a0)?;
             let pc = a1 as usize;
             let arg = a2 as usize;
-            let thread = thread_spawn(current, process_handle, pc, arg)?;
+            let stack_size = a3 as usize;
+            let name_ptr = IsolationPtr::new(a4 as usize);
+            let name_len = a5 as usize;
+            let thread = thread_spawn(
+                current,
+                process_handle,
+                pc,
+                arg,
+                stack_size,
+                name_ptr,
+                name_len,
+            )?;
             Ok(SyscallResult::Done(thread.into()))
         }
+        SYS_THREAD_SET_PRIORITY => {
+            let thread_handle = HandleId::from_raw_isize(a0)?;
+            let priority = Priority::from_raw_isize(a1)?;
+            thread_set_priority(current, thread_handle, priority)?;
+            Ok(SyscallResult::Done(RetVal::new(0)))
+        }
         SYS_LOG_WRITE => {
             let str_ptr = IsolationPtr::new(a0 as usize);
             let len = a1 as usize;
@@ -480,7 +948,8 @@ fn do_syscall(
             let poll = HandleId::from_raw_isize(a0)?;
             let object = HandleId::from_raw_isize(a1)?;
             let interests = Readiness::from_raw_isize(a2)?;
-            poll_add(current, poll, object, interests)?;
+            let mode = PollMode::from_raw_isize(a3)?;
+            poll_add(current, poll, object, interests, mode)?;
             Ok(SyscallResult::Done(RetVal::new(0)))
         }
         SYS_POLL_UPDATE => {
@@ -499,7 +968,20 @@ fn do_syscall(
         }
         SYS_POLL_WAIT => {
             let poll = HandleId::from_raw_isize(a0)?;
-            let ret = poll_wait(current, poll);
+            let deadline = deadline_from_raw_isize(a1);
+            let ret = poll_wait(current, poll, deadline, None);
+            Ok(ret)
+        }
+        SYS_POLL_WAIT_MANY => {
+            let poll = HandleId::from_raw_isize(a0)?;
+            let events_ptr = IsolationPtr::new(a1 as usize);
+            let max_events = a2 as usize;
+            let deadline = deadline_from_raw_isize(a3);
+            if max_events == 0 {
+                return Err(ErrorCode::InvalidArg);
+            }
+
+            let ret = poll_wait(current, poll, deadline, Some((events_ptr, max_events)));
             Ok(ret)
         }
         SYS_POLL_TRY_WAIT => {
@@ -508,7 +990,9 @@ fn do_syscall(
             Ok(ret)
         }
         SYS_CHANNEL_CREATE => {
-            let ch1 = channel_create(current)?;
+            let second_ptr = IsolationPtr::new(a0 as usize);
+            let second_slice = IsolationSliceMut::new(second_ptr, size_of::<HandleId>());
+            let ch1 = channel_create(current, second_slice)?;
             Ok(SyscallResult::Done(ch1.into()))
         }
         SYS_CHANNEL_SEND => {
@@ -516,7 +1000,12 @@ fn do_syscall(
             let msginfo = MessageInfo::from_raw_isize(a1)?;
             let data = IsolationPtr::new(a2 as usize);
             let handles = IsolationPtr::new(a3 as usize);
-            channel_send(current, ch, msginfo, data, handles)?;
+            let rights = if a4 == 0 {
+                None
+            } else {
+                Some(IsolationPtr::new(a4 as usize))
+            };
+            channel_send(current, ch, msginfo, data, handles, rights)?;
             Ok(SyscallResult::Done(RetVal::new(0)))
         }
         SYS_CHANNEL_RECV => {
@@ -526,6 +1015,21 @@ fn do_syscall(
             let msginfo = channel_recv(current, handle, data_ptr, handles_ptr)?;
             Ok(SyscallResult::Done(msginfo.into()))
         }
+        SYS_CHANNEL_CALL => {
+            let handle = HandleId::from_raw_isize(a0)?;
+            let msginfo = MessageInfo::from_raw_isize(a1)?;
+            let data_ptr = IsolationPtr::new(a2 as usize);
+            let data_len = a3 as usize;
+            let handles_ptr = IsolationPtr::new(a4 as usize);
+            Ok(channel_call(
+                current,
+                handle,
+                msginfo,
+                data_ptr,
+                data_len,
+                handles_ptr,
+            ))
+        }
         SYS_VMSPACE_MAP => {
             let handle = HandleId::from_raw_isize(a0)?;
             let vaddr = VAddr::new(a1 as usize);
@@ -536,6 +1040,21 @@ fn do_syscall(
             let ret = vmspace_map(current, handle, vaddr, len, folio, offset, prot)?;
             Ok(SyscallResult::Done(ret.into()))
         }
+        SYS_VMSPACE_UNMAP => {
+            let handle = HandleId::from_raw_isize(a0)?;
+            let vaddr = VAddr::new(a1 as usize);
+            let len = a2 as usize;
+            vmspace_unmap(current, handle, vaddr, len)?;
+            Ok(SyscallResult::Done(RetVal::new(0)))
+        }
+        SYS_VMSPACE_PROTECT => {
+            let handle = HandleId::from_raw_isize(a0)?;
+            let vaddr = VAddr::new(a1 as usize);
+            let len = a2 as usize;
+            let prot = PageProtect::from_raw_isize(a3)?;
+            vmspace_protect(current, handle, vaddr, len, prot)?;
+            Ok(SyscallResult::Done(RetVal::new(0)))
+        }
         SYS_FOLIO_ALLOC => {
             let len = a0 as usize;
             let ret = folio_alloc(current, len)?;
@@ -547,6 +1066,11 @@ fn do_syscall(
             let ret = folio_pin(current, paddr, len)?;
             Ok(SyscallResult::Done(ret.into()))
         }
+        SYS_VMO_CREATE => {
+            let len = a0 as usize;
+            let ret = vmo_create(current, len)?;
+            Ok(SyscallResult::Done(ret.into()))
+        }
         SYS_FOLIO_PADDR => {
             let handle = HandleId::from_raw_isize(a0)?;
             let ret = folio_paddr(current, handle)?;
@@ -596,19 +1120,80 @@ fn do_syscall(
         }
         SYS_TIMER_SET => {
             let timer_handle = HandleId::from_raw_isize(a0)?;
-            let duration_ns = a1 as u64;
-            timer_set(current, timer_handle, duration_ns)?;
+            let deadline = MonotonicTime::from_nanos(a1 as u64);
+            let interval_ns = match a2 as u64 {
+                0 => None,
+                ns => Some(ns),
+            };
+            timer_set(current, timer_handle, deadline, interval_ns)?;
             Ok(SyscallResult::Done(RetVal::new(0)))
         }
+        SYS_TIMER_ACK => {
+            let timer_handle = HandleId::from_raw_isize(a0)?;
+            let expirations = timer_ack(current, timer_handle)?;
+            let expirations = isize::try_from(expirations).unwrap_or(isize::MAX);
+            Ok(SyscallResult::Done(RetVal::new(expirations)))
+        }
         SYS_TIMER_NOW => {
             let now = crate::timer::now();
             Ok(SyscallResult::Done(now.into()))
         }
+        SYS_CLOCK_REALTIME => {
+            let now = crate::timer::realtime_now()?;
+            Ok(SyscallResult::Done(RetVal::new(now as isize)))
+        }
+        SYS_PROCESS_CREATE => {
+            let name_ptr = IsolationPtr::new(a0 as usize);
+            let name_len = a1 as usize;
+            let elf = HandleId::from_raw_isize(a2)?;
+            let ret = process_create(current, name_ptr, name_len, elf)?;
+            Ok(SyscallResult::Done(ret.into()))
+        }
+        SYS_PROCESS_EXIT => {
+            let code = a0 as i32;
+            current.process().exit(code);
+            Ok(SyscallResult::Block(ThreadState::Exited))
+        }
+        SYS_PROCESS_KILL => {
+            let process_handle = HandleId::from_raw_isize(a0)?;
+            let code = a1 as i32;
+            process_kill(current, process_handle, code)?;
+            Ok(SyscallResult::Done(RetVal::new(0)))
+        }
+        SYS_PROCESS_EXIT_CODE => {
+            let process_handle = HandleId::from_raw_isize(a0)?;
+            let code = process_exit_code(current, process_handle)?;
+            // Zero-extend so that negative exit codes are not mistaken for
+            // errors.
+            Ok(SyscallResult::Done(RetVal::new(code as u32 as isize)))
+        }
+        SYS_HANDLE_LIST => {
+            let process_handle = HandleId::from_raw_isize(a0)?;
+            let skip = a1 as usize;
+            let buf_ptr = IsolationPtr::new(a2 as usize);
+            let max_len = a3 as usize;
+            let buf_len = max_len
+                .checked_mul(size_of::<RawHandleInfo>())
+                .ok_or(ErrorCode::InvalidArg)?;
+            let buf = IsolationSliceMut::new(buf_ptr, buf_len);
+            let len = handle_list(current, process_handle, skip, buf, max_len)?;
+            Ok(SyscallResult::Done(RetVal::new(len as isize)))
+        }
+        SYS_PROCESS_MEMORY_USAGE => {
+            let process_handle = HandleId::from_raw_isize(a0)?;
+            let usage_ptr = IsolationPtr::new(a1 as usize);
+            let usage_slice = IsolationSliceMut::new(usage_ptr, size_of::<MemoryUsage>());
+            process_memory_usage(current, process_handle, usage_slice)?;
+            Ok(SyscallResult::Done(RetVal::new(0)))
+        }
         SYS_LOG_READ => {
-            let buf_ptr = IsolationPtr::new(a0 as usize);
-            let buf_len = a1 as usize;
-            let bytes_read = log_read(current, buf_ptr, buf_len)?;
-            Ok(SyscallResult::Done(RetVal::new(bytes_read as isize)))
+            let cursor = a0 as u64;
+            let buf_ptr = IsolationPtr::new(a1 as usize);
+            let buf_len = a2 as usize;
+            let result_ptr = IsolationPtr::new(a3 as usize);
+            let result_slice = IsolationSliceMut::new(result_ptr, size_of::<LogRead>());
+            log_read(current, cursor, buf_ptr, buf_len, result_slice)?;
+            Ok(SyscallResult::Done(RetVal::new(0)))
         }
         _ => {
             debug_warn!("unknown syscall: {}", n);
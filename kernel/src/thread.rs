@@ -1,28 +1,90 @@
+use core::fmt;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicU8;
 use core::sync::atomic::AtomicUsize;
 use core::sync::atomic::Ordering;
 
+use arrayvec::ArrayString;
 use starina::poll::Readiness;
+use starina_types::address::VAddr;
 use starina_types::error::ErrorCode;
+use starina_types::handle::HandleKind;
 use starina_types::syscall::RetVal;
+use starina_types::thread::Priority;
+use starina_types::vmspace::PageProtect;
 
 use crate::arch;
+use crate::arch::PAGE_SIZE;
+use crate::channel::BlockedCall;
 use crate::handle::Handleable;
 use crate::poll::Listener;
+use crate::poll::ListenerSet;
 use crate::poll::Poll;
+use crate::poll::PollWait;
 use crate::process::KERNEL_PROCESS;
 use crate::process::Process;
 use crate::refcount::SharedRef;
 use crate::scheduler::GLOBAL_SCHEDULER;
 use crate::spinlock::SpinLock;
+use crate::spinlock::SpinLockGuard;
 use crate::syscall::SyscallResult;
+use crate::timer;
 use crate::vcpu::VCpu;
+use crate::vmo::Vmo;
+
+/// The stack size used when the app doesn't specify one.
+pub const DEFAULT_STACK_SIZE: usize = 1024 * 1024; // 1 MiB
+/// The size of the unmapped area below each thread stack.
+const STACK_GUARD_SIZE: usize = PAGE_SIZE;
+pub const THREAD_NAME_LEN_MAX: usize = 32;
 
 static NUM_THREADS: AtomicUsize = AtomicUsize::new(0);
 
+/// A thread stack, with an unmapped guard page below it.
+///
+/// In user-mode processes, stack pages are allocated on demand. Stacks of
+/// in-kernel apps are allocated up front because they can't take page faults
+/// (see [`VmSpace`]). In both cases, only the guard page is left unmapped: a
+/// stack overflow hits it instead of silently corrupting whatever is mapped
+/// next to the stack.
+///
+/// [`VmSpace`]: crate::vmspace::VmSpace
+pub struct ThreadStack {
+    bottom: VAddr,
+    size: usize,
+}
+
+impl ThreadStack {
+    /// Allocates a stack of `size` bytes in `process`'s address space.
+    pub fn alloc(process: &Process, size: usize) -> Result<ThreadStack, ErrorCode> {
+        let vmo = SharedRef::new(Vmo::new(size, process.memory_account().clone())?)?;
+        let prot = PageProtect::READABLE | PageProtect::WRITEABLE;
+        let bottom = process.isolation().vmspace().map_anywhere_with_guard(
+            vmo,
+            STACK_GUARD_SIZE,
+            prot,
+            prot,
+        )?;
+        Ok(ThreadStack { bottom, size })
+    }
+
+    /// The initial stack pointer.
+    pub fn top(&self) -> VAddr {
+        self.bottom.add(self.size)
+    }
+
+    /// Returns `true` if `vaddr` is in the guard page.
+    fn is_guard(&self, vaddr: VAddr) -> bool {
+        let guard_start = self.bottom.as_usize() - STACK_GUARD_SIZE;
+        (guard_start..self.bottom.as_usize()).contains(&vaddr.as_usize())
+    }
+}
+
 #[derive(Debug)]
 pub enum ThreadState {
     Runnable(Option<RetVal>),
-    BlockedByPoll(SharedRef<Poll>),
+    BlockedByPoll(PollWait),
+    BlockedByCall(BlockedCall),
     RunVCpu(SharedRef<VCpu>),
     ExitVCpu(SharedRef<VCpu>),
     Exited,
@@ -31,6 +93,7 @@ pub enum ThreadState {
 struct Mutable {
     state: ThreadState,
     arch: arch::Thread,
+    listeners: ListenerSet,
 }
 
 impl Mutable {
@@ -42,6 +105,26 @@ impl Mutable {
 pub struct Thread {
     mutable: SpinLock<Mutable>,
     process: SharedRef<Process>,
+    /// The name given by the app, for debugging.
+    name: ArrayString<THREAD_NAME_LEN_MAX>,
+    /// The stack allocated by the kernel. `None` for idle threads, which run
+    /// on the kernel's stack.
+    stack: Option<ThreadStack>,
+    /// The current scheduling priority. This is not in `Mutable` because the
+    /// scheduler reads it while the thread's lock is held.
+    priority: AtomicU8,
+    /// The priority set by the app. `priority` is lowered from this while
+    /// the thread keeps using up its time slices, and is restored when the
+    /// thread blocks.
+    base_priority: AtomicU8,
+    /// Whether the thread is in a run queue.
+    queued: AtomicBool,
+    /// Whether a CPU is running the thread, or is still using its context.
+    /// Another CPU must not resume the thread until it's cleared.
+    on_cpu: AtomicBool,
+    /// Whether another CPU has skipped the thread because `on_cpu` was set.
+    /// The CPU releasing the thread queues it again.
+    requeue_on_release: AtomicBool,
 }
 
 impl Thread {
@@ -50,23 +133,75 @@ impl Thread {
             mutable: SpinLock::new(Mutable {
                 state: ThreadState::Runnable(None),
                 arch: arch::Thread::new_idle(),
+                listeners: ListenerSet::new(),
             }),
             process: KERNEL_PROCESS.clone(),
+            name: ArrayString::from("idle").unwrap(),
+            stack: None,
+            priority: AtomicU8::new(Priority::MIN.as_u8()),
+            base_priority: AtomicU8::new(Priority::MIN.as_u8()),
+            queued: AtomicBool::new(false),
+            on_cpu: AtomicBool::new(false),
+            requeue_on_release: AtomicBool::new(false),
         })
     }
 
-    pub fn new_inkernel(pc: usize, arg: usize) -> Result<SharedRef<Thread>, ErrorCode> {
+    pub fn new_inkernel(
+        process: SharedRef<Process>,
+        name: &str,
+        stack: ThreadStack,
+        pc: usize,
+        arg: usize,
+        priority: Priority,
+    ) -> Result<SharedRef<Thread>, ErrorCode> {
+        debug_assert!(!process.isolation().is_usermode());
+        let arch = arch::Thread::new_inkernel(pc, stack.top().as_usize(), arg);
+        Self::new(process, name, stack, arch, priority)
+    }
+
+    /// Creates a thread in a user-mode process. `pc` is an address in the
+    /// process's address space, and `stack` is allocated in it.
+    pub fn new_user(
+        process: SharedRef<Process>,
+        name: &str,
+        stack: ThreadStack,
+        pc: usize,
+        arg: usize,
+        priority: Priority,
+    ) -> Result<SharedRef<Thread>, ErrorCode> {
+        debug_assert!(process.isolation().is_usermode());
+        let arch = arch::Thread::new_user(pc, stack.top().as_usize(), arg);
+        Self::new(process, name, stack, arch, priority)
+    }
+
+    fn new(
+        process: SharedRef<Process>,
+        name: &str,
+        stack: ThreadStack,
+        arch: arch::Thread,
+        priority: Priority,
+    ) -> Result<SharedRef<Thread>, ErrorCode> {
+        let name = ArrayString::from(name).map_err(|_| ErrorCode::TooLarge)?;
         let thread = SharedRef::new(Thread {
             mutable: SpinLock::new(Mutable {
                 state: ThreadState::Runnable(None), // TODO: Mark as blocked by default.
-                arch: arch::Thread::new_inkernel(pc, arg),
+                arch,
+                listeners: ListenerSet::new(),
             }),
-            process: KERNEL_PROCESS.clone(),
+            process: process.clone(),
+            name,
+            stack: Some(stack),
+            priority: AtomicU8::new(priority.as_u8()),
+            base_priority: AtomicU8::new(priority.as_u8()),
+            queued: AtomicBool::new(false),
+            on_cpu: AtomicBool::new(false),
+            requeue_on_release: AtomicBool::new(false),
         })?;
 
         let old_num_threads = NUM_THREADS.fetch_add(1, Ordering::Relaxed);
         GLOBAL_SCHEDULER.try_reserve_cap(old_num_threads + 1)?;
 
+        process.add_thread();
         GLOBAL_SCHEDULER.push(thread.clone());
         Ok(thread)
     }
@@ -80,6 +215,100 @@ impl Thread {
         &self.process
     }
 
+    /// The thread name, or `<unnamed>` if the app didn't name it.
+    pub fn name(&self) -> &str {
+        if self.name.is_empty() {
+            "<unnamed>"
+        } else {
+            &self.name
+        }
+    }
+
+    /// Returns `true` if a fault at `vaddr` is a stack overflow of this
+    /// thread.
+    pub fn is_stack_overflow(&self, vaddr: VAddr) -> bool {
+        self.stack
+            .as_ref()
+            .is_some_and(|stack| stack.is_guard(vaddr))
+    }
+
+    pub fn priority(&self) -> Priority {
+        let level = self.priority.load(Ordering::Relaxed);
+        // SAFETY: Only valid priorities are stored.
+        unsafe { Priority::new(level).unwrap_unchecked() }
+    }
+
+    pub fn base_priority(&self) -> Priority {
+        let level = self.base_priority.load(Ordering::Relaxed);
+        // SAFETY: Only valid priorities are stored.
+        unsafe { Priority::new(level).unwrap_unchecked() }
+    }
+
+    /// Changes the scheduling priority. If the thread is already in a run
+    /// queue, it takes effect the next time the thread is queued.
+    pub fn set_priority(&self, priority: Priority) {
+        self.base_priority
+            .store(priority.as_u8(), Ordering::Relaxed);
+        self.priority.store(priority.as_u8(), Ordering::Relaxed);
+    }
+
+    /// Lowers the priority by one level because the thread has used up its
+    /// time slice, so that CPU-bound threads don't starve others.
+    fn demote(&self) {
+        let level = self.priority.load(Ordering::Relaxed);
+        self.priority
+            .store(level.saturating_sub(1), Ordering::Relaxed);
+    }
+
+    /// Called by the scheduler when the thread is being queued. Returns
+    /// `false` if it's already in a run queue.
+    pub fn mark_as_queued(&self) -> bool {
+        !self.queued.swap(true, Ordering::AcqRel)
+    }
+
+    /// Called by the scheduler when the thread is taken from a run queue.
+    pub fn mark_as_dequeued(&self) {
+        self.queued.store(false, Ordering::Release);
+    }
+
+    /// Claims the thread for the current CPU. Returns `false` if another CPU
+    /// is still using the thread's context. In that case, the CPU queues the
+    /// thread again when it releases the thread.
+    fn try_claim_cpu(&self) -> bool {
+        let claim = || {
+            self.on_cpu
+                .compare_exchange(false, true, Ordering::SeqCst, Ordering::Relaxed)
+                .is_ok()
+        };
+
+        if claim() {
+            return true;
+        }
+
+        self.requeue_on_release.store(true, Ordering::SeqCst);
+
+        // The other CPU might have released the thread before seeing the
+        // flag.
+        if claim() {
+            self.requeue_on_release.store(false, Ordering::SeqCst);
+            return true;
+        }
+
+        false
+    }
+
+    /// Called when the current CPU no longer touches the thread's context.
+    /// Other CPUs may resume it from now on.
+    fn release_cpu(self: &SharedRef<Self>) {
+        self.on_cpu.store(false, Ordering::SeqCst);
+
+        // Another CPU has picked the thread while we were still using it,
+        // and skipped it. Queue it again.
+        if self.requeue_on_release.swap(false, Ordering::SeqCst) {
+            GLOBAL_SCHEDULER.push(self.clone());
+        }
+    }
+
     pub fn wake(self: &SharedRef<Self>) {
         GLOBAL_SCHEDULER.push(self.clone());
     }
@@ -88,7 +317,9 @@ impl Thread {
         let mut mutable = self.mutable.lock();
         debug_assert!(matches!(
             mutable.state,
-            ThreadState::Runnable(_) | ThreadState::BlockedByPoll(_)
+            ThreadState::Runnable(_)
+                | ThreadState::BlockedByPoll(_)
+                | ThreadState::BlockedByCall(_)
         ));
 
         let was_blocked = !matches!(mutable.state, ThreadState::Runnable(_));
@@ -96,10 +327,47 @@ impl Thread {
         // Update the thread's state.
         mutable.state = new_state;
 
+        // The thread is waiting for an event rather than consuming the CPU.
+        // Forgive its past demotions.
+        if matches!(
+            mutable.state,
+            ThreadState::BlockedByPoll(_) | ThreadState::BlockedByCall(_)
+        ) {
+            self.priority.store(
+                self.base_priority.load(Ordering::Relaxed),
+                Ordering::Relaxed,
+            );
+        }
+
         // If the thread is now runnable, push it to the scheduler.
         if was_blocked && matches!(mutable.state, ThreadState::Runnable(_)) {
             GLOBAL_SCHEDULER.push(self.clone());
         }
+
+        if matches!(mutable.state, ThreadState::Exited) {
+            self.on_exited(mutable);
+        }
+    }
+
+    /// Marks the thread as exited because its process has been terminated.
+    fn reap(&self) {
+        let mut mutable = self.mutable.lock();
+        if matches!(mutable.state, ThreadState::Exited) {
+            return;
+        }
+
+        mutable.state = ThreadState::Exited;
+        self.on_exited(mutable);
+    }
+
+    fn on_exited(&self, mutable: SpinLockGuard<'_, Mutable>) {
+        // Wake up threads joining this thread.
+        mutable
+            .listeners
+            .notify_all(Readiness::READABLE | Readiness::CLOSED);
+        drop(mutable);
+
+        self.process.remove_thread();
     }
 
     pub fn exit_vcpu(self: &SharedRef<Self>) {
@@ -116,6 +384,20 @@ impl Thread {
 impl Drop for Thread {
     fn drop(&mut self) {
         NUM_THREADS.fetch_sub(1, Ordering::Relaxed);
+
+        // No one runs on the stack anymore.
+        if let Some(stack) = &self.stack {
+            let vmspace = self.process.isolation().vmspace();
+            if let Err(err) = vmspace.unmap(stack.bottom, stack.size) {
+                debug_warn!("failed to unmap the stack of {:?}: {:?}", self, err);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Thread {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Thread({}/{})", self.process.name(), self.name())
     }
 }
 
@@ -124,19 +406,42 @@ impl Handleable for Thread {
         // Nothing to do.
     }
 
-    fn add_listener(&self, _listener: Listener) -> Result<(), ErrorCode> {
-        debug_warn!("unsupported method at {}:{}", file!(), line!());
-        Err(ErrorCode::NotSupported)
+    fn add_listener(&self, listener: Listener) -> Result<(), ErrorCode> {
+        self.mutable.lock().listeners.add_listener(listener)?;
+        Ok(())
     }
 
-    fn remove_listener(&self, _poll: &Poll) -> Result<(), ErrorCode> {
-        debug_warn!("unsupported method at {}:{}", file!(), line!());
-        Err(ErrorCode::NotSupported)
+    fn remove_listener(&self, poll: &Poll) -> Result<(), ErrorCode> {
+        self.mutable.lock().listeners.remove_listener(poll);
+        Ok(())
     }
 
     fn readiness(&self) -> Result<Readiness, ErrorCode> {
-        debug_warn!("unsupported method at {}:{}", file!(), line!());
-        Err(ErrorCode::NotSupported)
+        let readiness = match self.mutable.lock().state {
+            ThreadState::Exited => Readiness::READABLE | Readiness::CLOSED,
+            _ => Readiness::new(),
+        };
+
+        Ok(readiness)
+    }
+
+    fn kind(&self) -> HandleKind {
+        HandleKind::Thread
+    }
+}
+
+/// Picks the next thread to run on this CPU, or `None` if the run queues are
+/// empty.
+fn pick_next() -> Option<SharedRef<Thread>> {
+    loop {
+        let next = GLOBAL_SCHEDULER.schedule()?;
+        if next.try_claim_cpu() {
+            return Some(next);
+        }
+
+        // Another CPU hasn't left the thread yet, e.g. it has been woken up
+        // while entering a blocking system call. That CPU will queue it
+        // again, so try other threads instead of waiting for it.
     }
 }
 
@@ -157,28 +462,63 @@ pub fn switch_thread() -> ! {
             (current_thread, is_idle, is_runnable)
         };
 
-        let next = if is_runnable && !is_idle {
+        // If the current thread has used up its time slice, demote it and
+        // give other threads a chance to run. The timer interrupts the thread
+        // when the slice expires, whether it's a user-mode thread, an
+        // in-kernel app, or a vCPU.
+        let slice_expired = is_runnable && !is_idle && timer::is_time_slice_expired();
+        if slice_expired {
+            current_thread.demote();
+        }
+
+        // Preempt the current thread if a higher-priority thread is waiting
+        // to run, e.g. a driver woken up by an interrupt.
+        let preempted = is_runnable
+            && !is_idle
+            && (slice_expired || GLOBAL_SCHEDULER.has_higher_priority(current_thread.priority()));
+
+        let keep_current = is_runnable && !is_idle && !preempted;
+        if !keep_current && !is_idle {
+            current_thread.release_cpu();
+        }
+
+        if preempted {
+            GLOBAL_SCHEDULER.push(current_thread.clone());
+        }
+
+        let (next, new_slice) = if keep_current {
             // If the current thread is still runnable, prioritize it because
             // it might be sending multiple messages in a row.
-            current_thread.clone()
-        } else if let Some(next) = GLOBAL_SCHEDULER.schedule() {
-            // Get the next thread to run. If the runqueue is empty, run the
-            // idle thread.
-            next
+            (current_thread.clone(), false)
+        } else if let Some(next) = pick_next() {
+            (next, true)
         } else {
+            // Switch to the idle thread so that other CPUs don't see the
+            // previous thread as running here.
+            *current_thread = arch::get_cpuvar().idle_thread.clone();
             drop(current_thread);
+            timer::stop_time_slice();
             arch::idle();
         };
 
         // Make the next thread the current thread.
         *current_thread = next;
+        if new_slice {
+            timer::start_time_slice();
+        }
+
+        // Don't resume threads in a terminated process.
+        if current_thread.process().is_exited() {
+            current_thread.reap();
+            continue 'next_thread;
+        }
 
         // Try unblocking the next thread.
         let arch_thread = {
             let mut mutable = current_thread.mutable.lock();
             let retval = match &mutable.state {
-                ThreadState::BlockedByPoll(poll) => {
-                    match poll.try_wait(&current_thread, false) {
+                ThreadState::BlockedByPoll(wait) => {
+                    match wait.try_wait(&current_thread, false) {
                         SyscallResult::Done(result) => {
                             // We've got an event. Resume the thread with a return
                             // value.
@@ -197,6 +537,23 @@ pub fn switch_thread() -> ! {
                         }
                     }
                 }
+                ThreadState::BlockedByCall(call) => {
+                    // Don't hold the thread's lock: receiving the reply
+                    // locks the process's handle table.
+                    let call = call.clone();
+                    drop(mutable);
+                    let result = call.try_complete(&current_thread);
+                    mutable = current_thread.mutable.lock();
+                    match result {
+                        SyscallResult::Done(result) => Some(result),
+                        SyscallResult::Err(err) => Some(err.into()),
+                        SyscallResult::Block(new_state) => {
+                            // The reply hasn't arrived yet.
+                            mutable.state = new_state;
+                            continue 'next_thread;
+                        }
+                    }
+                }
                 ThreadState::RunVCpu(vcpu) => {
                     // Keep at least one reference to vcpu in the state to keep alive.
                     let vcpu_ptr = unsafe { vcpu.arch_vcpu_ptr() };
@@ -215,6 +572,13 @@ pub fn switch_thread() -> ! {
                 ThreadState::Runnable(retval) => *retval,
             };
 
+            // Deliver the return value only once. Otherwise, it would
+            // overwrite the register when the thread is resumed after a
+            // preemption.
+            if retval.is_some() {
+                mutable.state = ThreadState::Runnable(None);
+            }
+
             // The thread is runnable. Get ready to restore the thread's context.
             unsafe {
                 let arch = mutable.arch_thread_ptr();
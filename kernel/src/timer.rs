@@ -1,11 +1,11 @@
-use alloc::vec::Vec;
+use alloc::collections::btree_map::BTreeMap;
 use core::fmt;
-use core::sync::atomic::AtomicU64;
-use core::sync::atomic::Ordering;
 
 use starina::error::ErrorCode;
 use starina::poll::Readiness;
+use starina_types::handle::HandleKind;
 use starina_types::timer::MonotonicTime;
+use starina_types::timer::TimePage;
 
 use crate::arch;
 use crate::handle::Handleable;
@@ -13,16 +13,15 @@ use crate::poll::ListenerSet;
 use crate::poll::Poll;
 use crate::refcount::SharedRef;
 use crate::spinlock::SpinLock;
-
-#[derive(Debug, Clone)]
-enum State {
-    NotSet,
-    Expired,
-    Pending(u64 /* ticks */),
-}
+use crate::thread::Thread;
 
 struct Mutable {
-    state: State,
+    /// The entry in the timer queue. `None` if the timer is not armed.
+    queued: Option<QueueKey>,
+    /// The period of a periodic timer, in ticks.
+    interval: Option<u64>,
+    /// The number of expirations not yet acknowledged.
+    expirations: u64,
     listeners: ListenerSet,
 }
 
@@ -34,44 +33,77 @@ impl Timer {
     pub fn new() -> Self {
         Self {
             mutable: SpinLock::new(Mutable {
-                state: State::NotSet,
+                queued: None,
+                interval: None,
+                expirations: 0,
                 listeners: ListenerSet::new(),
             }),
         }
     }
 
-    pub fn set_timeout(self: &SharedRef<Self>, duration_ns: u64) -> Result<(), ErrorCode> {
+    /// Arms the timer to expire at `deadline`, and then every `interval_ns`
+    /// if it's a periodic timer.
+    ///
+    /// A deadline in the past makes the timer expire immediately.
+    pub fn set(
+        self: &SharedRef<Self>,
+        deadline: MonotonicTime,
+        interval_ns: Option<u64>,
+    ) -> Result<(), ErrorCode> {
         let now_ticks = arch::read_timer();
-        let freq = TIMER_FREQ.load(Ordering::Relaxed);
-        let duration_ticks = ns_to_ticks(duration_ns, freq);
+        let freq = TIME_PAGE.timer_freq();
+        let expires_at = ns_to_ticks(deadline.as_nanos(), freq);
 
         // Guarantee that is_tick_before and is_timer_expired work correctly.
-        if duration_ticks > u64::MAX / 2 {
+        if expires_at > now_ticks && expires_at - now_ticks > u64::MAX / 2 {
             return Err(ErrorCode::InvalidArg);
         }
 
-        let expires_at = now_ticks.wrapping_add(duration_ticks);
+        let interval = match interval_ns {
+            Some(ns) => {
+                let ticks = ns_to_ticks(ns, freq);
+                if ticks == 0 || ticks > u64::MAX / 2 {
+                    return Err(ErrorCode::InvalidArg);
+                }
+
+                Some(ticks)
+            }
+            None => None,
+        };
 
         let mut global_timer = GLOBAL_TIMER.lock();
 
         let mut mutable = self.mutable.lock();
-        mutable.state = State::Pending(expires_at);
+        if let Some(key) = mutable.queued.take() {
+            global_timer.queue.remove(&key);
+        }
+
+        let key = global_timer.insert(expires_at, QueueEntry::Timer(self.clone()));
+        mutable.queued = Some(key);
+        mutable.interval = interval;
+        mutable.expirations = 0;
         drop(mutable);
 
-        global_timer.actives.push(self.clone());
         reschedule_timer(&global_timer);
         Ok(())
     }
+
+    /// Returns the number of expirations since the last call, and clears it.
+    pub fn ack(&self) -> u64 {
+        let mut mutable = self.mutable.lock();
+        core::mem::take(&mut mutable.expirations)
+    }
 }
 
 impl Handleable for Timer {
     fn close(&self) {
+        let mut global_timer = GLOBAL_TIMER.lock();
         let mut mutable = self.mutable.lock();
 
-        // Mark the timer as expired so it will be removed from the global
-        // active list on the next timer interrupt. This prevents the timer
-        // from being processed further.
-        mutable.state = State::Expired;
+        // Remove the timer from the queue not to fire it anymore.
+        if let Some(key) = mutable.queued.take() {
+            global_timer.queue.remove(&key);
+        }
 
         mutable.listeners.notify_all(Readiness::CLOSED);
     }
@@ -91,12 +123,21 @@ impl Handleable for Timer {
         let mut readiness = Readiness::new();
 
         let mutable = self.mutable.lock();
-        if matches!(mutable.state, State::Expired) {
+        if mutable.expirations > 0 {
             readiness |= Readiness::READABLE;
         }
 
         Ok(readiness)
     }
+
+    fn kind(&self) -> HandleKind {
+        let mutable = self.mutable.lock();
+        HandleKind::Timer {
+            armed: mutable.queued.is_some(),
+            periodic: mutable.interval.is_some(),
+            expirations: mutable.expirations,
+        }
+    }
 }
 
 impl fmt::Debug for Timer {
@@ -105,30 +146,72 @@ impl fmt::Debug for Timer {
     }
 }
 
-pub static TIMER_FREQ: AtomicU64 = AtomicU64::new(0);
+/// The clock parameters, shared with in-kernel apps so that they can read the
+/// time without system calls.
+pub static TIME_PAGE: TimePage = TimePage::new();
+
+/// The time slice given to a thread each time it's picked from the run
+/// queue.
+const TIME_SLICE_NS: u64 = 10_000_000; // 10 ms
+
+/// The key of an entry in the timer queue.
+///
+/// Ticks are counted from the boot and never wrap around in practice, so
+/// entries are simply ordered by their deadlines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct QueueKey {
+    expires_at: u64, /* ticks */
+    /// A unique number to distinguish entries with the same deadline.
+    seq: u64,
+}
+
+enum QueueEntry {
+    Timer(SharedRef<Timer>),
+    /// A thread to be woken up at the deadline, e.g. a timed-out `poll_wait`.
+    Sleeper(SharedRef<Thread>),
+}
 
 struct GlobalTimer {
-    actives: Vec<SharedRef<Timer>>,
+    /// Armed timers and sleeping threads, ordered by their deadlines.
+    queue: BTreeMap<QueueKey, QueueEntry>,
+    /// The keys of sleeping threads in `queue`, indexed by the thread
+    /// addresses.
+    sleepers: BTreeMap<usize, QueueKey>,
+    next_seq: u64,
 }
 
 impl GlobalTimer {
     pub const fn new() -> Self {
         Self {
-            actives: Vec::new(),
+            queue: BTreeMap::new(),
+            sleepers: BTreeMap::new(),
+            next_seq: 0,
         }
     }
+
+    fn insert(&mut self, expires_at: u64, entry: QueueEntry) -> QueueKey {
+        let key = QueueKey {
+            expires_at,
+            seq: self.next_seq,
+        };
+
+        self.next_seq += 1;
+        self.queue.insert(key, entry);
+        key
+    }
+
+    fn remove_sleeper(&mut self, thread: &SharedRef<Thread>) -> Option<QueueEntry> {
+        let key = self.sleepers.remove(&SharedRef::addr(thread))?;
+        self.queue.remove(&key)
+    }
 }
 
 static GLOBAL_TIMER: SpinLock<GlobalTimer> = SpinLock::new(GlobalTimer::new());
 
 fn ns_to_ticks(ns: u64, freq: u64) -> u64 {
-    (ns * freq) / 1_000_000_000
-}
-
-fn ticks_to_monotonic_time(ticks: u64, freq: u64) -> MonotonicTime {
-    // Convert ticks to nanoseconds: nanos = ticks * 1_000_000_000 / freq
-    let nanos = ticks * (1_000_000_000 / freq);
-    MonotonicTime::from_nanos(nanos)
+    // Use u128 not to overflow in the multiplication.
+    let ticks = (ns as u128 * freq as u128) / 1_000_000_000;
+    ticks.try_into().unwrap_or(u64::MAX)
 }
 
 /// Compare two tick values considering potential wrapping.
@@ -151,33 +234,97 @@ fn is_timer_expired(now: u64, expires_at: u64) -> bool {
 pub fn init(freq: u64) {
     debug_assert_ne!(freq, 0);
 
-    TIMER_FREQ.store(freq, Ordering::Relaxed);
+    TIME_PAGE.set_timer_freq(freq);
     info!("timer initialized with frequency: {} Hz", freq);
 }
 
 /// Get the current monotonic time since kernel boot.
 pub fn now() -> MonotonicTime {
-    let freq = TIMER_FREQ.load(Ordering::Relaxed);
-    debug_assert_ne!(freq, 0, "timer not initialized");
+    debug_assert_ne!(TIME_PAGE.timer_freq(), 0, "timer not initialized");
 
     let ticks = arch::read_timer();
-    ticks_to_monotonic_time(ticks, freq)
+    TIME_PAGE.ticks_to_monotonic_time(ticks)
 }
 
-// Reschedule for the next earliest timer.
+/// Sets the current wall-clock time, in nanoseconds since the UNIX epoch.
+pub fn set_realtime(realtime_ns: u64) {
+    let at_boot = realtime_ns.saturating_sub(now().as_nanos());
+    TIME_PAGE.set_realtime_at_boot(at_boot);
+    info!(
+        "wall-clock time: {} seconds since the UNIX epoch",
+        realtime_ns / 1_000_000_000
+    );
+}
+
+/// Get the current wall-clock time, in nanoseconds since the UNIX epoch.
+pub fn realtime_now() -> Result<u64, ErrorCode> {
+    let at_boot = TIME_PAGE.realtime_at_boot();
+    if at_boot == 0 {
+        return Err(ErrorCode::NotSupported);
+    }
+
+    Ok(at_boot + now().as_nanos())
+}
+
+// Reschedule for the next earliest timer, or the end of the current time
+// slice.
 fn reschedule_timer(global_timer: &GlobalTimer) {
-    let mut earliest = None;
-    for timer in &global_timer.actives {
-        let mutable = timer.mutable.lock();
-        if let State::Pending(expires_ticks) = mutable.state
-            && (earliest.is_none() || is_tick_before(expires_ticks, earliest.unwrap()))
-        {
-            earliest = Some(expires_ticks);
-        }
+    let mut earliest = arch::get_cpuvar().time_slice_expires_at.get();
+    if let Some((key, _)) = global_timer.queue.first_key_value()
+        && (earliest.is_none() || is_tick_before(key.expires_at, earliest.unwrap()))
+    {
+        earliest = Some(key.expires_at);
     }
 
-    if let Some(timeout) = earliest {
-        arch::set_timer(timeout);
+    // Disarm the timer if there's nothing to wait for. Otherwise, the timer
+    // interrupt would keep firing.
+    arch::set_timer(earliest.unwrap_or(u64::MAX));
+}
+
+/// Wakes up `thread` at `deadline`. It replaces the previous deadline of the
+/// thread, if any.
+pub fn wake_at(thread: &SharedRef<Thread>, deadline: MonotonicTime) -> Result<(), ErrorCode> {
+    let freq = TIME_PAGE.timer_freq();
+    let wakes_at = ns_to_ticks(deadline.as_nanos(), freq);
+
+    let mut global_timer = GLOBAL_TIMER.lock();
+    global_timer.remove_sleeper(thread);
+    let key = global_timer.insert(wakes_at, QueueEntry::Sleeper(thread.clone()));
+    global_timer.sleepers.insert(SharedRef::addr(thread), key);
+    reschedule_timer(&global_timer);
+    Ok(())
+}
+
+/// Cancels the wake-up registered by [`wake_at`], if any.
+pub fn cancel_wake(thread: &SharedRef<Thread>) {
+    let mut global_timer = GLOBAL_TIMER.lock();
+    global_timer.remove_sleeper(thread);
+}
+
+/// Starts a new time slice for the current thread.
+pub fn start_time_slice() {
+    let freq = TIME_PAGE.timer_freq();
+    let expires_at = arch::read_timer().wrapping_add(ns_to_ticks(TIME_SLICE_NS, freq));
+
+    let global_timer = GLOBAL_TIMER.lock();
+    arch::get_cpuvar()
+        .time_slice_expires_at
+        .set(Some(expires_at));
+    reschedule_timer(&global_timer);
+}
+
+/// Stops the time slice because the CPU is going idle.
+pub fn stop_time_slice() {
+    let global_timer = GLOBAL_TIMER.lock();
+    arch::get_cpuvar().time_slice_expires_at.set(None);
+    reschedule_timer(&global_timer);
+}
+
+/// Returns true if the current thread has used up its time slice.
+pub fn is_time_slice_expired() -> bool {
+    match arch::get_cpuvar().time_slice_expires_at.get() {
+        Some(expires_at) => is_timer_expired(arch::read_timer(), expires_at),
+        None => false,
     }
 }
 
@@ -185,26 +332,39 @@ pub fn handle_timer_interrupt() {
     let now_ticks = arch::read_timer();
     let mut global_timer = GLOBAL_TIMER.lock();
 
-    // Check all timers and remove expired ones.
-    let mut new_actives = Vec::new();
-    for timer in &global_timer.actives {
-        let mut mutable = timer.mutable.lock();
-        match mutable.state {
-            State::Pending(expires_at) if is_timer_expired(now_ticks, expires_at) => {
+    // Pop expired entries in the order of their deadlines.
+    while let Some(first) = global_timer.queue.first_entry() {
+        if !is_timer_expired(now_ticks, first.key().expires_at) {
+            break;
+        }
+
+        let (key, entry) = first.remove_entry();
+        match entry {
+            QueueEntry::Timer(timer) => {
+                let mut mutable = timer.mutable.lock();
+                mutable.queued = None;
+                if let Some(interval) = mutable.interval {
+                    // Count the periods we've missed as overruns, and compute
+                    // the next deadline from the previous one, not from now,
+                    // so that the timer doesn't drift.
+                    let missed = now_ticks.wrapping_sub(key.expires_at) / interval;
+                    let next = key.expires_at.wrapping_add((missed + 1) * interval);
+                    mutable.expirations = mutable.expirations.saturating_add(missed + 1);
+                    mutable.queued =
+                        Some(global_timer.insert(next, QueueEntry::Timer(timer.clone())));
+                } else {
+                    mutable.expirations = mutable.expirations.saturating_add(1);
+                }
+
                 // The timer has expired, notify the listeners.
-                mutable.state = State::Expired;
                 mutable.listeners.notify_all(Readiness::READABLE);
             }
-            State::Pending(_) => {
-                // The timer is still pending, keep it in the active list.
-                new_actives.push(timer.clone());
-            }
-            _ => {
-                unreachable!("timer is active but not pending");
+            QueueEntry::Sleeper(thread) => {
+                global_timer.sleepers.remove(&SharedRef::addr(&thread));
+                thread.wake();
             }
         }
     }
 
-    global_timer.actives = new_actives;
     reschedule_timer(&global_timer);
 }
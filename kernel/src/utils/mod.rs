@@ -1,2 +1 @@
 pub mod mmio;
-pub mod ring_buffer;
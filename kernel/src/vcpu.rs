@@ -2,6 +2,7 @@ use core::fmt;
 
 use starina::error::ErrorCode;
 use starina::poll::Readiness;
+use starina_types::handle::HandleKind;
 
 use crate::arch;
 use crate::handle::Handleable;
@@ -23,7 +24,7 @@ impl VCpu {
         arg0: usize,
         arg1: usize,
     ) -> Result<VCpu, ErrorCode> {
-        let arch = arch::VCpu::new(&hvspace, entry, arg0, arg1)?;
+        let arch = arch::VCpu::new(hvspace, entry, arg0, arg1)?;
         Ok(VCpu { arch })
     }
 
@@ -66,4 +67,8 @@ impl Handleable for VCpu {
         debug_warn!("unsupported method at {}:{}", file!(), line!());
         Err(ErrorCode::NotSupported)
     }
+
+    fn kind(&self) -> HandleKind {
+        HandleKind::VCpu
+    }
 }
@@ -0,0 +1,104 @@
+//! Virtual memory object (VMO), a memory region allocated on demand.
+use alloc::collections::BTreeMap;
+
+use starina_types::address::PAddr;
+use starina_types::error::ErrorCode;
+use starina_types::handle::HandleKind;
+use starina_types::poll::Readiness;
+use starina_utils::alignment::is_aligned;
+
+use crate::arch::PAGE_SIZE;
+use crate::folio::Folio;
+use crate::handle::Handleable;
+use crate::memory_account::MemoryAccount;
+use crate::poll::Listener;
+use crate::poll::Poll;
+use crate::refcount::SharedRef;
+use crate::spinlock::SpinLock;
+
+/// A memory region whose pages are allocated on first access.
+///
+/// Unlike [`Folio`], a VMO is not physically contiguous, and creating one
+/// does not allocate any memory. When a mapped page is touched for the first
+/// time, the page fault handler asks the VMO for the page, and it allocates
+/// a zero-filled one.
+///
+/// In the kernel's address space, where in-kernel apps run, all pages are
+/// allocated when the VMO is mapped instead (see [`VmSpace`]).
+///
+/// [`VmSpace`]: crate::vmspace::VmSpace
+///
+/// Pages are shared between all mappings of the same VMO, and are freed
+/// when the VMO is dropped. They're charged to `account` as they're
+/// allocated.
+pub struct Vmo {
+    len: usize,
+    account: SharedRef<MemoryAccount>,
+    /// Allocated pages, keyed by their offsets in the VMO.
+    pages: SpinLock<BTreeMap<usize, Folio>>,
+}
+
+impl Vmo {
+    pub fn new(len: usize, account: SharedRef<MemoryAccount>) -> Result<Vmo, ErrorCode> {
+        if len == 0 || !is_aligned(len, PAGE_SIZE) {
+            return Err(ErrorCode::InvalidArg);
+        }
+
+        Ok(Vmo {
+            len,
+            account,
+            pages: SpinLock::new(BTreeMap::new()),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the physical address of the page at `offset`, allocating it
+    /// if it's the first access.
+    pub fn get_or_alloc_page(&self, offset: usize) -> Result<PAddr, ErrorCode> {
+        debug_assert!(is_aligned(offset, PAGE_SIZE));
+        if offset >= self.len {
+            return Err(ErrorCode::InvalidArg);
+        }
+
+        let mut pages = self.pages.lock();
+        if let Some(page) = pages.get(&offset) {
+            return Ok(page.paddr());
+        }
+
+        let page = Folio::alloc_charged(PAGE_SIZE, &self.account)?;
+        let paddr = page.paddr();
+        pages.insert(offset, page);
+        Ok(paddr)
+    }
+}
+
+impl Handleable for Vmo {
+    fn close(&self) {
+        // Do nothing
+    }
+
+    fn add_listener(&self, _listener: Listener) -> Result<(), ErrorCode> {
+        debug_warn!("unsupported method at {}:{}", file!(), line!());
+        Err(ErrorCode::NotSupported)
+    }
+
+    fn remove_listener(&self, _poll: &Poll) -> Result<(), ErrorCode> {
+        debug_warn!("unsupported method at {}:{}", file!(), line!());
+        Err(ErrorCode::NotSupported)
+    }
+
+    fn readiness(&self) -> Result<Readiness, ErrorCode> {
+        debug_warn!("unsupported method at {}:{}", file!(), line!());
+        Err(ErrorCode::NotSupported)
+    }
+
+    fn kind(&self) -> HandleKind {
+        HandleKind::Vmo {
+            len: self.len,
+            allocated_len: self.pages.lock().len() * PAGE_SIZE,
+        }
+    }
+}
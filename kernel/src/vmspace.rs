@@ -1,37 +1,372 @@
 //! Virtual memory space management.
+use alloc::collections::BTreeMap;
+
 use starina::error::ErrorCode;
 use starina::poll::Readiness;
+use starina_types::address::PAddr;
 use starina_types::address::VAddr;
+use starina_types::handle::HandleKind;
 use starina_types::vmspace::PageProtect;
+use starina_utils::alignment::align_down;
 
 use crate::arch;
+use crate::arch::PAGE_SIZE;
 use crate::folio::Folio;
 use crate::handle::Handleable;
 use crate::poll::Listener;
 use crate::refcount::SharedRef;
+use crate::spinlock::SpinLock;
+use crate::vmo::Vmo;
+
+/// A kernel object that can be mapped into a [`VmSpace`].
+pub enum MappedObject {
+    /// Physically contiguous memory, mapped entirely when it's mapped.
+    Folio(SharedRef<Folio>),
+    /// Memory allocated on demand: pages are mapped on page faults. In the
+    /// kernel's address space, all pages are mapped when it's mapped.
+    Vmo(SharedRef<Vmo>),
+}
+
+impl MappedObject {
+    pub fn len(&self) -> usize {
+        match self {
+            MappedObject::Folio(folio) => folio.len(),
+            MappedObject::Vmo(vmo) => vmo.len(),
+        }
+    }
+}
+
+impl From<SharedRef<Folio>> for MappedObject {
+    fn from(folio: SharedRef<Folio>) -> MappedObject {
+        MappedObject::Folio(folio)
+    }
+}
+
+impl From<SharedRef<Vmo>> for MappedObject {
+    fn from(vmo: SharedRef<Vmo>) -> MappedObject {
+        MappedObject::Vmo(vmo)
+    }
+}
+
+struct Mapping {
+    /// The mapped object. The mapping keeps it alive until it's unmapped.
+    object: MappedObject,
+    /// The current protection of the mapping.
+    prot: PageProtect,
+    /// The most permissive protection allowed for this mapping, derived from
+    /// the rights of the handle it was mapped with.
+    max_prot: PageProtect,
+}
 
 pub struct VmSpace {
     arch: arch::VmSpace,
+    /// Whether this is an address space for user-mode processes. If so,
+    /// mapped pages are accessible from user mode.
+    user: bool,
+    /// Objects mapped in this space, keyed by the start address.
+    mappings: SpinLock<BTreeMap<VAddr, Mapping>>,
 }
 
 impl VmSpace {
     pub fn new() -> Result<VmSpace, ErrorCode> {
         let arch = arch::VmSpace::new()?;
-        Ok(VmSpace { arch })
+        Ok(VmSpace {
+            arch,
+            user: false,
+            mappings: SpinLock::new(BTreeMap::new()),
+        })
     }
 
+    pub fn new_user() -> Result<VmSpace, ErrorCode> {
+        let arch = arch::VmSpace::new()?;
+        Ok(VmSpace {
+            arch,
+            user: true,
+            mappings: SpinLock::new(BTreeMap::new()),
+        })
+    }
+
+    /// Maps `object` at an arbitrary address. `max_prot` limits the
+    /// protection the mapping can be changed to later by
+    /// [`VmSpace::protect`].
     pub fn map_anywhere(
         &self,
-        folio: SharedRef<Folio>,
+        object: impl Into<MappedObject>,
+        prot: PageProtect,
+        max_prot: PageProtect,
+    ) -> Result<VAddr, ErrorCode> {
+        if prot & max_prot != prot {
+            return Err(ErrorCode::NotAllowed);
+        }
+
+        let object = object.into();
+        let mut mappings = self.mappings.lock();
+        let vaddr = match &object {
+            MappedObject::Folio(folio) => {
+                self.arch
+                    .map_anywhere(folio.paddr(), folio.len(), self.arch_prot(prot))?
+            }
+            MappedObject::Vmo(vmo) => self.arch.reserve_anywhere(vmo.len())?,
+        };
+
+        let vmo_len = match &object {
+            MappedObject::Folio(_) => None,
+            MappedObject::Vmo(vmo) => Some(vmo.len()),
+        };
+
+        mappings.insert(
+            vaddr,
+            Mapping {
+                object,
+                prot,
+                max_prot,
+            },
+        );
+        drop(mappings);
+
+        if let Some(len) = vmo_len {
+            self.commit_if_kernel(vaddr, len, prot)?;
+        }
+
+        Ok(vaddr)
+    }
+
+    /// Maps `vmo` at an arbitrary address, with `guard_len` bytes of address
+    /// space right below it left unmapped. Nothing is ever mapped in the
+    /// guard, so running off the beginning of the mapping always faults.
+    pub fn map_anywhere_with_guard(
+        &self,
+        vmo: SharedRef<Vmo>,
+        guard_len: usize,
         prot: PageProtect,
+        max_prot: PageProtect,
     ) -> Result<VAddr, ErrorCode> {
-        let paddr = folio.paddr();
-        let len = folio.len();
+        if prot & max_prot != prot {
+            return Err(ErrorCode::NotAllowed);
+        }
+
+        let len = guard_len
+            .checked_add(vmo.len())
+            .ok_or(ErrorCode::InvalidArg)?;
+
+        let vmo_len = vmo.len();
+        let mut mappings = self.mappings.lock();
+        let vaddr = self.arch.reserve_anywhere(len)?.add(guard_len);
+        mappings.insert(
+            vaddr,
+            Mapping {
+                object: MappedObject::Vmo(vmo),
+                prot,
+                max_prot,
+            },
+        );
+        drop(mappings);
+
+        self.commit_if_kernel(vaddr, vmo_len, prot)?;
+        Ok(vaddr)
+    }
+
+    /// Maps `object` at `vaddr`. See [`VmSpace::map_anywhere`] for
+    /// `max_prot`.
+    pub fn map_fixed(
+        &self,
+        vaddr: VAddr,
+        object: impl Into<MappedObject>,
+        prot: PageProtect,
+        max_prot: PageProtect,
+    ) -> Result<(), ErrorCode> {
+        if prot & max_prot != prot {
+            return Err(ErrorCode::NotAllowed);
+        }
+
+        let object = object.into();
+        let mut mappings = self.mappings.lock();
+
+        // VMO pages are not in the page table until they're touched. Check
+        // the existing mappings too.
+        let end = vaddr
+            .as_usize()
+            .checked_add(object.len())
+            .ok_or(ErrorCode::InvalidArg)?;
+        if let Some((start, mapping)) = mappings.range(..VAddr::new(end)).next_back()
+            && start.as_usize() + mapping.object.len() > vaddr.as_usize()
+        {
+            return Err(ErrorCode::AlreadyMapped);
+        }
 
-        // The arch's page table will own an reference to the folio.
-        core::mem::forget(folio);
+        let vmo_len = match &object {
+            MappedObject::Folio(folio) => {
+                self.arch
+                    .map_fixed(vaddr, folio.paddr(), folio.len(), self.arch_prot(prot))?;
+                None
+            }
+            MappedObject::Vmo(vmo) => {
+                self.arch.reserve_fixed(vaddr, vmo.len())?;
+                Some(vmo.len())
+            }
+        };
 
-        self.arch.map_anywhere(paddr, len, prot)
+        mappings.insert(
+            vaddr,
+            Mapping {
+                object,
+                prot,
+                max_prot,
+            },
+        );
+        drop(mappings);
+
+        if let Some(len) = vmo_len {
+            self.commit_if_kernel(vaddr, len, prot)?;
+        }
+
+        Ok(())
+    }
+
+    /// Unmaps the object mapped at `vaddr`. The object is freed if no one
+    /// else references it.
+    ///
+    /// `vaddr` and `len` must match an existing mapping exactly: unmapping a
+    /// part of a mapping is not supported.
+    pub fn unmap(&self, vaddr: VAddr, len: usize) -> Result<(), ErrorCode> {
+        let mut mappings = self.mappings.lock();
+        let Some(mapping) = mappings.get(&vaddr) else {
+            return Err(ErrorCode::NotFound);
+        };
+
+        if mapping.object.len() != len {
+            debug_warn!("unmap: partial unmap is not supported");
+            return Err(ErrorCode::InvalidArg);
+        }
+
+        self.arch.unmap(vaddr, len)?;
+        mappings.remove(&vaddr);
+        Ok(())
+    }
+
+    /// Changes the protection of `[vaddr, vaddr + len)`. The range must be
+    /// within a single mapping.
+    pub fn protect(&self, vaddr: VAddr, len: usize, prot: PageProtect) -> Result<(), ErrorCode> {
+        let mut mappings = self.mappings.lock();
+        let Some((start, mapping)) = mappings.range_mut(..=vaddr).next_back() else {
+            return Err(ErrorCode::NotFound);
+        };
+
+        let mapping_end = start.as_usize() + mapping.object.len();
+        match vaddr.as_usize().checked_add(len) {
+            Some(end) if end <= mapping_end => {}
+            _ => return Err(ErrorCode::NotFound),
+        }
+
+        if prot & mapping.max_prot != prot {
+            return Err(ErrorCode::NotAllowed);
+        }
+
+        if matches!(mapping.object, MappedObject::Vmo(_)) {
+            // Pages not touched yet are not in the page table.
+            debug_warn!("protect: changing the protection of a VMO is not supported");
+            return Err(ErrorCode::NotSupported);
+        }
+
+        self.arch.protect(vaddr, len, self.arch_prot(prot))?;
+        if *start == vaddr && len == mapping.object.len() {
+            mapping.prot = prot;
+        }
+
+        Ok(())
+    }
+
+    /// Handles a page fault at `vaddr` caused by an `access`. Returns an
+    /// error if the fault is not for a demand-paged page, or the access is
+    /// not allowed.
+    pub fn handle_page_fault(&self, vaddr: VAddr, access: PageProtect) -> Result<(), ErrorCode> {
+        let mappings = self.mappings.lock();
+        let Some((start, mapping)) = mappings.range(..=vaddr).next_back() else {
+            return Err(ErrorCode::NotFound);
+        };
+
+        let offset = vaddr.as_usize() - start.as_usize();
+        if offset >= mapping.object.len() {
+            return Err(ErrorCode::NotFound);
+        }
+
+        let MappedObject::Vmo(vmo) = &mapping.object else {
+            // Folios are mapped entirely. This is a protection violation.
+            return Err(ErrorCode::NotAllowed);
+        };
+
+        if mapping.prot & access != access {
+            return Err(ErrorCode::NotAllowed);
+        }
+
+        let page_offset = align_down(offset, PAGE_SIZE);
+        let paddr = vmo.get_or_alloc_page(page_offset)?;
+        self.arch
+            .map_page(start.add(page_offset), paddr, self.arch_prot(mapping.prot))
+    }
+
+    /// Makes sure the demand-paged pages in `[vaddr, vaddr + len)` are
+    /// allocated and mapped, so that the kernel can access them without
+    /// page faults. Pages not in a VMO mapping are left as they are.
+    pub fn populate(&self, vaddr: VAddr, len: usize, access: PageProtect) -> Result<(), ErrorCode> {
+        let end = vaddr
+            .as_usize()
+            .checked_add(len)
+            .ok_or(ErrorCode::InvalidAddress)?;
+        let mut page = align_down(vaddr.as_usize(), PAGE_SIZE);
+        while page < end {
+            match self.handle_page_fault(VAddr::new(page), access) {
+                Ok(()) | Err(ErrorCode::NotFound) => {}
+                // Let the caller handle it as it does for other pages.
+                Err(ErrorCode::NotAllowed) => {}
+                Err(err) => return Err(err),
+            }
+
+            page += PAGE_SIZE;
+        }
+
+        Ok(())
+    }
+
+    /// Allocates and maps all pages of a VMO mapped at `vaddr` if this is the
+    /// kernel's address space.
+    ///
+    /// In-kernel apps run in the kernel mode, where page faults are not
+    /// resolved: the app might be interrupted while holding a lock the fault
+    /// handler needs, such as the heap allocator's. If it fails, the mapping
+    /// is removed.
+    fn commit_if_kernel(
+        &self,
+        vaddr: VAddr,
+        len: usize,
+        prot: PageProtect,
+    ) -> Result<(), ErrorCode> {
+        if self.user {
+            return Ok(());
+        }
+
+        if let Err(err) = self.populate(vaddr, len, prot) {
+            if let Err(unmap_err) = self.unmap(vaddr, len) {
+                debug_warn!("failed to unmap a VMO at {}: {:?}", vaddr, unmap_err);
+            }
+
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    fn arch_prot(&self, prot: PageProtect) -> PageProtect {
+        if self.user {
+            prot | PageProtect::USER
+        } else {
+            prot
+        }
+    }
+
+    /// Translates `vaddr` into the physical address and the page protection.
+    pub fn lookup(&self, vaddr: VAddr) -> Result<(PAddr, PageProtect), ErrorCode> {
+        self.arch.lookup(vaddr)
     }
 
     pub fn switch(&self) {
@@ -58,4 +393,8 @@ impl Handleable for VmSpace {
         debug_warn!("unsupported method at {}:{}", file!(), line!());
         Err(ErrorCode::NotSupported)
     }
+
+    fn kind(&self) -> HandleKind {
+        HandleKind::VmSpace
+    }
 }
@@ -1,13 +1,18 @@
 use alloc::sync::Arc;
+use core::ptr;
 
+use starina_types::message::MESSAGE_DATA_LEN_MAX;
 use starina_types::message::MessageInfo;
 
 use crate::error::ErrorCode;
 use crate::handle::HandleId;
+use crate::handle::HandleRights;
 use crate::handle::Handleable;
 use crate::handle::OwnedHandle;
 use crate::message::Message;
 use crate::message::MessageBuffer;
+use crate::poll::RawPoll;
+use crate::poll::Readiness;
 use crate::syscall;
 
 #[derive(Debug)]
@@ -34,11 +39,62 @@ impl Channel {
     pub fn send(&self, msg: Message<'_>) -> Result<(), ErrorCode> {
         let mut msgbuffer = MessageBuffer::new();
         let msginfo = msg.serialize(&mut msgbuffer)?;
-        self.do_send(msginfo, msgbuffer)
+        self.do_send(msginfo, &msgbuffer)
     }
 
-    fn do_send(&self, msginfo: MessageInfo, msg: MessageBuffer) -> Result<(), ErrorCode> {
-        syscall::channel_send(self.0.id(), msginfo, msg.data_ptr(), msg.handles_ptr())?;
+    /// Sends a message. If the peer's queue is full, blocks until the peer
+    /// receives enough messages.
+    pub fn send_blocking(&self, msg: Message<'_>) -> Result<(), ErrorCode> {
+        let mut msgbuffer = MessageBuffer::new();
+        let msginfo = msg.serialize(&mut msgbuffer)?;
+        loop {
+            // The kernel keeps the handles in our process if it fails to
+            // send. Retry with the same message buffer.
+            match self.do_send(msginfo, &msgbuffer) {
+                Err(ErrorCode::Full) => self.wait_writable()?,
+                result => return result,
+            }
+        }
+    }
+
+    fn wait_writable(&self) -> Result<(), ErrorCode> {
+        let poll = RawPoll::create()?;
+        poll.add(self.0.id(), Readiness::WRITABLE | Readiness::CLOSED)?;
+        poll.wait()?;
+        Ok(())
+    }
+
+    /// Sends a message, restricting the rights of the `i`-th handle in the
+    /// message to `rights[i]`.
+    pub fn send_with_rights(
+        &self,
+        msg: Message<'_>,
+        rights: &[HandleRights],
+    ) -> Result<(), ErrorCode> {
+        let mut msgbuffer = MessageBuffer::new();
+        let msginfo = msg.serialize(&mut msgbuffer)?;
+        if rights.len() != msginfo.num_handles() {
+            return Err(ErrorCode::InvalidArg);
+        }
+
+        syscall::channel_send(
+            self.0.id(),
+            msginfo,
+            msgbuffer.data_ptr(),
+            msgbuffer.handles_ptr(),
+            rights.as_ptr(),
+        )?;
+        Ok(())
+    }
+
+    fn do_send(&self, msginfo: MessageInfo, msg: &MessageBuffer) -> Result<(), ErrorCode> {
+        syscall::channel_send(
+            self.0.id(),
+            msginfo,
+            msg.data_ptr(),
+            msg.handles_ptr(),
+            ptr::null(),
+        )?;
         Ok(())
     }
 
@@ -49,6 +105,26 @@ impl Channel {
         Message::deserialize(msginfo, buffer).ok_or(RecvError::Parse(msginfo))
     }
 
+    /// Sends a request, and waits for the reply with the same call ID.
+    ///
+    /// Other messages on the channel are left for `recv`.
+    pub fn call<'a>(
+        &self,
+        msg: Message<'_>,
+        buffer: &'a mut MessageBuffer,
+    ) -> Result<Message<'a>, RecvError> {
+        let msginfo = msg.serialize(buffer).map_err(RecvError::Syscall)?;
+        let msginfo = syscall::channel_call(
+            self.0.id(),
+            msginfo,
+            buffer.data_mut_ptr(),
+            MESSAGE_DATA_LEN_MAX,
+            buffer.handles_mut_ptr(),
+        )
+        .map_err(RecvError::Syscall)?;
+        Message::deserialize(msginfo, buffer).ok_or(RecvError::Parse(msginfo))
+    }
+
     pub fn split(self) -> (ChannelSender, ChannelReceiver) {
         let ch = Arc::new(self);
         let sender = ChannelSender(ch.clone());
@@ -84,6 +160,10 @@ impl ChannelSender {
     pub fn send(&self, msg: Message<'_>) -> Result<(), ErrorCode> {
         self.0.send(msg)
     }
+
+    pub fn send_blocking(&self, msg: Message<'_>) -> Result<(), ErrorCode> {
+        self.0.send_blocking(msg)
+    }
 }
 
 impl Handleable for ChannelSender {
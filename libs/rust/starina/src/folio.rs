@@ -2,6 +2,7 @@
 use starina_types::address::PAddr;
 use starina_types::error::ErrorCode;
 use starina_types::handle::HandleId;
+use starina_types::handle::HandleRights;
 use starina_utils::alignment::is_aligned;
 
 use crate::handle::Handleable;
@@ -65,6 +66,15 @@ impl Folio {
     pub fn paddr(&self) -> Result<PAddr, ErrorCode> {
         syscall::folio_paddr(self.handle.id())
     }
+
+    /// Creates a new handle to the same memory region, with rights restricted
+    /// to `rights`. Send it to another process to share the memory with it:
+    /// for example, without [`HandleRights::WRITE`], the receiver can only
+    /// map it read-only.
+    pub fn duplicate(&self, rights: HandleRights) -> Result<Folio, ErrorCode> {
+        let handle = self.handle.duplicate(rights)?;
+        Ok(Folio { handle })
+    }
 }
 
 impl Handleable for Folio {
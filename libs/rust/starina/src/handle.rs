@@ -1,5 +1,8 @@
+use core::mem::MaybeUninit;
+
 pub use starina_types::handle::*;
 
+use crate::error::ErrorCode;
 pub use crate::prelude::*;
 use crate::syscall;
 
@@ -14,6 +17,12 @@ impl OwnedHandle {
     pub fn id(&self) -> HandleId {
         self.0
     }
+
+    /// Creates a new handle to the same object with a subset of the rights.
+    pub fn duplicate(&self, rights: HandleRights) -> Result<OwnedHandle, ErrorCode> {
+        let id = syscall::handle_duplicate(self.0, rights)?;
+        Ok(OwnedHandle(id))
+    }
 }
 
 impl Drop for OwnedHandle {
@@ -23,6 +32,25 @@ impl Drop for OwnedHandle {
     }
 }
 
+/// Describes all handles of `process`, or of the current process if it's
+/// `0`. Useful for finding leaked handles.
+pub fn list(process: HandleId) -> Result<Vec<HandleInfo>, ErrorCode> {
+    let mut infos = Vec::new();
+    let mut buf = [MaybeUninit::uninit(); 32];
+    loop {
+        let len = syscall::handle_list(process, infos.len(), &mut buf)?;
+        for raw in &buf[..len] {
+            // SAFETY: The kernel has initialized the first `len` entries.
+            let raw = unsafe { raw.assume_init() };
+            infos.push(HandleInfo::try_from(raw)?);
+        }
+
+        if len < buf.len() {
+            return Ok(infos);
+        }
+    }
+}
+
 pub trait Handleable {
     fn handle_id(&self) -> HandleId;
 }
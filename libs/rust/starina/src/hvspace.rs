@@ -3,10 +3,10 @@ use starina_types::handle::HandleId;
 use starina_types::vmspace::PageProtect;
 
 use crate::error::ErrorCode;
-use crate::folio::Folio;
 use crate::handle::Handleable;
 use crate::handle::OwnedHandle;
 use crate::syscall;
+use crate::vmspace::Mappable;
 
 #[derive(Debug)]
 pub struct HvSpace {
@@ -24,11 +24,11 @@ impl HvSpace {
     pub fn map(
         &self,
         gpaddr: GPAddr,
-        folio: &Folio,
+        object: &impl Mappable,
         len: usize,
         prot: PageProtect,
     ) -> Result<(), ErrorCode> {
-        syscall::sys_hvspace_map(self.handle.id(), gpaddr, folio.handle_id(), len, prot)?;
+        syscall::sys_hvspace_map(self.handle.id(), gpaddr, object.handle_id(), len, prot)?;
         Ok(())
     }
 }
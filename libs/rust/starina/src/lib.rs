@@ -30,13 +30,17 @@ pub mod mainloop;
 pub mod message;
 pub mod mmio;
 pub mod poll;
+pub mod process;
+pub mod shared_ring;
 pub mod start;
 pub mod sync;
 pub mod thread;
+pub mod time;
 pub mod timer;
 pub mod vcpu;
 
 pub mod prelude;
 pub mod syscall;
 pub mod tls;
+pub mod vmo;
 pub mod vmspace;
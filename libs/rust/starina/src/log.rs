@@ -1,8 +1,13 @@
 use alloc::vec::Vec;
 use core::fmt;
 
+pub use starina_types::log::LOG_RECORD_LEN_MAX;
+pub use starina_types::log::LogRead;
+
+use crate::sync::Mutex;
+
 struct Writer {
-    buf: spin::Mutex<Vec<u8>>,
+    buf: Mutex<Vec<u8>>,
 }
 
 static LOGGER: Writer = Writer::new();
@@ -10,7 +15,7 @@ static LOGGER: Writer = Writer::new();
 impl Writer {
     const fn new() -> Writer {
         Writer {
-            buf: spin::Mutex::new(Vec::new()),
+            buf: Mutex::new(Vec::new()),
         }
     }
 
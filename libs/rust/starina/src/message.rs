@@ -13,17 +13,6 @@ use crate::channel::Channel;
 use crate::handle::Handleable;
 use crate::handle::OwnedHandle;
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-#[repr(u8)]
-pub enum MessageKind {
-    Connect = 1,
-    Open = 3,
-    OpenReply = 4,
-    Data = 5,
-    Abort = 7,
-    Error = 8,
-}
-
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
 #[repr(transparent)]
 pub struct CallId(u32);
@@ -241,7 +230,7 @@ impl<'a> Message<'a> {
             Message::OpenReply { call_id, ch } => {
                 let len = data.header_only(call_id);
                 handles.write(0, ch);
-                Ok(MessageInfo::new(MessageKind::OpenReply as i32, len, 1))
+                Ok(MessageInfo::new(MessageKind::OpenReply as i32, len, 1).as_reply())
             }
             Message::Data { data: msg_data } => {
                 let len = data.bytes_only(msg_data);
@@ -249,7 +238,7 @@ impl<'a> Message<'a> {
             }
             Message::Abort { call_id, reason } => {
                 let len = data.header_only(RawAbortMsg { call_id, reason });
-                Ok(MessageInfo::new(MessageKind::Abort as i32, len, 0))
+                Ok(MessageInfo::new(MessageKind::Abort as i32, len, 0).as_reply())
             }
             Message::Error { reason } => {
                 let len = data.header_only(RawErrorMsg { reason });
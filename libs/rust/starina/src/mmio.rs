@@ -98,3 +98,11 @@ impl MmioRegion {
         self.paddr
     }
 }
+
+impl Drop for MmioRegion {
+    fn drop(&mut self) {
+        if let Err(err) = syscall::vmspace_unmap(SELF_VMSPACE, self.vaddr, self.len) {
+            debug_warn!("failed to unmap MMIO region at {}: {:?}", self.vaddr, err);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use alloc::collections::VecDeque;
 use alloc::sync::Arc;
 
 pub use starina_types::poll::*;
@@ -9,6 +10,7 @@ use crate::handle::Handleable;
 use crate::handle::OwnedHandle;
 use crate::sync::Mutex;
 use crate::syscall;
+use crate::timer::MonotonicTime;
 
 pub struct RawPoll(OwnedHandle);
 
@@ -19,7 +21,16 @@ impl RawPoll {
     }
 
     pub fn add(&self, object: HandleId, interests: Readiness) -> Result<(), ErrorCode> {
-        syscall::poll_add(self.0.id(), object, interests)
+        self.add_with_mode(object, interests, PollMode::Level)
+    }
+
+    pub fn add_with_mode(
+        &self,
+        object: HandleId,
+        interests: Readiness,
+        mode: PollMode,
+    ) -> Result<(), ErrorCode> {
+        syscall::poll_add(self.0.id(), object, interests, mode)
     }
 
     pub fn update(
@@ -36,7 +47,23 @@ impl RawPoll {
     }
 
     pub fn wait(&self) -> Result<(HandleId, Readiness), ErrorCode> {
-        syscall::poll_wait(self.0.id())
+        syscall::poll_wait(self.0.id(), None)
+    }
+
+    /// Waits for an event until `deadline`. Returns `ErrorCode::TimedOut` if
+    /// no events arrive by then.
+    pub fn wait_until(&self, deadline: MonotonicTime) -> Result<(HandleId, Readiness), ErrorCode> {
+        syscall::poll_wait(self.0.id(), Some(deadline))
+    }
+
+    /// Waits for events, and fills `events` with as many ready events as
+    /// possible. Returns the number of events filled.
+    pub fn wait_many(
+        &self,
+        events: &mut [PollEvent],
+        deadline: Option<MonotonicTime>,
+    ) -> Result<usize, ErrorCode> {
+        syscall::poll_wait_many(self.0.id(), events, deadline)
     }
 
     pub fn try_wait(&self) -> Result<(HandleId, Readiness), ErrorCode> {
@@ -50,9 +77,14 @@ impl Handleable for RawPoll {
     }
 }
 
+/// The maximum number of events [`Poll`] receives in a single system call.
+const POLL_BATCH_LEN: usize = 16;
+
 pub struct Poll<S> {
     raw_poll: RawPoll,
     states: Mutex<HashMap<HandleId, Arc<S>>>,
+    /// Events received from the kernel but not yet returned.
+    pending_events: Mutex<VecDeque<PollEvent>>,
 }
 
 impl<S> Poll<S> {
@@ -61,15 +93,29 @@ impl<S> Poll<S> {
         Ok(Self {
             raw_poll: raw,
             states: Mutex::new(HashMap::new()),
+            pending_events: Mutex::new(VecDeque::new()),
         })
     }
 
     pub fn add(&self, listenee: HandleId, state: S, interests: Readiness) -> Result<(), ErrorCode> {
+        self.add_with_mode(listenee, state, interests, PollMode::Level)
+    }
+
+    /// Adds an object with the given mode. In [`PollMode::OneShot`], the
+    /// object is not reported again until [`Poll::listen`] or
+    /// [`Poll::unlisten`] is called.
+    pub fn add_with_mode(
+        &self,
+        listenee: HandleId,
+        state: S,
+        interests: Readiness,
+        mode: PollMode,
+    ) -> Result<(), ErrorCode> {
         // Insert the state first. The poll might wake other threads up to start
         // handling events on this object immediately.
         self.states.lock().insert(listenee, Arc::new(state));
 
-        if let Err(err) = self.raw_poll.add(listenee, interests) {
+        if let Err(err) = self.raw_poll.add_with_mode(listenee, interests, mode) {
             self.states.lock().remove(&listenee);
             return Err(err);
         }
@@ -95,18 +141,36 @@ impl<S> Poll<S> {
     }
 
     pub fn wait(&self) -> Result<(Arc<S>, Readiness), ErrorCode> {
-        loop {
-            let (id, readiness) = self.raw_poll.wait()?;
-            let state = self.states.lock().get(&id).cloned();
+        self.do_wait(None)
+    }
 
+    /// Waits for an event until `deadline`. Returns `ErrorCode::TimedOut` if
+    /// no events arrive by then.
+    pub fn wait_until(&self, deadline: MonotonicTime) -> Result<(Arc<S>, Readiness), ErrorCode> {
+        self.do_wait(Some(deadline))
+    }
+
+    fn do_wait(&self, deadline: Option<MonotonicTime>) -> Result<(Arc<S>, Readiness), ErrorCode> {
+        loop {
+            // Receive multiple events at once to reduce system calls.
+            let event = self.pending_events.lock().pop_front();
+            let Some(event) = event else {
+                let mut events =
+                    [PollEvent::new(HandleId::from_raw(0), Readiness::NONE); POLL_BATCH_LEN];
+                let len = self.raw_poll.wait_many(&mut events, deadline)?;
+                self.pending_events.lock().extend(&events[..len]);
+                continue;
+            };
+
+            let state = self.states.lock().get(&event.handle_id()).cloned();
             match state {
                 Some(state) => {
-                    return Ok((state, readiness));
+                    return Ok((state, event.readiness()));
                 }
                 None => {
                     // If the state is not found, it might have been removed
                     // after the poll was woken up. Ignore it.
-                    debug_warn!("state not found for handle {:?} in poll", id);
+                    debug_warn!("state not found for handle {:?} in poll", event.handle_id());
                     continue;
                 }
             }
@@ -115,7 +179,13 @@ impl<S> Poll<S> {
 
     pub fn try_wait(&self) -> Result<(Arc<S>, Readiness), ErrorCode> {
         loop {
-            let (id, readiness) = self.raw_poll.try_wait()?;
+            // Return events already received by `wait` first.
+            let event = self.pending_events.lock().pop_front();
+            let (id, readiness) = match event {
+                Some(event) => (event.handle_id(), event.readiness()),
+                None => self.raw_poll.try_wait()?,
+            };
+
             let state = self.states.lock().get(&id).cloned();
 
             match state {
@@ -0,0 +1,93 @@
+//! User-mode processes.
+use starina_types::error::ErrorCode;
+use starina_types::handle::HandleId;
+pub use starina_types::process::MemoryUsage;
+
+use crate::folio::Folio;
+use crate::handle;
+use crate::handle::HandleInfo;
+use crate::handle::Handleable;
+use crate::handle::OwnedHandle;
+use crate::prelude::*;
+use crate::syscall;
+use crate::thread::Thread;
+
+/// Terminates the current process with `code`.
+///
+/// All handles owned by the process are closed, and other threads in the
+/// process are stopped.
+pub fn exit(code: i32) -> ! {
+    syscall::process_exit(code)
+}
+
+/// Returns the memory consumed by the current process.
+pub fn memory_usage() -> Result<MemoryUsage, ErrorCode> {
+    let current = HandleId::from_raw(0); /* current process */
+    syscall::process_memory_usage(current)
+}
+
+/// A user-mode process, running an executable in its own address space.
+///
+/// The process handle becomes readable when the process exits.
+pub struct Process {
+    handle: OwnedHandle,
+}
+
+impl Process {
+    /// Creates a process from a statically-linked ELF executable in `elf`.
+    ///
+    /// The process has no threads until [`Process::start`] is called.
+    pub fn create(name: &str, elf: &Folio) -> Result<Process, ErrorCode> {
+        let id = syscall::process_create(name, elf.handle_id())?;
+        Ok(Process {
+            handle: OwnedHandle::from_raw(id),
+        })
+    }
+
+    pub const fn from_handle(handle: OwnedHandle) -> Self {
+        Self { handle }
+    }
+
+    /// Spawns a thread at the executable's entry point with `arg` in the
+    /// first argument register.
+    pub fn start(&self, arg: usize) -> Result<Thread, ErrorCode> {
+        let id = syscall::thread_spawn(
+            self.handle.id(),
+            0, /* entry point */
+            arg,
+            0, /* default stack size */
+            "main",
+        )?;
+        Ok(Thread::from_handle(OwnedHandle::from_raw(id)))
+    }
+
+    /// Terminates the process with `code`.
+    pub fn kill(&self, code: i32) -> Result<(), ErrorCode> {
+        syscall::process_kill(self.handle.id(), code)
+    }
+
+    /// Returns the memory consumed by the process.
+    pub fn memory_usage(&self) -> Result<MemoryUsage, ErrorCode> {
+        syscall::process_memory_usage(self.handle.id())
+    }
+
+    /// Describes the handles owned by the process.
+    pub fn handles(&self) -> Result<Vec<HandleInfo>, ErrorCode> {
+        handle::list(self.handle.id())
+    }
+
+    /// Returns the exit code, or `None` if the process is still running.
+    pub fn exit_code(&self) -> Result<Option<i32>, ErrorCode> {
+        match syscall::process_exit_code(self.handle.id()) {
+            Ok(code) => Ok(Some(code)),
+            Err(ErrorCode::WouldBlock) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl Handleable for Process {
+    fn handle_id(&self) -> HandleId {
+        self.handle.id()
+    }
+}
@@ -0,0 +1,249 @@
+//! A single-producer single-consumer ring buffer over a shared folio.
+//!
+//! Channel messages are copied by the kernel. For bulk data like packets, two
+//! processes can instead map the same folio and exchange variable-length
+//! records through [`SharedRing`] without involving the kernel. Use a channel
+//! message (or any other notification) to tell the peer that new records are
+//! available.
+//!
+//! ```ignore
+//! // Producer:
+//! let mut ring = SharedRing::create(0x4000)?;
+//! let folio = ring.folio().duplicate(HandleRights::READ | HandleRights::WRITE | HandleRights::MAP)?;
+//! // ... send `folio` to the consumer ...
+//! ring.push(b"hello")?;
+//!
+//! // Consumer:
+//! let mut ring = SharedRing::from_folio(folio, 0x4000)?;
+//! ring.pop_with(|record| assert_eq!(record, b"hello"))?;
+//! ```
+use core::ptr;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use starina_types::address::VAddr;
+use starina_types::error::ErrorCode;
+use starina_types::vmspace::PageProtect;
+use starina_utils::alignment::align_up;
+use starina_utils::alignment::is_aligned;
+
+use crate::folio::Folio;
+use crate::folio::page_size;
+use crate::vmspace::VmSpace;
+
+/// The offset of the data area. The header occupies its own cache line.
+const DATA_OFFSET: usize = 64;
+/// The length prefix of a record.
+const RECORD_HEADER_LEN: usize = size_of::<u32>();
+/// Records are aligned to this so that a length prefix never wraps around.
+const RECORD_ALIGN: usize = 8;
+/// The length prefix which means "skip to the beginning of the data area".
+const PADDING: u32 = u32::MAX;
+
+/// The header at the beginning of the folio.
+#[repr(C)]
+struct Header {
+    /// The total number of bytes written by the producer.
+    head: AtomicU64,
+    /// The total number of bytes consumed by the consumer.
+    tail: AtomicU64,
+}
+
+/// A ring buffer of variable-length records in a folio shared between two
+/// processes.
+///
+/// One side only calls [`SharedRing::push`], and the other side only calls
+/// [`SharedRing::pop`] or [`SharedRing::pop_with`]. Each record is stored
+/// contiguously, so the consumer can read it in place.
+///
+/// The peer may be buggy or malicious. Records read from the ring are
+/// validated, and a broken ring results in [`ErrorCode::InvalidMessage`]
+/// instead of an out-of-bounds access.
+pub struct SharedRing {
+    folio: Folio,
+    vaddr: VAddr,
+    len: usize,
+}
+
+impl SharedRing {
+    /// Allocates a new ring buffer of `len` bytes (including the header).
+    pub fn create(len: usize) -> Result<SharedRing, ErrorCode> {
+        // Folio::alloc fills the memory with zeros: both head and tail are 0.
+        let folio = Folio::alloc(len)?;
+        SharedRing::from_folio(folio, len)
+    }
+
+    /// Maps a ring buffer created by [`SharedRing::create`], typically in
+    /// another process. `len` must be the same as the one passed to `create`.
+    pub fn from_folio(folio: Folio, len: usize) -> Result<SharedRing, ErrorCode> {
+        if !is_aligned(len, page_size()) || len <= DATA_OFFSET {
+            return Err(ErrorCode::InvalidArg);
+        }
+
+        let vaddr = VmSpace::map_anywhere_current(
+            &folio,
+            len,
+            PageProtect::READABLE | PageProtect::WRITEABLE,
+        )?;
+
+        Ok(SharedRing { folio, vaddr, len })
+    }
+
+    /// Returns the underlying folio. Duplicate it to share the ring buffer
+    /// with another process.
+    pub fn folio(&self) -> &Folio {
+        &self.folio
+    }
+
+    /// The size of the data area in bytes.
+    pub fn capacity(&self) -> usize {
+        self.len - DATA_OFFSET
+    }
+
+    /// Appends a record. Returns [`ErrorCode::Full`] if there's not enough
+    /// free space for now, or [`ErrorCode::TooLarge`] if the record won't fit
+    /// even in an empty ring.
+    pub fn push(&mut self, data: &[u8]) -> Result<(), ErrorCode> {
+        let capacity = self.capacity();
+        let record_len = align_up(RECORD_HEADER_LEN + data.len(), RECORD_ALIGN);
+        if record_len > capacity || data.len() >= PADDING as usize {
+            return Err(ErrorCode::TooLarge);
+        }
+
+        let header = self.header();
+        let mut head = header.head.load(Ordering::Relaxed) as usize;
+        let tail = header.tail.load(Ordering::Acquire) as usize;
+        let used = head.wrapping_sub(tail);
+        if used > capacity {
+            return Err(ErrorCode::InvalidMessage);
+        }
+
+        // Don't split a record: if it doesn't fit in the rest of the data
+        // area, skip to the beginning.
+        let offset = head % capacity;
+        let contiguous = capacity - offset;
+        let padding = if record_len > contiguous {
+            contiguous
+        } else {
+            0
+        };
+        if used + padding + record_len > capacity {
+            return Err(ErrorCode::Full);
+        }
+
+        if padding > 0 {
+            self.write_u32(offset, PADDING);
+            head = head.wrapping_add(padding);
+        }
+
+        let offset = head % capacity;
+        self.write_u32(offset, data.len() as u32);
+        unsafe {
+            ptr::copy_nonoverlapping(
+                data.as_ptr(),
+                self.data_ptr().add(offset + RECORD_HEADER_LEN),
+                data.len(),
+            );
+        }
+
+        header
+            .head
+            .store(head.wrapping_add(record_len) as u64, Ordering::Release);
+        Ok(())
+    }
+
+    /// Takes the oldest record and passes it to `f` without copying it.
+    /// Returns [`ErrorCode::Empty`] if there's no record.
+    pub fn pop_with<F, R>(&mut self, f: F) -> Result<R, ErrorCode>
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        let (offset, data_len, next_tail) = self.peek()?;
+        let data = unsafe { core::slice::from_raw_parts(self.data_ptr().add(offset), data_len) };
+        let ret = f(data);
+        self.header()
+            .tail
+            .store(next_tail as u64, Ordering::Release);
+        Ok(ret)
+    }
+
+    /// Copies the oldest record into `buf` and returns its length. Returns
+    /// [`ErrorCode::TooSmall`] without consuming the record if `buf` is too
+    /// small.
+    pub fn pop(&mut self, buf: &mut [u8]) -> Result<usize, ErrorCode> {
+        let (offset, data_len, next_tail) = self.peek()?;
+        if data_len > buf.len() {
+            return Err(ErrorCode::TooSmall);
+        }
+
+        unsafe {
+            ptr::copy_nonoverlapping(self.data_ptr().add(offset), buf.as_mut_ptr(), data_len);
+        }
+
+        self.header()
+            .tail
+            .store(next_tail as u64, Ordering::Release);
+        Ok(data_len)
+    }
+
+    /// Locates the oldest record. Returns the offset of its data in the data
+    /// area, its length, and the tail position after consuming it.
+    fn peek(&self) -> Result<(usize, usize, usize), ErrorCode> {
+        let capacity = self.capacity();
+        let header = self.header();
+        let mut tail = header.tail.load(Ordering::Relaxed) as usize;
+        let head = header.head.load(Ordering::Acquire) as usize;
+        if head.wrapping_sub(tail) > capacity {
+            return Err(ErrorCode::InvalidMessage);
+        }
+
+        if head == tail {
+            return Err(ErrorCode::Empty);
+        }
+
+        let mut offset = tail % capacity;
+        if self.read_u32(offset) == PADDING {
+            tail = tail.wrapping_add(capacity - offset);
+            offset = 0;
+            if head.wrapping_sub(tail) > capacity || head == tail {
+                return Err(ErrorCode::InvalidMessage);
+            }
+        }
+
+        let data_len = self.read_u32(offset) as usize;
+        let record_len = align_up(RECORD_HEADER_LEN + data_len, RECORD_ALIGN);
+        if offset + record_len > capacity || record_len > head.wrapping_sub(tail) {
+            return Err(ErrorCode::InvalidMessage);
+        }
+
+        Ok((
+            offset + RECORD_HEADER_LEN,
+            data_len,
+            tail.wrapping_add(record_len),
+        ))
+    }
+
+    fn header(&self) -> &Header {
+        unsafe { &*self.vaddr.as_ptr::<Header>() }
+    }
+
+    fn data_ptr(&self) -> *mut u8 {
+        unsafe { self.vaddr.add(DATA_OFFSET).as_mut_ptr::<u8>() }
+    }
+
+    fn read_u32(&self, offset: usize) -> u32 {
+        unsafe { ptr::read_volatile(self.data_ptr().add(offset) as *const u32) }
+    }
+
+    fn write_u32(&self, offset: usize, value: u32) {
+        unsafe { ptr::write_volatile(self.data_ptr().add(offset) as *mut u32, value) }
+    }
+}
+
+impl Drop for SharedRing {
+    fn drop(&mut self) {
+        if let Err(err) = VmSpace::unmap_current(self.vaddr, self.len) {
+            debug_warn!("failed to unmap shared ring at {}: {:?}", self.vaddr, err);
+        }
+    }
+}
@@ -8,6 +8,7 @@ use crate::tls;
 
 pub extern "C" fn start(vsyscall: *const VsyscallPage) -> ! {
     let vsyscall = unsafe { &*vsyscall };
+    crate::timer::set_time_page(vsyscall.time_page);
 
     let name_slice = unsafe { slice::from_raw_parts(vsyscall.name, vsyscall.name_len) };
     let name = str::from_utf8(name_slice).unwrap();
@@ -1,6 +1,16 @@
+use core::mem::ManuallyDrop;
 use core::ops::Deref;
 use core::ops::DerefMut;
 
+/// A spinlock.
+///
+/// In-kernel apps can be preempted by the timer. If a thread holding a lock
+/// were preempted, other threads would spin on the lock for the whole time
+/// slice, or even forever if they have a higher priority. To prevent that,
+/// interrupts are disabled while an in-kernel app holds the lock.
+///
+/// In-kernel apps must use this instead of `spin::Mutex` for locks shared
+/// between threads.
 pub struct Mutex<T: ?Sized> {
     inner: spin::Mutex<T>,
 }
@@ -19,26 +29,78 @@ impl<T> Mutex<T> {
 
 impl<T: ?Sized> Mutex<T> {
     pub fn lock(&self) -> MutexGuard<'_, T> {
-        MutexGuard {
-            guard: self.inner.lock(),
+        loop {
+            let interrupts_enabled = disable_interrupts();
+            if let Some(guard) = self.inner.try_lock() {
+                return MutexGuard {
+                    guard: ManuallyDrop::new(guard),
+                    interrupts_enabled,
+                };
+            }
+
+            // Let the timer preempt us while spinning.
+            restore_interrupts(interrupts_enabled);
+            core::hint::spin_loop();
         }
     }
 }
 
 pub struct MutexGuard<'a, T: ?Sized> {
-    guard: spin::MutexGuard<'a, T>,
+    guard: ManuallyDrop<spin::MutexGuard<'a, T>>,
+    /// Whether interrupts were enabled before locking.
+    interrupts_enabled: bool,
 }
 
 impl<'a, T: ?Sized> Deref for MutexGuard<'a, T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        &*self.guard
+        &self.guard
     }
 }
 
 impl<'a, T: ?Sized> DerefMut for MutexGuard<'a, T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut *self.guard
+        &mut self.guard
     }
 }
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        // Unlock first, and then enable interrupts.
+        unsafe {
+            ManuallyDrop::drop(&mut self.guard);
+        }
+
+        restore_interrupts(self.interrupts_enabled);
+    }
+}
+
+/// Disables interrupts, and returns whether they were enabled.
+#[cfg(all(feature = "in-kernel", target_arch = "riscv64"))]
+fn disable_interrupts() -> bool {
+    let sstatus: u64;
+    unsafe {
+        core::arch::asm!("csrrci {}, sstatus, 1 << 1", out(reg) sstatus);
+    }
+
+    sstatus & (1 << 1) != 0
+}
+
+#[cfg(all(feature = "in-kernel", target_arch = "riscv64"))]
+fn restore_interrupts(enabled: bool) {
+    if enabled {
+        unsafe {
+            core::arch::asm!("csrsi sstatus, 1 << 1");
+        }
+    }
+}
+
+/// User-mode apps can't disable interrupts.
+#[cfg(not(all(feature = "in-kernel", target_arch = "riscv64")))]
+fn disable_interrupts() -> bool {
+    false
+}
+
+#[cfg(not(all(feature = "in-kernel", target_arch = "riscv64")))]
+fn restore_interrupts(_enabled: bool) {}
@@ -1,12 +1,21 @@
+use core::mem::MaybeUninit;
+
 use starina_types::address::GPAddr;
 use starina_types::address::PAddr;
 use starina_types::address::VAddr;
 use starina_types::error::ErrorCode;
 use starina_types::handle::HandleId;
+use starina_types::handle::HandleRights;
+use starina_types::handle::RawHandleInfo;
 use starina_types::interrupt::IrqMatcher;
+use starina_types::log::LogRead;
 use starina_types::message::MessageInfo;
+use starina_types::poll::PollEvent;
+use starina_types::poll::PollMode;
 use starina_types::poll::Readiness;
+use starina_types::process::MemoryUsage;
 pub use starina_types::syscall::*;
+use starina_types::thread::Priority;
 use starina_types::timer::MonotonicTime;
 use starina_types::vcpu::VCpuRunState;
 use starina_types::vmspace::PageProtect;
@@ -42,8 +51,54 @@ fn syscall(
             }
         }
     } else {
-        unimplemented!()
+        let ret = usermode_syscall(a0, a1, a2, a3, a4, a5, n as isize);
+        if ret < 0 {
+            Err(ErrorCode::from(ret))
+        } else {
+            Ok(RetVal::new(ret))
+        }
+    }
+}
+
+/// Invokes a system call from a user-mode process. The syscall number is
+/// passed in `a6`, the same register as the in-kernel entry's 7th argument.
+#[cfg(target_arch = "riscv64")]
+fn usermode_syscall(
+    a0: isize,
+    a1: isize,
+    a2: isize,
+    a3: isize,
+    a4: isize,
+    a5: isize,
+    n: isize,
+) -> isize {
+    let ret: isize;
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            inlateout("a0") a0 => ret,
+            in("a1") a1,
+            in("a2") a2,
+            in("a3") a3,
+            in("a4") a4,
+            in("a5") a5,
+            in("a6") n,
+        );
     }
+    ret
+}
+
+#[cfg(not(target_arch = "riscv64"))]
+fn usermode_syscall(
+    _a0: isize,
+    _a1: isize,
+    _a2: isize,
+    _a3: isize,
+    _a4: isize,
+    _a5: isize,
+    _n: isize,
+) -> isize {
+    unimplemented!()
 }
 
 pub fn log_write(s: &[u8]) {
@@ -58,12 +113,50 @@ pub fn log_write(s: &[u8]) {
     );
 }
 
-pub fn thread_spawn(process: HandleId, entry: usize, arg: usize) -> Result<HandleId, ErrorCode> {
+/// Spawns a thread in `process`. The kernel allocates a stack of
+/// `stack_size` bytes, or the default size if it's `0`.
+pub fn thread_spawn(
+    process: HandleId,
+    entry: usize,
+    arg: usize,
+    stack_size: usize,
+    name: &str,
+) -> Result<HandleId, ErrorCode> {
     let ret = syscall(
         SYS_THREAD_SPAWN,
         process.as_raw() as isize,
         entry.try_into().unwrap(),
         arg.try_into().unwrap(),
+        stack_size.try_into().unwrap(),
+        name.as_ptr() as isize,
+        name.len().try_into().unwrap(),
+    )?;
+    // SAFETY: The syscall returns a valid handle ID.
+    let id = unsafe { HandleId::from_raw_isize(ret.as_isize()).unwrap_unchecked() };
+    Ok(id)
+}
+
+/// Sets the priority of `thread`. If `thread` is `0`, it sets the priority of
+/// the current thread.
+pub fn thread_set_priority(thread: HandleId, priority: Priority) -> Result<(), ErrorCode> {
+    syscall(
+        SYS_THREAD_SET_PRIORITY,
+        thread.as_raw() as isize,
+        priority.as_u8() as isize,
+        0,
+        0,
+        0,
+        0,
+    )?;
+    Ok(())
+}
+
+pub fn process_create(name: &str, elf: HandleId) -> Result<HandleId, ErrorCode> {
+    let ret = syscall(
+        SYS_PROCESS_CREATE,
+        name.as_ptr() as isize,
+        name.len().try_into().unwrap(),
+        elf.as_raw() as isize,
         0,
         0,
         0,
@@ -73,6 +166,78 @@ pub fn thread_spawn(process: HandleId, entry: usize, arg: usize) -> Result<Handl
     Ok(id)
 }
 
+pub fn process_exit(code: i32) -> ! {
+    let _ = syscall(SYS_PROCESS_EXIT, code as isize, 0, 0, 0, 0, 0);
+    unreachable!("process_exit returned");
+}
+
+pub fn process_kill(process: HandleId, code: i32) -> Result<(), ErrorCode> {
+    syscall(
+        SYS_PROCESS_KILL,
+        process.as_raw() as isize,
+        code as isize,
+        0,
+        0,
+        0,
+        0,
+    )?;
+    Ok(())
+}
+
+/// Returns the exit code of `process`, or [`ErrorCode::WouldBlock`] if it's
+/// still running.
+pub fn process_exit_code(process: HandleId) -> Result<i32, ErrorCode> {
+    let ret = syscall(
+        SYS_PROCESS_EXIT_CODE,
+        process.as_raw() as isize,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )?;
+    // The kernel zero-extends the exit code.
+    Ok(ret.as_isize() as u32 as i32)
+}
+
+/// Describes handles of `process` (`0` for the current process) into `buf`,
+/// skipping the first `skip` handles. Returns the number of entries written.
+pub fn handle_list(
+    process: HandleId,
+    skip: usize,
+    buf: &mut [MaybeUninit<RawHandleInfo>],
+) -> Result<usize, ErrorCode> {
+    let ret = syscall(
+        SYS_HANDLE_LIST,
+        process.as_raw() as isize,
+        skip.try_into().unwrap(),
+        buf.as_mut_ptr() as isize,
+        buf.len().try_into().unwrap(),
+        0,
+        0,
+    )?;
+
+    let len = ret.as_isize() as usize;
+    debug_assert!(len <= buf.len());
+    Ok(len)
+}
+
+/// Returns the memory usage of `process`. If `process` is `0`, it returns
+/// the usage of the current process.
+pub fn process_memory_usage(process: HandleId) -> Result<MemoryUsage, ErrorCode> {
+    let mut usage = MemoryUsage::default();
+    syscall(
+        SYS_PROCESS_MEMORY_USAGE,
+        process.as_raw() as isize,
+        &raw mut usage as isize,
+        0,
+        0,
+        0,
+        0,
+    )?;
+    Ok(usage)
+}
+
 pub fn thread_exit() -> ! {
     let _ = syscall(SYS_THREAD_EXIT, 0, 0, 0, 0, 0, 0);
     unreachable!("thread_exit returned");
@@ -85,13 +250,18 @@ pub fn poll_create() -> Result<HandleId, ErrorCode> {
     Ok(id)
 }
 
-pub fn poll_add(poll: HandleId, object: HandleId, interests: Readiness) -> Result<(), ErrorCode> {
+pub fn poll_add(
+    poll: HandleId,
+    object: HandleId,
+    interests: Readiness,
+    mode: PollMode,
+) -> Result<(), ErrorCode> {
     syscall(
         SYS_POLL_ADD,
         poll.as_raw() as isize,
         object.as_raw() as isize,
         interests.as_isize(),
-        0,
+        mode.as_isize(),
         0,
         0,
     )?;
@@ -129,12 +299,48 @@ pub fn poll_remove(poll: HandleId, object: HandleId) -> Result<(), ErrorCode> {
     Ok(())
 }
 
-pub fn poll_wait(poll: HandleId) -> Result<(HandleId, Readiness), ErrorCode> {
-    let ret = syscall(SYS_POLL_WAIT, poll.as_raw() as isize, 0, 0, 0, 0, 0)?;
+/// Encodes a deadline argument. Zero means no deadline.
+fn deadline_to_isize(deadline: Option<MonotonicTime>) -> isize {
+    match deadline {
+        Some(deadline) => deadline.as_nanos().max(1) as isize,
+        None => 0,
+    }
+}
+
+pub fn poll_wait(
+    poll: HandleId,
+    deadline: Option<MonotonicTime>,
+) -> Result<(HandleId, Readiness), ErrorCode> {
+    let ret = syscall(
+        SYS_POLL_WAIT,
+        poll.as_raw() as isize,
+        deadline_to_isize(deadline),
+        0,
+        0,
+        0,
+        0,
+    )?;
     let (id, readiness) = ret.into();
     Ok((id, readiness))
 }
 
+pub fn poll_wait_many(
+    poll: HandleId,
+    events: &mut [PollEvent],
+    deadline: Option<MonotonicTime>,
+) -> Result<usize, ErrorCode> {
+    let ret = syscall(
+        SYS_POLL_WAIT_MANY,
+        poll.as_raw() as isize,
+        events.as_mut_ptr() as isize,
+        events.len() as isize,
+        deadline_to_isize(deadline),
+        0,
+        0,
+    )?;
+    Ok(ret.as_isize() as usize)
+}
+
 pub fn poll_try_wait(poll: HandleId) -> Result<(HandleId, Readiness), ErrorCode> {
     let ret = syscall(SYS_POLL_TRY_WAIT, poll.as_raw() as isize, 0, 0, 0, 0, 0)?;
     let (id, readiness) = ret.into();
@@ -142,9 +348,9 @@ pub fn poll_try_wait(poll: HandleId) -> Result<(HandleId, Readiness), ErrorCode>
 }
 
 pub fn channel_create() -> Result<(HandleId, HandleId), ErrorCode> {
-    let ret = syscall(SYS_CHANNEL_CREATE, 0, 0, 0, 0, 0, 0)?;
+    let mut second = HandleId::from_raw(0);
+    let ret = syscall(SYS_CHANNEL_CREATE, &raw mut second as isize, 0, 0, 0, 0, 0)?;
     let first: HandleId = ret.into();
-    let second = HandleId::from_raw(first.as_raw() + 1);
     Ok((first, second))
 }
 
@@ -153,6 +359,7 @@ pub fn channel_send(
     msginfo: MessageInfo,
     data: *const u8,
     handles: *const HandleId,
+    rights: *const HandleRights,
 ) -> Result<(), ErrorCode> {
     syscall(
         SYS_CHANNEL_SEND,
@@ -160,7 +367,7 @@ pub fn channel_send(
         msginfo.as_raw(),
         data as isize,
         handles as isize,
-        0,
+        rights as isize,
         0,
     )?;
     Ok(())
@@ -185,11 +392,59 @@ pub fn channel_recv(
     Ok(msginfo)
 }
 
+/// Sends a message and waits for its reply. The reply is received into the
+/// same buffers: `data` must be `data_len` bytes long, and `handles` must
+/// have room for [`MESSAGE_NUM_HANDLES_MAX`] handles.
+///
+/// [`MESSAGE_NUM_HANDLES_MAX`]: starina_types::message::MESSAGE_NUM_HANDLES_MAX
+pub fn channel_call(
+    ch: HandleId,
+    msginfo: MessageInfo,
+    data: *mut u8,
+    data_len: usize,
+    handles: *mut HandleId,
+) -> Result<MessageInfo, ErrorCode> {
+    let ret = syscall(
+        SYS_CHANNEL_CALL,
+        ch.as_raw() as isize,
+        msginfo.as_raw(),
+        data as isize,
+        data_len.try_into().unwrap(),
+        handles as isize,
+        0,
+    )?;
+    // SAFETY: The syscall returns a valid message info.
+    let msginfo = unsafe { MessageInfo::from_raw_isize(ret.as_isize()).unwrap_unchecked() };
+    Ok(msginfo)
+}
+
 pub fn handle_close(handle: HandleId) -> Result<(), ErrorCode> {
     syscall(SYS_HANDLE_CLOSE, handle.as_raw() as isize, 0, 0, 0, 0, 0)?;
     Ok(())
 }
 
+pub fn handle_duplicate(handle: HandleId, rights: HandleRights) -> Result<HandleId, ErrorCode> {
+    let ret = syscall(
+        SYS_HANDLE_DUPLICATE,
+        handle.as_raw() as isize,
+        rights.0 as isize,
+        0,
+        0,
+        0,
+        0,
+    )?;
+    // SAFETY: The syscall returns a valid handle ID.
+    let id = unsafe { HandleId::from_raw_isize(ret.as_isize()).unwrap_unchecked() };
+    Ok(id)
+}
+
+pub fn vmo_create(len: usize) -> Result<HandleId, ErrorCode> {
+    let ret = syscall(SYS_VMO_CREATE, len.try_into().unwrap(), 0, 0, 0, 0, 0)?;
+    // SAFETY: The syscall returns a valid handle ID.
+    let id = unsafe { HandleId::from_raw_isize(ret.as_isize()).unwrap_unchecked() };
+    Ok(id)
+}
+
 pub fn folio_alloc(len: usize) -> Result<HandleId, ErrorCode> {
     let ret = syscall(SYS_FOLIO_ALLOC, len.try_into().unwrap(), 0, 0, 0, 0, 0)?;
     // SAFETY: The syscall returns a valid handle ID.
@@ -241,6 +496,37 @@ pub fn vmspace_map(
     Ok(vaddr)
 }
 
+pub fn vmspace_unmap(handle: HandleId, vaddr: VAddr, len: usize) -> Result<(), ErrorCode> {
+    syscall(
+        SYS_VMSPACE_UNMAP,
+        handle.as_raw() as isize,
+        vaddr.as_usize() as isize,
+        len.try_into().unwrap(),
+        0,
+        0,
+        0,
+    )?;
+    Ok(())
+}
+
+pub fn vmspace_protect(
+    handle: HandleId,
+    vaddr: VAddr,
+    len: usize,
+    prot: PageProtect,
+) -> Result<(), ErrorCode> {
+    syscall(
+        SYS_VMSPACE_PROTECT,
+        handle.as_raw() as isize,
+        vaddr.as_usize() as isize,
+        len.try_into().unwrap(),
+        prot.as_raw() as isize,
+        0,
+        0,
+    )?;
+    Ok(())
+}
+
 pub fn interrupt_create(irq_matcher: IrqMatcher) -> Result<HandleId, ErrorCode> {
     let ret = syscall(
         SYS_INTERRUPT_CREATE,
@@ -325,12 +611,16 @@ pub fn timer_create() -> Result<HandleId, ErrorCode> {
     Ok(id)
 }
 
-pub fn timer_set(timer: HandleId, duration_ns: u64) -> Result<(), ErrorCode> {
+pub fn timer_set(
+    timer: HandleId,
+    deadline: MonotonicTime,
+    interval_ns: Option<u64>,
+) -> Result<(), ErrorCode> {
     syscall(
         SYS_TIMER_SET,
         timer.as_raw() as isize,
-        duration_ns as isize,
-        0,
+        deadline.as_nanos() as isize,
+        interval_ns.unwrap_or(0) as isize,
         0,
         0,
         0,
@@ -338,23 +628,38 @@ pub fn timer_set(timer: HandleId, duration_ns: u64) -> Result<(), ErrorCode> {
     Ok(())
 }
 
+pub fn timer_ack(timer: HandleId) -> Result<u64, ErrorCode> {
+    let ret = syscall(SYS_TIMER_ACK, timer.as_raw() as isize, 0, 0, 0, 0, 0)?;
+    Ok(ret.as_isize() as u64)
+}
+
 pub fn timer_now() -> Result<MonotonicTime, ErrorCode> {
     let ret = syscall(SYS_TIMER_NOW, 0, 0, 0, 0, 0, 0)?;
     Ok(MonotonicTime::from(ret))
 }
 
-pub fn log_read(buf: &mut [u8]) -> Result<usize, ErrorCode> {
-    let ret = syscall(
+/// Returns the wall-clock time in nanoseconds since the UNIX epoch.
+pub fn clock_realtime() -> Result<u64, ErrorCode> {
+    let ret = syscall(SYS_CLOCK_REALTIME, 0, 0, 0, 0, 0, 0)?;
+    Ok(ret.as_isize() as u64)
+}
+
+/// Reads kernel log records starting from `cursor`, without consuming them.
+///
+/// Use `0` as the initial cursor to read from the oldest record, and
+/// [`LogRead::next_cursor`] to continue reading.
+pub fn log_read(cursor: u64, buf: &mut [u8]) -> Result<LogRead, ErrorCode> {
+    let mut result = LogRead::default();
+    syscall(
         SYS_LOG_READ,
+        cursor as isize,
         buf.as_mut_ptr() as isize,
         buf.len().try_into().unwrap(),
-        0,
-        0,
+        &raw mut result as isize,
         0,
         0,
     )?;
 
-    let read_len = ret.as_isize() as usize;
-    debug_assert!(read_len <= buf.len());
-    Ok(read_len)
+    debug_assert!(result.len <= buf.len());
+    Ok(result)
 }
@@ -1,74 +1,162 @@
 use starina_types::error::ErrorCode;
 use starina_types::handle::HandleId;
+pub use starina_types::thread::Priority;
+use starina_utils::alignment::align_up;
 
+use crate::folio::page_size;
 use crate::handle::Handleable;
 use crate::handle::OwnedHandle;
+use crate::poll::RawPoll;
+use crate::poll::Readiness;
 use crate::prelude::*;
 use crate::syscall;
 
-const THREAD_STACK_SIZE: usize = 1024 * 1024; // 1 MiB
-
-#[allow(dead_code)]
 struct Arg {
-    sp_top: usize,
     closure: Box<dyn FnOnce() + Send + 'static>,
 }
 
-#[cfg(all(target_os = "none", target_arch = "riscv64"))]
-#[unsafe(naked)]
-extern "C" fn arch_entry() -> ! {
-    use core::arch::naked_asm;
-    use core::mem::offset_of;
-
-    naked_asm!(
-        // a0 points to *mut Arg.
-        "ld sp, {sp_offset}(a0)",
-        "j {rust_trampoline}",
-        sp_offset = const offset_of!(Arg, sp_top),
-        rust_trampoline = sym rust_trampoline,
-    );
+/// The entry point of spawned threads. The kernel has already set up the
+/// stack.
+#[cfg(target_os = "none")]
+extern "C" fn thread_entry(arg: *mut Arg) -> ! {
+    let arg = unsafe { Box::from_raw(arg) };
+    (arg.closure)();
+    syscall::thread_exit();
 }
 
 #[cfg(not(target_os = "none"))]
-extern "C" fn arch_entry() -> ! {
+extern "C" fn thread_entry(_arg: *mut Arg) -> ! {
     unimplemented!()
 }
 
-#[cfg(target_os = "none")]
-fn rust_trampoline(arg: *mut Arg) -> ! {
-    let arg = unsafe { Box::from_raw(arg as *mut Arg) };
-    (arg.closure)();
-    syscall::thread_exit();
+/// Sets the priority of the current thread.
+pub fn set_priority(priority: Priority) -> Result<(), ErrorCode> {
+    let current = HandleId::from_raw(0); /* current thread */
+    syscall::thread_set_priority(current, priority)
 }
 
 pub struct Thread {
-    _handle: OwnedHandle,
+    handle: OwnedHandle,
 }
 
 impl Thread {
+    /// Spawns a thread with the default settings. Use [`Thread::builder`]
+    /// to configure the thread.
     pub fn spawn<F>(entry: F) -> Result<Self, ErrorCode>
     where
         F: FnOnce() + Send + 'static,
     {
-        // FIXME: Skip filling the stack with zeros.
-        let stack = vec![0; THREAD_STACK_SIZE];
+        Thread::builder().spawn(entry)
+    }
+
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
 
-        let sp_top = stack.as_ptr() as usize + stack.len();
+    pub const fn from_handle(handle: OwnedHandle) -> Self {
+        Self { handle }
+    }
+
+    pub fn set_priority(&self, priority: Priority) -> Result<(), ErrorCode> {
+        syscall::thread_set_priority(self.handle.id(), priority)
+    }
+
+    /// Blocks until the thread exits.
+    pub fn join(self) -> Result<(), ErrorCode> {
+        // The thread handle becomes readable when the thread exits.
+        let poll = RawPoll::create()?;
+        poll.add(self.handle.id(), Readiness::READABLE)?;
+        loop {
+            let (id, readiness) = poll.wait()?;
+            if id == self.handle.id() && readiness.contains(Readiness::READABLE) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Handleable for Thread {
+    fn handle_id(&self) -> HandleId {
+        self.handle.id()
+    }
+}
+
+/// Thread configuration, for [`Thread::builder`].
+///
+/// ```ignore
+/// let thread = Thread::builder()
+///     .name("worker")
+///     .stack_size(256 * 1024)
+///     .spawn(|| { /* ... */ })?;
+/// ```
+pub struct Builder {
+    name: Option<String>,
+    stack_size: Option<usize>,
+}
+
+impl Builder {
+    pub fn new() -> Builder {
+        Builder {
+            name: None,
+            stack_size: None,
+        }
+    }
+
+    /// Names the thread. The name shows up in error messages, such as a
+    /// stack overflow.
+    pub fn name(mut self, name: &str) -> Builder {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Sets the stack size in bytes. It's rounded up to the page size.
+    ///
+    /// The stack is allocated on demand, so a large stack costs only the
+    /// pages actually used.
+    pub fn stack_size(mut self, size: usize) -> Builder {
+        self.stack_size = Some(size);
+        self
+    }
+
+    pub fn spawn<F>(self, entry: F) -> Result<Thread, ErrorCode>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let stack_size = match self.stack_size {
+            Some(0) => return Err(ErrorCode::InvalidArg),
+            Some(size) => align_up(size, page_size()),
+            None => 0, /* default */
+        };
+
+        let name = self.name.as_deref().unwrap_or("");
         let arg = Box::into_raw(Box::new(Arg {
-            sp_top,
             closure: Box::new(entry),
         }));
 
         let process = HandleId::from_raw(0); /* current process */
-        let handle = syscall::thread_spawn(process, arch_entry as usize, arg as usize)?;
+        let handle = match syscall::thread_spawn(
+            process,
+            thread_entry as usize,
+            arg as usize,
+            stack_size,
+            name,
+        ) {
+            Ok(handle) => handle,
+            Err(err) => {
+                // The thread won't run. Free the closure.
+                drop(unsafe { Box::from_raw(arg) });
+                return Err(err);
+            }
+        };
+
         Ok(Thread {
-            _handle: OwnedHandle::from_raw(handle),
+            handle: OwnedHandle::from_raw(handle),
         })
     }
 }
 
-impl Handleable for Thread {
-    fn handle_id(&self) -> HandleId {
-        self._handle.id()
+impl Default for Builder {
+    fn default() -> Builder {
+        Builder::new()
     }
 }
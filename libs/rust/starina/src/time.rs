@@ -0,0 +1,107 @@
+//! Wall-clock time.
+use core::fmt;
+use core::time::Duration;
+
+use starina_types::error::ErrorCode;
+
+use crate::syscall;
+use crate::timer;
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// A point in wall-clock time, in nanoseconds since the UNIX epoch.
+///
+/// Unlike [`MonotonicTime`](crate::timer::MonotonicTime), this is not
+/// guaranteed to be steady. Use it for showing and recording the time, not
+/// for measuring durations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SystemTime(u64);
+
+impl SystemTime {
+    pub const UNIX_EPOCH: SystemTime = SystemTime(0);
+
+    /// Returns the current wall-clock time.
+    ///
+    /// Returns `ErrorCode::NotSupported` if the system doesn't know the time,
+    /// e.g. no real-time clock is available.
+    pub fn now() -> Result<SystemTime, ErrorCode> {
+        if let Some(time_page) = timer::time_page() {
+            let at_boot = time_page.realtime_at_boot();
+            if at_boot != 0 {
+                return Ok(SystemTime(at_boot + timer::now().as_nanos()));
+            }
+        }
+
+        let nanos = syscall::clock_realtime()?;
+        Ok(SystemTime(nanos))
+    }
+
+    pub const fn from_nanos(nanos: u64) -> SystemTime {
+        SystemTime(nanos)
+    }
+
+    pub const fn as_nanos(&self) -> u64 {
+        self.0
+    }
+
+    pub const fn duration_since_epoch(&self) -> Duration {
+        Duration::from_nanos(self.0)
+    }
+
+    /// Converts the time into a calendar date and time in UTC.
+    pub fn to_utc(&self) -> DateTime {
+        let secs = self.0 / NANOS_PER_SEC;
+        let days = secs / SECS_PER_DAY;
+        let secs_of_day = secs % SECS_PER_DAY;
+
+        // Convert the days since the epoch into a civil date. See
+        // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719468;
+        let era = z / 146097;
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        DateTime {
+            year: year as u32,
+            month: month as u8,
+            day: day as u8,
+            hour: (secs_of_day / 3600) as u8,
+            minute: (secs_of_day / 60 % 60) as u8,
+            second: (secs_of_day % 60) as u8,
+            // 1970-01-01 was a Thursday.
+            weekday: ((days + 4) % 7) as u8,
+        }
+    }
+}
+
+/// A calendar date and time in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u32,
+    /// 1 to 12.
+    pub month: u8,
+    /// 1 to 31.
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// Days since Sunday (0 to 6).
+    pub weekday: u8,
+}
+
+impl fmt::Display for DateTime {
+    /// Formats in ISO 8601, e.g. `2025-01-02T03:04:05Z`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
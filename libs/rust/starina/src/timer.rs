@@ -1,3 +1,6 @@
+use core::ptr;
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::Ordering;
 use core::time::Duration;
 
 use starina_types::error::ErrorCode;
@@ -19,13 +22,40 @@ impl Timer {
         Ok(Timer { handle })
     }
 
+    /// Arms the timer to expire once after `after`.
     pub fn set_timeout(&self, after: Duration) -> Result<(), ErrorCode> {
-        let duration_ns = after
+        self.set_deadline(now() + after)
+    }
+
+    /// Arms the timer to expire once at `deadline`.
+    pub fn set_deadline(&self, deadline: MonotonicTime) -> Result<(), ErrorCode> {
+        syscall::timer_set(self.handle.id(), deadline, None)
+    }
+
+    /// Arms the timer to expire at `first`, and then every `interval`.
+    ///
+    /// Unlike re-arming the timer by [`Timer::set_timeout`] on each
+    /// expiration, the deadlines don't drift.
+    pub fn set_periodic(&self, first: MonotonicTime, interval: Duration) -> Result<(), ErrorCode> {
+        let interval_ns = interval
             .as_nanos()
             .try_into()
             .map_err(|_| ErrorCode::InvalidArg)?;
 
-        syscall::timer_set(self.handle.id(), duration_ns)
+        if interval_ns == 0 {
+            return Err(ErrorCode::InvalidArg);
+        }
+
+        syscall::timer_set(self.handle.id(), first, Some(interval_ns))
+    }
+
+    /// Acknowledges the expirations, and returns how many times the timer
+    /// has expired since the last acknowledgement.
+    ///
+    /// The timer stays readable until this is called or the timer is re-armed.
+    /// A value greater than 1 means some periods have been missed (overrun).
+    pub fn ack(&self) -> Result<u64, ErrorCode> {
+        syscall::timer_ack(self.handle.id())
     }
 }
 
@@ -35,8 +65,45 @@ impl Handleable for Timer {
     }
 }
 
+/// The time page exported by the kernel, or null if it's not available.
+static TIME_PAGE: AtomicPtr<TimePage> = AtomicPtr::new(ptr::null_mut());
+
+pub(crate) fn set_time_page(time_page: *const TimePage) {
+    TIME_PAGE.store(time_page as *mut TimePage, Ordering::Relaxed);
+}
+
+pub(crate) fn time_page() -> Option<&'static TimePage> {
+    let time_page = TIME_PAGE.load(Ordering::Relaxed);
+    // SAFETY: The kernel keeps the time page alive forever.
+    unsafe { time_page.as_ref() }
+}
+
+/// Reads the timer counter directly.
+#[cfg(target_arch = "riscv64")]
+fn read_timer_counter() -> Option<u64> {
+    let ticks: u64;
+    unsafe {
+        core::arch::asm!("rdtime {}", out(reg) ticks);
+    }
+
+    Some(ticks)
+}
+
+#[cfg(not(target_arch = "riscv64"))]
+fn read_timer_counter() -> Option<u64> {
+    None
+}
+
 /// Get the current monotonic time since kernel boot.
 pub fn now() -> MonotonicTime {
+    // Compute the time from the timer counter if the kernel tells us how.
+    if let Some(time_page) = time_page()
+        && time_page.timer_freq() != 0
+        && let Some(ticks) = read_timer_counter()
+    {
+        return time_page.ticks_to_monotonic_time(ticks);
+    }
+
     // This should never fail.
     syscall::timer_now().unwrap()
 }
@@ -0,0 +1,50 @@
+//! Demand-paged memory.
+use starina_types::error::ErrorCode;
+use starina_types::handle::HandleId;
+use starina_types::handle::HandleRights;
+use starina_utils::alignment::is_aligned;
+
+use crate::handle::Handleable;
+use crate::handle::OwnedHandle;
+use crate::syscall;
+
+/// A virtual memory object (VMO), a memory region whose pages are allocated
+/// on first access.
+///
+/// Unlike [`Folio`](crate::folio::Folio), creating a VMO does not allocate
+/// memory, and its pages are not physically contiguous. Use it for large
+/// regions which are not fully used, such as thread stacks and guest RAM.
+///
+/// Map it with [`VmSpace`](crate::vmspace::VmSpace) or
+/// [`HvSpace`](crate::hvspace::HvSpace). Pages are zero-filled, and shared
+/// between all mappings of the same VMO.
+pub struct Vmo {
+    handle: OwnedHandle,
+}
+
+impl Vmo {
+    pub fn create(len: usize) -> Result<Vmo, ErrorCode> {
+        assert!(is_aligned(len, 0x1000));
+
+        let id = syscall::vmo_create(len)?;
+        let handle = OwnedHandle::from_raw(id);
+        Ok(Vmo { handle })
+    }
+
+    pub const fn from_handle(handle: OwnedHandle) -> Self {
+        Self { handle }
+    }
+
+    /// Creates a new handle to the same VMO, with rights restricted to
+    /// `rights`.
+    pub fn duplicate(&self, rights: HandleRights) -> Result<Vmo, ErrorCode> {
+        let handle = self.handle.duplicate(rights)?;
+        Ok(Vmo { handle })
+    }
+}
+
+impl Handleable for Vmo {
+    fn handle_id(&self) -> HandleId {
+        self.handle.id()
+    }
+}
@@ -7,9 +7,17 @@ use crate::folio::Folio;
 use crate::handle::Handleable;
 use crate::handle::OwnedHandle;
 use crate::syscall;
+use crate::vmo::Vmo;
 
 pub(crate) const SELF_VMSPACE: HandleId = HandleId::from_raw(0);
 
+/// A memory object which can be mapped into [`VmSpace`] and
+/// [`HvSpace`](crate::hvspace::HvSpace).
+pub trait Mappable: Handleable {}
+
+impl Mappable for Folio {}
+impl Mappable for Vmo {}
+
 #[derive(Debug)]
 pub struct VmSpace {
     handle: OwnedHandle,
@@ -17,7 +25,7 @@ pub struct VmSpace {
 
 impl VmSpace {
     pub fn map_anywhere_current(
-        folio: &Folio,
+        object: &impl Mappable,
         len: usize,
         prot: PageProtect,
     ) -> Result<VAddr, ErrorCode> {
@@ -25,12 +33,36 @@ impl VmSpace {
             SELF_VMSPACE,
             VAddr::new(0), /* anywhere */
             len,
-            folio.handle_id(),
+            object.handle_id(),
             0,
             prot,
         )?;
         Ok(vaddr)
     }
+
+    /// Maps `object` at `vaddr` in the current process's vmspace. Fails with
+    /// [`ErrorCode::AlreadyMapped`] if the range overlaps with an existing
+    /// mapping.
+    pub fn map_fixed_current(
+        vaddr: VAddr,
+        object: &impl Mappable,
+        len: usize,
+        prot: PageProtect,
+    ) -> Result<(), ErrorCode> {
+        syscall::vmspace_map(SELF_VMSPACE, vaddr, len, object.handle_id(), 0, prot)?;
+        Ok(())
+    }
+
+    /// Unmaps an object mapped at `vaddr` in the current process's vmspace.
+    pub fn unmap_current(vaddr: VAddr, len: usize) -> Result<(), ErrorCode> {
+        syscall::vmspace_unmap(SELF_VMSPACE, vaddr, len)
+    }
+
+    /// Changes the protection of `[vaddr, vaddr + len)` in the current
+    /// process's vmspace.
+    pub fn protect_current(vaddr: VAddr, len: usize, prot: PageProtect) -> Result<(), ErrorCode> {
+        syscall::vmspace_protect(SELF_VMSPACE, vaddr, len, prot)
+    }
 }
 
 impl Handleable for VmSpace {
@@ -47,7 +47,9 @@ define_errors!(
     AlreadyHeld = -25,
     TooSmall = -26,
     InUse = -27,
-    WouldBlock = -28
+    WouldBlock = -28,
+    InvalidAddress = -29,
+    TimedOut = -30
 );
 
 impl fmt::Display for ErrorCode {
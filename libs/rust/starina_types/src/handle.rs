@@ -1,6 +1,12 @@
+use core::fmt;
+use core::fmt::Write;
+use core::ops::BitAnd;
 use core::ops::BitOr;
 
+use serde::Serialize;
+
 use crate::error::ErrorCode;
+use crate::poll::Readiness;
 use crate::syscall::RetVal;
 
 /// A handle ID.
@@ -43,11 +49,39 @@ impl HandleRights {
     pub const MAP: HandleRights = HandleRights(1 << 3);
     pub const EXEC: HandleRights = HandleRights(1 << 4);
 
+    pub const ALL: HandleRights =
+        HandleRights(Self::READ.0 | Self::WRITE.0 | Self::POLL.0 | Self::MAP.0 | Self::EXEC.0);
+
+    pub fn from_raw_isize(raw: isize) -> Result<HandleRights, ErrorCode> {
+        match u8::try_from(raw) {
+            Ok(raw) if raw & !Self::ALL.0 == 0 => Ok(HandleRights(raw)),
+            _ => Err(ErrorCode::InvalidArg),
+        }
+    }
+
     pub fn is_capable(&self, required: HandleRights) -> bool {
         self.0 & required.0 == required.0
     }
 }
 
+impl fmt::Debug for HandleRights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (right, c) in [
+            (HandleRights::READ, 'R'),
+            (HandleRights::WRITE, 'W'),
+            (HandleRights::POLL, 'P'),
+            (HandleRights::MAP, 'M'),
+            (HandleRights::EXEC, 'X'),
+        ] {
+            if self.is_capable(right) {
+                f.write_char(c)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl BitOr for HandleRights {
     type Output = Self;
 
@@ -55,3 +89,184 @@ impl BitOr for HandleRights {
         HandleRights(self.0 | rhs.0)
     }
 }
+
+impl BitAnd for HandleRights {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        HandleRights(self.0 & rhs.0)
+    }
+}
+
+/// What a handle refers to, with details for debugging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HandleKind {
+    Channel {
+        /// The number of messages waiting to be received.
+        queue_len: usize,
+        /// Whether the peer channel is still open.
+        peer_alive: bool,
+    },
+    Poll,
+    Process,
+    Thread,
+    Timer {
+        armed: bool,
+        periodic: bool,
+        /// The number of expirations not yet acknowledged.
+        expirations: u64,
+    },
+    Interrupt,
+    Folio {
+        len: usize,
+    },
+    Vmo {
+        len: usize,
+        /// The bytes of pages allocated so far.
+        allocated_len: usize,
+    },
+    VmSpace,
+    HvSpace,
+    VCpu,
+}
+
+/// A handle in a process's handle table, reported by the `handle_list`
+/// system call as [`RawHandleInfo`].
+#[derive(Debug, Clone, Copy)]
+pub struct HandleInfo {
+    pub id: HandleId,
+    pub rights: HandleRights,
+    /// The current readiness. `None` if the handle doesn't have
+    /// [`HandleRights::POLL`].
+    pub readiness: Option<Readiness>,
+    pub kind: HandleKind,
+}
+
+const KIND_CHANNEL: u32 = 1;
+const KIND_POLL: u32 = 2;
+const KIND_PROCESS: u32 = 3;
+const KIND_THREAD: u32 = 4;
+const KIND_TIMER: u32 = 5;
+const KIND_INTERRUPT: u32 = 6;
+const KIND_FOLIO: u32 = 7;
+const KIND_VMO: u32 = 8;
+const KIND_VMSPACE: u32 = 9;
+const KIND_HVSPACE: u32 = 10;
+const KIND_VCPU: u32 = 11;
+
+/// [`HandleInfo`] in the `handle_list` system call's buffer.
+///
+/// Every byte is an explicit field, so that the kernel never copies
+/// uninitialized padding to the user space.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct RawHandleInfo {
+    pub id: i32,
+    pub rights: u8,
+    /// `1` if `readiness` is valid, `0` otherwise.
+    pub has_readiness: u8,
+    pub readiness: u8,
+    pub _pad0: u8,
+    /// What the handle refers to.
+    pub kind: u32,
+    pub _pad1: u32,
+    /// Details of the kind, in the order of [`HandleKind`]'s fields.
+    pub details: [u64; 3],
+}
+
+// No implicit padding.
+const _: () = assert!(size_of::<RawHandleInfo>() == 40);
+
+impl From<HandleInfo> for RawHandleInfo {
+    fn from(info: HandleInfo) -> Self {
+        let mut raw = RawHandleInfo {
+            id: info.id.as_raw(),
+            rights: info.rights.0,
+            ..Default::default()
+        };
+
+        if let Some(readiness) = info.readiness {
+            raw.has_readiness = 1;
+            raw.readiness = readiness.as_raw();
+        }
+
+        let (kind, details) = match info.kind {
+            HandleKind::Channel {
+                queue_len,
+                peer_alive,
+            } => (KIND_CHANNEL, [queue_len as u64, peer_alive as u64, 0]),
+            HandleKind::Poll => (KIND_POLL, [0; 3]),
+            HandleKind::Process => (KIND_PROCESS, [0; 3]),
+            HandleKind::Thread => (KIND_THREAD, [0; 3]),
+            HandleKind::Timer {
+                armed,
+                periodic,
+                expirations,
+            } => (KIND_TIMER, [armed as u64, periodic as u64, expirations]),
+            HandleKind::Interrupt => (KIND_INTERRUPT, [0; 3]),
+            HandleKind::Folio { len } => (KIND_FOLIO, [len as u64, 0, 0]),
+            HandleKind::Vmo { len, allocated_len } => {
+                (KIND_VMO, [len as u64, allocated_len as u64, 0])
+            }
+            HandleKind::VmSpace => (KIND_VMSPACE, [0; 3]),
+            HandleKind::HvSpace => (KIND_HVSPACE, [0; 3]),
+            HandleKind::VCpu => (KIND_VCPU, [0; 3]),
+        };
+
+        raw.kind = kind;
+        raw.details = details;
+        raw
+    }
+}
+
+impl TryFrom<RawHandleInfo> for HandleInfo {
+    type Error = ErrorCode;
+
+    fn try_from(raw: RawHandleInfo) -> Result<Self, ErrorCode> {
+        let [d0, d1, d2] = raw.details;
+        let kind = match raw.kind {
+            KIND_CHANNEL => {
+                HandleKind::Channel {
+                    queue_len: d0 as usize,
+                    peer_alive: d1 != 0,
+                }
+            }
+            KIND_POLL => HandleKind::Poll,
+            KIND_PROCESS => HandleKind::Process,
+            KIND_THREAD => HandleKind::Thread,
+            KIND_TIMER => {
+                HandleKind::Timer {
+                    armed: d0 != 0,
+                    periodic: d1 != 0,
+                    expirations: d2,
+                }
+            }
+            KIND_INTERRUPT => HandleKind::Interrupt,
+            KIND_FOLIO => HandleKind::Folio { len: d0 as usize },
+            KIND_VMO => {
+                HandleKind::Vmo {
+                    len: d0 as usize,
+                    allocated_len: d1 as usize,
+                }
+            }
+            KIND_VMSPACE => HandleKind::VmSpace,
+            KIND_HVSPACE => HandleKind::HvSpace,
+            KIND_VCPU => HandleKind::VCpu,
+            _ => return Err(ErrorCode::UnexpectedType),
+        };
+
+        let readiness = if raw.has_readiness != 0 {
+            Some(Readiness::from_raw(raw.readiness))
+        } else {
+            None
+        };
+
+        Ok(HandleInfo {
+            id: HandleId::from_raw(raw.id),
+            rights: HandleRights(raw.rights),
+            readiness,
+            kind,
+        })
+    }
+}
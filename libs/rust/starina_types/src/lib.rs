@@ -9,10 +9,13 @@ pub mod environ;
 pub mod error;
 pub mod handle;
 pub mod interrupt;
+pub mod log;
 pub mod message;
 pub mod poll;
+pub mod process;
 pub mod spec;
 pub mod syscall;
+pub mod thread;
 pub mod timer;
 pub mod vcpu;
 pub mod vmspace;
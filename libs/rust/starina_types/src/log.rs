@@ -0,0 +1,19 @@
+/// The maximum length of a kernel log record in bytes. Longer lines are
+/// split into multiple records.
+///
+/// A buffer passed to `log_read` must be at least this long to make
+/// progress.
+pub const LOG_RECORD_LEN_MAX: usize = 256;
+
+/// The result of reading the kernel log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(C)]
+pub struct LogRead {
+    /// The number of bytes written into the buffer.
+    pub len: usize,
+    /// The cursor to continue reading from.
+    pub next_cursor: u64,
+    /// The number of records evicted from the kernel log before they could
+    /// be read from the given cursor.
+    pub dropped: u64,
+}
@@ -4,6 +4,21 @@ use crate::syscall::RetVal;
 pub const MESSAGE_NUM_HANDLES_MAX: usize = 3;
 pub const MESSAGE_DATA_LEN_MAX: usize = 4 * 1024;
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum MessageKind {
+    Connect = 1,
+    Open = 3,
+    OpenReply = 4,
+    Data = 5,
+    Abort = 7,
+    Error = 8,
+}
+
+/// Set in [`MessageInfo`] if the message is a reply to a call. The data of a
+/// reply starts with the call ID.
+const REPLY_BIT: i32 = 1 << 30;
+
 /// The message metadata.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 #[repr(transparent)]
@@ -15,6 +30,11 @@ impl MessageInfo {
         MessageInfo((kind << 18) | ((num_handles as i32) << 16) | (data_len as i32))
     }
 
+    /// Marks the message as a reply to a call.
+    pub const fn as_reply(self) -> Self {
+        MessageInfo(self.0 | REPLY_BIT)
+    }
+
     pub fn from_raw_isize(raw: isize) -> Result<Self, ErrorCode> {
         match i32::try_from(raw) {
             Ok(raw) if raw >= 0 => Ok(MessageInfo(raw)),
@@ -27,7 +47,13 @@ impl MessageInfo {
     }
 
     pub fn kind(self) -> usize {
-        (self.0 >> 18) as usize
+        ((self.0 & !REPLY_BIT) >> 18) as usize
+    }
+
+    /// Whether the message is a reply to a call. The data of a reply starts
+    /// with the call ID.
+    pub fn is_reply(self) -> bool {
+        self.0 & REPLY_BIT != 0
     }
 
     pub fn data_len(self) -> usize {
@@ -6,6 +6,7 @@ use core::ops::BitOr;
 use core::ops::BitOrAssign;
 
 use crate::error::ErrorCode;
+use crate::handle::HandleId;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 #[repr(transparent)]
@@ -39,6 +40,10 @@ impl Readiness {
         }
     }
 
+    pub const fn as_raw(&self) -> u8 {
+        self.0
+    }
+
     pub fn as_isize(&self) -> isize {
         self.0 as isize
     }
@@ -99,3 +104,65 @@ impl BitAndAssign for Readiness {
         self.0 &= rhs.0;
     }
 }
+
+/// How a poll reports readiness of an object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum PollMode {
+    /// Report the object as long as it is ready.
+    #[default]
+    Level = 0,
+    /// Report the object once each time it becomes ready.
+    Edge = 1,
+    /// Report the object once, and then stop reporting it until the interests
+    /// are updated by `poll_update`.
+    OneShot = 2,
+}
+
+impl PollMode {
+    pub fn from_raw_isize(raw: isize) -> Result<PollMode, ErrorCode> {
+        match raw {
+            0 => Ok(PollMode::Level),
+            1 => Ok(PollMode::Edge),
+            2 => Ok(PollMode::OneShot),
+            _ => Err(ErrorCode::InvalidArg),
+        }
+    }
+
+    pub fn as_isize(&self) -> isize {
+        *self as isize
+    }
+}
+
+/// A ready event filled by `poll_wait_many`.
+///
+/// The padding is an explicit field, so that the kernel never copies
+/// uninitialized bytes to the user space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct PollEvent {
+    handle_id: HandleId,
+    readiness: Readiness,
+    _pad: [u8; 3],
+}
+
+// No implicit padding.
+const _: () = assert!(size_of::<PollEvent>() == 8);
+
+impl PollEvent {
+    pub const fn new(handle_id: HandleId, readiness: Readiness) -> PollEvent {
+        PollEvent {
+            handle_id,
+            readiness,
+            _pad: [0; 3],
+        }
+    }
+
+    pub const fn handle_id(&self) -> HandleId {
+        self.handle_id
+    }
+
+    pub const fn readiness(&self) -> Readiness {
+        self.readiness
+    }
+}
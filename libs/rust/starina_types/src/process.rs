@@ -0,0 +1,21 @@
+/// Memory consumed by a process and its child processes, reported by the
+/// kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(C)]
+pub struct MemoryUsage {
+    /// Bytes of folios and demand-paged memory (VMO pages, thread stacks)
+    /// allocated for the process.
+    pub folio_bytes: usize,
+    /// Bytes of kernel objects (channels, polls, threads, ...) created by
+    /// the process.
+    pub object_bytes: usize,
+    /// The limit of the total bytes, or `None` if unlimited. A child
+    /// process is also bounded by its creator's limit.
+    pub limit: Option<usize>,
+}
+
+impl MemoryUsage {
+    pub const fn total(&self) -> usize {
+        self.folio_bytes + self.object_bytes
+    }
+}
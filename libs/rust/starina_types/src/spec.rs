@@ -1,10 +1,24 @@
 use crate::environ::Environ;
+use crate::thread::Priority;
 
 #[derive(Debug)]
 pub struct AppSpec {
     pub name: &'static str,
     pub env: &'static [EnvItem],
     pub exports: &'static [ExportItem],
+    /// The priority of the app's main thread. Threads spawned by the app
+    /// inherit the priority of the spawning thread.
+    pub priority: Priority,
+    /// The maximum bytes of folios, demand-paged memory, and kernel objects
+    /// the app can allocate. Allocations beyond it fail with
+    /// `ErrorCode::OutOfMemory`. `None` means unlimited.
+    ///
+    /// It also bounds processes created by the app. The app's own heap is not
+    /// counted: in-kernel apps share the kernel's heap allocator.
+    pub memory_limit: Option<usize>,
+    /// The maximum number of handles the app can have at once. `None` means
+    /// the kernel's default.
+    pub handle_limit: Option<usize>,
     pub main: fn(env: Environ),
 }
 
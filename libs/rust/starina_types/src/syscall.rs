@@ -5,6 +5,7 @@ use crate::error::ErrorCode;
 use crate::handle::HandleId;
 use crate::poll::Readiness;
 use crate::timer::MonotonicTime;
+use crate::timer::TimePage;
 
 pub const SYS_LOG_WRITE: u8 = 0;
 pub const SYS_HANDLE_CLOSE: u8 = 1;
@@ -33,6 +34,21 @@ pub const SYS_TIMER_CREATE: u8 = 23;
 pub const SYS_TIMER_SET: u8 = 24;
 pub const SYS_TIMER_NOW: u8 = 25;
 pub const SYS_LOG_READ: u8 = 26;
+pub const SYS_PROCESS_CREATE: u8 = 27;
+pub const SYS_PROCESS_EXIT: u8 = 28;
+pub const SYS_PROCESS_KILL: u8 = 29;
+pub const SYS_PROCESS_EXIT_CODE: u8 = 30;
+pub const SYS_THREAD_SET_PRIORITY: u8 = 31;
+pub const SYS_HANDLE_DUPLICATE: u8 = 32;
+pub const SYS_CHANNEL_CALL: u8 = 33;
+pub const SYS_POLL_WAIT_MANY: u8 = 34;
+pub const SYS_TIMER_ACK: u8 = 35;
+pub const SYS_CLOCK_REALTIME: u8 = 36;
+pub const SYS_VMSPACE_UNMAP: u8 = 37;
+pub const SYS_VMSPACE_PROTECT: u8 = 38;
+pub const SYS_VMO_CREATE: u8 = 39;
+pub const SYS_PROCESS_MEMORY_USAGE: u8 = 40;
+pub const SYS_HANDLE_LIST: u8 = 41;
 
 #[repr(C)]
 pub struct VsyscallPage {
@@ -41,6 +57,7 @@ pub struct VsyscallPage {
     pub main: fn(environ: Environ),
     pub name: *const u8,
     pub name_len: usize,
+    pub time_page: *const TimePage,
 }
 
 /// SAFETY: VsyscallPage is pre-allocated, the same across threads, and immutable.
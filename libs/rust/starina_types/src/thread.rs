@@ -0,0 +1,47 @@
+use crate::error::ErrorCode;
+
+/// The scheduling priority of a thread. Runnable threads with a higher
+/// priority are always scheduled first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct Priority(u8);
+
+impl Priority {
+    /// The number of priority levels.
+    pub const NUM_LEVELS: usize = 8;
+
+    pub const MIN: Priority = Priority(0);
+    pub const LOW: Priority = Priority(2);
+    pub const NORMAL: Priority = Priority(4);
+    pub const HIGH: Priority = Priority(6);
+    pub const MAX: Priority = Priority(Self::NUM_LEVELS as u8 - 1);
+
+    pub const fn new(level: u8) -> Option<Priority> {
+        if (level as usize) < Self::NUM_LEVELS {
+            Some(Priority(level))
+        } else {
+            None
+        }
+    }
+
+    pub fn from_raw_isize(raw: isize) -> Result<Priority, ErrorCode> {
+        match u8::try_from(raw) {
+            Ok(level) => Priority::new(level).ok_or(ErrorCode::InvalidArg),
+            Err(_) => Err(ErrorCode::InvalidArg),
+        }
+    }
+
+    pub const fn as_u8(&self) -> u8 {
+        self.0
+    }
+
+    pub const fn as_usize(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Priority::NORMAL
+    }
+}
@@ -1,4 +1,9 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use core::ops::Add;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+use core::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(transparent)]
 pub struct MonotonicTime(u64);
 
@@ -15,3 +20,62 @@ impl MonotonicTime {
         self.0 / 1_000_000
     }
 }
+
+impl Add<Duration> for MonotonicTime {
+    type Output = MonotonicTime;
+
+    fn add(self, rhs: Duration) -> MonotonicTime {
+        let nanos = u64::try_from(rhs.as_nanos()).unwrap_or(u64::MAX);
+        MonotonicTime(self.0.saturating_add(nanos))
+    }
+}
+
+/// Clock parameters exported by the kernel, to read the time without system
+/// calls.
+#[repr(C)]
+pub struct TimePage {
+    /// The frequency of the timer counter in Hz, or zero if it's not
+    /// initialized yet.
+    timer_freq: AtomicU64,
+    /// The wall-clock time at boot in nanoseconds since the UNIX epoch, or
+    /// zero if it's unknown.
+    realtime_at_boot: AtomicU64,
+}
+
+impl TimePage {
+    pub const fn new() -> TimePage {
+        TimePage {
+            timer_freq: AtomicU64::new(0),
+            realtime_at_boot: AtomicU64::new(0),
+        }
+    }
+
+    pub fn timer_freq(&self) -> u64 {
+        self.timer_freq.load(Ordering::Relaxed)
+    }
+
+    pub fn set_timer_freq(&self, freq: u64) {
+        self.timer_freq.store(freq, Ordering::Relaxed);
+    }
+
+    pub fn realtime_at_boot(&self) -> u64 {
+        self.realtime_at_boot.load(Ordering::Relaxed)
+    }
+
+    pub fn set_realtime_at_boot(&self, nanos: u64) {
+        self.realtime_at_boot.store(nanos, Ordering::Relaxed);
+    }
+
+    /// Converts a timer counter value into the monotonic time.
+    pub fn ticks_to_monotonic_time(&self, ticks: u64) -> MonotonicTime {
+        // Use u128 not to overflow in the multiplication.
+        let nanos = (ticks as u128 * 1_000_000_000) / self.timer_freq() as u128;
+        MonotonicTime(nanos.try_into().unwrap_or(u64::MAX))
+    }
+}
+
+impl Default for TimePage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
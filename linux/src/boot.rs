@@ -1,19 +1,20 @@
 use core::time::Duration;
 
 use starina::channel::Channel;
-use starina::handle::Handleable;
+use starina::error::ErrorCode;
 use starina::poll::Poll;
-use starina::poll::Readiness;
 use starina::prelude::*;
 use starina::sync::Arc;
 use starina::sync::Mutex;
-use starina::timer::Timer;
+use starina::timer;
 use starina::vcpu::VCpu;
 use starina_types::address::GPAddr;
 use starina_types::vcpu::VCpuExit;
 use starina_utils::static_assert;
 
 use crate::fs::FileSystem;
+use crate::goldfish_rtc::GOLDFISH_RTC_MMIO_SIZE;
+use crate::goldfish_rtc::GoldfishRtc;
 use crate::guest_memory::GuestMemory;
 use crate::guest_net::GuestNet;
 use crate::interrupt::IrqTrigger;
@@ -39,8 +40,10 @@ const PLIC_MMIO_SIZE: usize = plic_mmio_size(NUM_CPUS);
 static_assert!(PLIC_BASE_ADDR.as_usize() + PLIC_MMIO_SIZE <= VIRTIO_FS_ADDR.as_usize());
 const VIRTIO_FS_ADDR: GPAddr = GPAddr::new(0x0b00_0000);
 const VIRTIO_NET_ADDR: GPAddr = GPAddr::new(0x0b00_1000);
+const GOLDFISH_RTC_ADDR: GPAddr = GPAddr::new(0x0b00_2000);
 const VIRTIO_FS_IRQ: u8 = 1;
 const VIRTIO_NET_IRQ: u8 = 2;
+const GOLDFISH_RTC_IRQ: u8 = 3;
 const GUEST_RAM_ADDR: GPAddr = GPAddr::new(0x8000_0000);
 
 pub fn boot_linux(fs: FileSystem, ports: &[Port], tcpip_ch: Channel) {
@@ -78,6 +81,7 @@ pub fn boot_linux(fs: FileSystem, ports: &[Port], tcpip_ch: Channel) {
             (VIRTIO_FS_ADDR, VIRTIO_FS_IRQ),
             (VIRTIO_NET_ADDR, VIRTIO_NET_IRQ),
         ],
+        (GOLDFISH_RTC_ADDR, GOLDFISH_RTC_IRQ),
         &guest_net,
     )
     .expect("failed to build device tree");
@@ -113,16 +117,17 @@ pub fn boot_linux(fs: FileSystem, ports: &[Port], tcpip_ch: Channel) {
     ));
     bus.add_device(VIRTIO_FS_ADDR, VIRTIO_MMIO_SIZE, virtio_mmio_fs);
     bus.add_device(VIRTIO_NET_ADDR, VIRTIO_MMIO_SIZE, virtio_mmio_net.clone());
+    bus.add_device(
+        GOLDFISH_RTC_ADDR,
+        GOLDFISH_RTC_MMIO_SIZE,
+        Arc::new(GoldfishRtc::new()),
+    );
 
     let mut port_forwarder =
         port_forward::Builder::new(tcpip_ch, guest_net.clone(), virtio_mmio_net.clone(), ports)
             .build();
 
-    let timer = Timer::new().unwrap();
-    let timer_poll = Poll::new().unwrap();
-    timer_poll
-        .add(timer.handle_id(), (), Readiness::READABLE)
-        .unwrap();
+    let idle_poll: Poll<()> = Poll::new().unwrap();
 
     // Fill registers that Linux expects:
     //
@@ -144,9 +149,12 @@ pub fn boot_linux(fs: FileSystem, ports: &[Port], tcpip_ch: Channel) {
         match exit {
             VCpuExit::Reboot => break,
             VCpuExit::Idle => {
-                // FIXME:
-                timer.set_timeout(Duration::from_millis(1)).unwrap();
-                timer_poll.wait().unwrap();
+                // FIXME: Wake up on guest timer and device interrupts instead.
+                let deadline = timer::now() + Duration::from_millis(1);
+                match idle_poll.wait_until(deadline) {
+                    Ok(_) | Err(ErrorCode::TimedOut) => {}
+                    Err(err) => panic!("failed to wait for idle: {:?}", err),
+                }
             }
             VCpuExit::LoadPageFault { gpaddr, data } => {
                 bus.read(&mut memory, gpaddr, data).unwrap();
@@ -0,0 +1,66 @@
+//! An emulated goldfish RTC, which gives the guest the wall-clock time.
+//!
+//! <https://android.googlesource.com/platform/external/qemu/+/master/docs/GOLDFISH-VIRTUAL-HARDWARE.TXT>
+use starina::prelude::*;
+use starina::sync::Mutex;
+use starina::time::SystemTime;
+
+use crate::guest_memory::GuestMemory;
+use crate::mmio;
+
+pub const GOLDFISH_RTC_MMIO_SIZE: usize = 0x1000;
+
+const REG_TIME_LOW: u64 = 0x00;
+const REG_TIME_HIGH: u64 = 0x04;
+
+pub struct GoldfishRtc {
+    /// The upper 32 bits of the time, latched when `TIME_LOW` is read.
+    time_high: Mutex<u32>,
+}
+
+impl GoldfishRtc {
+    pub fn new() -> Self {
+        Self {
+            time_high: Mutex::new(0),
+        }
+    }
+}
+
+impl mmio::Device for GoldfishRtc {
+    fn mmio_read(
+        &self,
+        _memory: &mut GuestMemory,
+        offset: u64,
+        dst: &mut [u8],
+    ) -> Result<(), mmio::Error> {
+        if dst.len() != 4 {
+            panic!("unsupported goldfish-rtc read width: {:x}", dst.len());
+        }
+
+        let value = match offset {
+            REG_TIME_LOW => {
+                // If we don't know the time, the guest starts from the epoch.
+                let now = SystemTime::now().unwrap_or(SystemTime::UNIX_EPOCH);
+                *self.time_high.lock() = (now.as_nanos() >> 32) as u32;
+                now.as_nanos() as u32
+            }
+            REG_TIME_HIGH => *self.time_high.lock(),
+            // Alarms are not supported.
+            _ => 0,
+        };
+
+        dst.copy_from_slice(&value.to_ne_bytes());
+        Ok(())
+    }
+
+    fn mmio_write(
+        &self,
+        _memory: &mut GuestMemory,
+        offset: u64,
+        _src: &[u8],
+    ) -> Result<(), mmio::Error> {
+        // The guest can't change the time nor set alarms.
+        trace!("ignored goldfish-rtc write: offset={offset:x}");
+        Ok(())
+    }
+}
@@ -6,17 +6,17 @@ use core::slice;
 use starina::address::GPAddr;
 use starina::address::VAddr;
 use starina::error::ErrorCode;
-use starina::folio::Folio;
 use starina::hvspace::HvSpace;
 use starina::prelude::*;
+use starina::vmo::Vmo;
 use starina::vmspace::PageProtect;
 use starina::vmspace::VmSpace;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("failed to allocate folio: {0}")]
-    AllocFolio(ErrorCode),
+    #[error("failed to allocate memory: {0}")]
+    AllocVmo(ErrorCode),
     #[error("failed to create hypervisor space: {0}")]
     CreateHvSpace(ErrorCode),
     #[error("failed to map memory: {0}")]
@@ -40,7 +40,7 @@ pub struct GuestMemory {
     start: GPAddr,
     end: GPAddr,
     size: usize,
-    _folio: Folio,
+    _vmo: Vmo,
     vaddr: VAddr,
     free_offset: usize,
 }
@@ -49,23 +49,24 @@ impl GuestMemory {
     pub fn new(start: GPAddr, size: usize) -> Result<Self, Error> {
         let end = start.checked_add(size).unwrap();
 
-        // Allocate a virtually-contiguous memory region (folio).
-        let folio = Folio::alloc(size).map_err(Error::AllocFolio)?;
+        // Allocate a memory region. Its pages are allocated when the guest
+        // or the VMM touches them.
+        let vmo = Vmo::create(size).map_err(Error::AllocVmo)?;
 
-        // Map the folio into the current (VMM's) address space.
+        // Map it into the current (VMM's) address space.
         let vaddr = VmSpace::map_anywhere_current(
-            &folio,
+            &vmo,
             size,
             PageProtect::READABLE | PageProtect::WRITEABLE,
         )
         .map_err(Error::VmSpaceMap)?;
 
-        // Create a guest address space and map the folio into it.
+        // Create a guest address space and map the same memory into it.
         let hvspace = HvSpace::new().map_err(Error::CreateHvSpace)?;
         hvspace
             .map(
                 start,
-                &folio,
+                &vmo,
                 size,
                 PageProtect::READABLE | PageProtect::WRITEABLE | PageProtect::EXECUTABLE,
             )
@@ -76,7 +77,7 @@ impl GuestMemory {
             start,
             end,
             size,
-            _folio: folio,
+            _vmo: vmo,
             vaddr,
             free_offset: 0,
         })
@@ -102,14 +103,14 @@ impl GuestMemory {
     }
 
     fn slice(&self) -> &[u8] {
-        // SAFETY: The folio is mapped to the current vmspace, and folio
-        // is kept alive as long as `self` is alive.
+        // SAFETY: The VMO is mapped to the current vmspace, and it's kept
+        // alive as long as `self` is alive.
         unsafe { slice::from_raw_parts(self.vaddr.as_ptr(), self.size) }
     }
 
     fn slice_mut(&self) -> &mut [u8] {
-        // SAFETY: The folio is mapped to the current vmspace, and folio
-        // is kept alive as long as `self` is alive.
+        // SAFETY: The VMO is mapped to the current vmspace, and it's kept
+        // alive as long as `self` is alive.
         unsafe { slice::from_raw_parts_mut(self.vaddr.as_mut_ptr(), self.size) }
     }
 
@@ -173,3 +174,12 @@ impl GuestMemory {
         Ok(())
     }
 }
+
+impl Drop for GuestMemory {
+    fn drop(&mut self) {
+        // The memory is freed once both the VMM and the guest mappings are gone.
+        if let Err(err) = VmSpace::unmap_current(self.vaddr, self.size) {
+            debug_warn!("failed to unmap guest memory: {:?}", err);
+        }
+    }
+}
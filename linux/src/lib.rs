@@ -3,6 +3,7 @@
 mod boot;
 mod command;
 mod fs;
+mod goldfish_rtc;
 mod guest_memory;
 mod guest_net;
 mod interrupt;
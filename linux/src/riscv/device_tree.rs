@@ -2,11 +2,13 @@ use starina::address::GPAddr;
 use starina::prelude::*;
 use vm_fdt::FdtWriter;
 
+use crate::goldfish_rtc::GOLDFISH_RTC_MMIO_SIZE;
 use crate::guest_net::GuestNet;
 use crate::virtio::device::VIRTIO_MMIO_SIZE;
 
 const TIMEBASE_FREQ: u32 = 10000000;
 
+#[allow(clippy::too_many_arguments)]
 pub fn build_fdt(
     num_cpus: u32,
     guest_ram_start: GPAddr,
@@ -14,6 +16,7 @@ pub fn build_fdt(
     plic_base: GPAddr,
     plic_mmio_size: usize,
     virtio_mmios: &[(GPAddr, u8 /* irq */)],
+    rtc: (GPAddr, u8 /* irq */),
     guest_net: &GuestNet,
 ) -> Result<Vec<u8>, vm_fdt::Error> {
     let mut fdt = FdtWriter::new()?;
@@ -117,6 +120,21 @@ pub fn build_fdt(
         fdt.end_node(virtio_mmio_node)?;
     }
 
+    // Goldfish RTC.
+    let (rtc_gpaddr, rtc_irq) = rtc;
+    let rtc_node = fdt.begin_node(&format!("rtc@{:x}", rtc_gpaddr.as_usize()))?;
+    fdt.property_string("compatible", "google,goldfish-rtc")?;
+    fdt.property_array_u64(
+        "reg",
+        &[
+            rtc_gpaddr.as_usize().try_into().unwrap(),
+            GOLDFISH_RTC_MMIO_SIZE as u64,
+        ],
+    )?;
+    fdt.property_array_u32("interrupts", &[rtc_irq as u32])?;
+    fdt.property_u32("interrupt-parent", PLIC_PHANDLE)?;
+    fdt.end_node(rtc_node)?;
+
     fdt.end_node(soc_node)?;
     fdt.end_node(root_node)?;
     fdt.finish()
@@ -3,7 +3,7 @@ set -ue
 
 setup_macos() {
     set -x
-    brew install make llvm lld zig findutils gnu-sed libelf skopeo squashfs
+    brew install make llvm lld zig findutils gnu-sed libelf skopeo squashfs qemu
 }
 
 setup_linux() {
@@ -17,7 +17,7 @@ setup_linux() {
     sudo apt-get install -y \
         build-essential clang llvm \
         curl flex bison bc cpio lz4 libelf-dev \
-        lld skopeo squashfs-tools
+        lld skopeo squashfs-tools qemu-system-misc
 
     sudo snap install zig --classic --beta
 }